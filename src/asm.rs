@@ -0,0 +1,749 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    error::AsmError,
+    inst::{inst_to_hex, Inst},
+};
+
+// Nested includes deeper than this are most likely a cycle
+const MAX_INCLUDE_DEPTH: usize = 16;
+// Constants defined in terms of each other deeper than this are most likely a cycle
+const MAX_EVAL_DEPTH: usize = 64;
+
+// Operators by precedence, lowest first
+const BINARY_OPS: [&[&str]; 6] = [
+    &["|"],
+    &["^"],
+    &["&"],
+    &["<<", ">>"],
+    &["+", "-"],
+    &["*", "/", "%"],
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Num(i64),
+    Ident(String),
+    Op(&'static str),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Expr {
+    Num(i64),
+    Symbol(String),
+    // $, the address of the current line
+    Here,
+    Unary(&'static str, Box<Expr>),
+    Binary(&'static str, Box<Expr>, Box<Expr>),
+}
+
+// Decimal, 0x hex or 0b binary
+fn parse_number(text: &str) -> Result<i64, String> {
+    let clean = text.replace('_', "");
+    let lower = clean.to_ascii_lowercase();
+    let parsed = if let Some(hex) = lower.strip_prefix("0x") {
+        i64::from_str_radix(hex, 16)
+    } else if let Some(bin) = lower.strip_prefix("0b") {
+        i64::from_str_radix(bin, 2)
+    } else {
+        lower.parse()
+    };
+    parsed.map_err(|_| format!("invalid number: {text}"))
+}
+
+fn is_ident_start(c: char) -> bool {
+    c.is_ascii_alphabetic() || c == '_' || c == '.'
+}
+
+fn is_ident(text: &str) -> bool {
+    let mut chars = text.chars();
+    chars.next().is_some_and(is_ident_start)
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.')
+}
+
+fn tokenize(text: &str) -> Result<Vec<Token>, String> {
+    const OPS: [&str; 14] = [
+        "<<", ">>", "|", "^", "&", "+", "-", "*", "/", "%", "~", "(", ")", "$",
+    ];
+    let mut tokens = Vec::new();
+    let mut rest = text.trim_start();
+    while let Some(c) = rest.chars().next() {
+        let len = if c.is_ascii_digit() || is_ident_start(c) {
+            let len = rest
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_' || c == '.'))
+                .unwrap_or(rest.len());
+            let word = &rest[..len];
+            if c.is_ascii_digit() {
+                tokens.push(Token::Num(parse_number(word)?));
+            } else {
+                tokens.push(Token::Ident(word.to_string()));
+            }
+            len
+        } else if c == '\'' {
+            // Character literal, 'A'
+            let mut chars = rest.chars();
+            match (chars.next(), chars.next(), chars.next()) {
+                (_, Some(ch), Some('\'')) if ch.is_ascii() => tokens.push(Token::Num(ch as i64)),
+                _ => return Err(format!("invalid character literal in: {text}")),
+            }
+            3
+        } else {
+            match OPS.iter().find(|op| rest.starts_with(**op)) {
+                Some(op) => {
+                    tokens.push(Token::Op(op));
+                    op.len()
+                }
+                None => return Err(format!("unexpected character '{c}' in: {text}")),
+            }
+        };
+        rest = rest[len..].trim_start();
+    }
+    Ok(tokens)
+}
+
+fn parse_expr(text: &str) -> Result<Expr, String> {
+    let tokens = tokenize(text)?;
+    let mut pos = 0;
+    let expr = parse_binary(&tokens, &mut pos, 0)?;
+    match tokens.get(pos) {
+        None => Ok(expr),
+        Some(_) => Err(format!("unexpected trailing input in: {text}")),
+    }
+}
+
+fn parse_binary(tokens: &[Token], pos: &mut usize, level: usize) -> Result<Expr, String> {
+    if level == BINARY_OPS.len() {
+        return parse_unary(tokens, pos);
+    }
+    let mut lhs = parse_binary(tokens, pos, level + 1)?;
+    while let Some(Token::Op(op)) = tokens.get(*pos) {
+        if !BINARY_OPS[level].contains(op) {
+            break;
+        }
+        *pos += 1;
+        let rhs = parse_binary(tokens, pos, level + 1)?;
+        lhs = Expr::Binary(op, Box::new(lhs), Box::new(rhs));
+    }
+    Ok(lhs)
+}
+
+fn parse_unary(tokens: &[Token], pos: &mut usize) -> Result<Expr, String> {
+    let token = tokens.get(*pos).ok_or("unexpected end of expression")?;
+    *pos += 1;
+    match token {
+        Token::Num(n) => Ok(Expr::Num(*n)),
+        Token::Ident(name) => Ok(Expr::Symbol(name.clone())),
+        Token::Op("$") => Ok(Expr::Here),
+        Token::Op(op @ ("-" | "~" | "+")) => {
+            Ok(Expr::Unary(op, Box::new(parse_unary(tokens, pos)?)))
+        }
+        Token::Op("(") => {
+            let expr = parse_binary(tokens, pos, 0)?;
+            match tokens.get(*pos) {
+                Some(Token::Op(")")) => {
+                    *pos += 1;
+                    Ok(expr)
+                }
+                _ => Err(String::from("missing )")),
+            }
+        }
+        Token::Op(op) => Err(format!("unexpected '{op}'")),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Operand {
+    V(u8),
+    I,
+    // [I]
+    IndI,
+    Dt,
+    St,
+    K,
+    F,
+    Hf,
+    B,
+    R,
+    // LONG <expr>, the 16 bit address of LD I, LONG
+    Long(Expr),
+    Expr(Expr),
+}
+
+fn parse_operand(text: &str) -> Result<Operand, String> {
+    let upper = text.to_ascii_uppercase();
+    let operand = match upper.as_str() {
+        "I" => Operand::I,
+        "[I]" => Operand::IndI,
+        "DT" => Operand::Dt,
+        "ST" => Operand::St,
+        "K" => Operand::K,
+        "F" => Operand::F,
+        "HF" => Operand::Hf,
+        "B" => Operand::B,
+        "R" => Operand::R,
+        _ => match upper.strip_prefix('V').map(|x| u8::from_str_radix(x, 16)) {
+            Some(Ok(x)) if upper.len() == 2 => Operand::V(x),
+            _ => match upper.strip_prefix("LONG") {
+                Some(rest) if rest.starts_with(char::is_whitespace) => {
+                    Operand::Long(parse_expr(&text[4..])?)
+                }
+                _ => Operand::Expr(parse_expr(text)?),
+            },
+        },
+    };
+    Ok(operand)
+}
+
+// Names that parse as something other than an expression operand
+fn is_reserved(name: &str) -> bool {
+    !matches!(parse_operand(name), Ok(Operand::Expr(_))) || name.eq_ignore_ascii_case("long")
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum DataItem {
+    Expr(Expr),
+    Str(Vec<u8>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Kind {
+    Inst {
+        mnemonic: String,
+        operands: Vec<Operand>,
+    },
+    Bytes(Vec<DataItem>),
+    Words(Vec<Expr>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Statement {
+    file: String,
+    line: usize,
+    addr: u16,
+    kind: Kind,
+}
+
+// Splits at commas outside of string and character literals
+fn split_args(text: &str) -> Vec<String> {
+    let mut args = Vec::new();
+    let mut current = String::new();
+    let mut quote: Option<char> = None;
+    for c in text.chars() {
+        match (c, quote) {
+            (',', None) => args.push(std::mem::take(&mut current).trim().to_string()),
+            ('"' | '\'', None) => {
+                quote = Some(c);
+                current.push(c);
+            }
+            (_, Some(q)) if c == q => {
+                quote = None;
+                current.push(c);
+            }
+            _ => current.push(c),
+        }
+    }
+    if !current.trim().is_empty() || !args.is_empty() {
+        args.push(current.trim().to_string());
+    }
+    args
+}
+
+// Strips the address and raw byte columns of a chip8-disasm listing, "0200: 00E0      CLS"
+fn strip_listing_columns(line: &str) -> &str {
+    let is_hex = |text: &str| {
+        !text.is_empty()
+            && text
+                .bytes()
+                .all(|b| b.is_ascii_digit() || (b'A'..=b'F').contains(&b))
+    };
+    let columns = line
+        .split_once(": ")
+        .and_then(|(addr, rest)| Some((addr, rest.split_once("  ")?)));
+    match columns {
+        Some((addr, (raw, text)))
+            if addr.len() == 4 && is_hex(addr) && is_hex(raw) && raw.len() % 2 == 0 =>
+        {
+            text
+        }
+        _ => line,
+    }
+}
+
+// Strips a ; comment that is not inside a literal
+fn strip_comment(line: &str) -> &str {
+    let mut quote: Option<char> = None;
+    for (index, c) in line.char_indices() {
+        match (c, quote) {
+            (';', None) => return &line[..index],
+            ('"' | '\'', None) => quote = Some(c),
+            (_, Some(q)) if c == q => quote = None,
+            _ => {}
+        }
+    }
+    line
+}
+
+fn parse_string(text: &str) -> Option<Vec<u8>> {
+    let inner = text.strip_prefix('"')?.strip_suffix('"')?;
+    Some(inner.bytes().collect())
+}
+
+#[derive(Default)]
+struct Assembler {
+    origin: u16,
+    addr: u32,
+    labels: HashMap<String, u16>,
+    // Constants with the address they were defined at, for $
+    constants: HashMap<String, (Expr, u16)>,
+    statements: Vec<Statement>,
+}
+
+impl Assembler {
+    fn define(&mut self, name: &str, file: &str, line: usize) -> Result<(), AsmError> {
+        let err = |message: String| AsmError {
+            file: file.to_string(),
+            line,
+            message,
+        };
+        if !is_ident(name) || is_reserved(name) {
+            return Err(err(format!("invalid symbol name: {name}")));
+        }
+        if self.labels.contains_key(name) || self.constants.contains_key(name) {
+            return Err(err(format!("symbol defined twice: {name}")));
+        }
+        Ok(())
+    }
+
+    // First pass, collects statements and assigns addresses to labels
+    fn load(&mut self, file: &str, source: &str, dir: &Path, depth: usize) -> Result<(), AsmError> {
+        for (index, raw) in source.lines().enumerate() {
+            let line = index + 1;
+            let err = |message: String| AsmError {
+                file: file.to_string(),
+                line,
+                message,
+            };
+            let mut rest = strip_listing_columns(strip_comment(raw).trim()).trim();
+
+            // Any number of labels before the statement
+            while let Some((word, after)) = rest
+                .split_once(char::is_whitespace)
+                .or(Some((rest, "")))
+                .filter(|(word, _)| word.ends_with(':'))
+            {
+                let name = &word[..word.len() - 1];
+                self.define(name, file, line)?;
+                let addr = u16::try_from(self.addr)
+                    .map_err(|_| err(String::from("address out of range")))?;
+                self.labels.insert(name.to_string(), addr);
+                rest = after.trim();
+            }
+            if rest.is_empty() {
+                continue;
+            }
+
+            let (word, args) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+            let args = args.trim();
+            let here = self.addr as u16;
+
+            if let Some((kw, value)) = args.split_once(char::is_whitespace) {
+                if kw.eq_ignore_ascii_case("equ") {
+                    self.define(word, file, line)?;
+                    let expr = parse_expr(value).map_err(err)?;
+                    self.constants.insert(word.to_string(), (expr, here));
+                    continue;
+                }
+            }
+
+            let (kind, size) = match word.to_ascii_uppercase().as_str() {
+                "ORG" => {
+                    let expr = parse_expr(args).map_err(err)?;
+                    let addr = self.eval(&expr, here, 0).map_err(err)?;
+                    if addr < self.addr as i64 || addr > u16::MAX as i64 {
+                        return Err(err(format!("ORG 0x{addr:X} is behind the current address")));
+                    }
+                    self.addr = addr as u32;
+                    continue;
+                }
+                "INCLUDE" => {
+                    if depth >= MAX_INCLUDE_DEPTH {
+                        return Err(err(String::from("includes nested too deeply")));
+                    }
+                    let name = parse_string(args)
+                        .and_then(|name| String::from_utf8(name).ok())
+                        .ok_or_else(|| err(format!("expected a quoted path: {args}")))?;
+                    let path = dir.join(name);
+                    let text = fs::read_to_string(&path)
+                        .map_err(|e| err(format!("{}: {e}", path.display())))?;
+                    let include_dir = path.parent().map(Path::to_path_buf).unwrap_or_default();
+                    self.load(&path.display().to_string(), &text, &include_dir, depth + 1)?;
+                    continue;
+                }
+                "DB" => {
+                    let mut items = Vec::new();
+                    let mut size = 0;
+                    for arg in split_args(args) {
+                        let item = match parse_string(&arg) {
+                            Some(bytes) => DataItem::Str(bytes),
+                            None => DataItem::Expr(parse_expr(&arg).map_err(err)?),
+                        };
+                        size += match &item {
+                            DataItem::Str(bytes) => bytes.len(),
+                            DataItem::Expr(_) => 1,
+                        };
+                        items.push(item);
+                    }
+                    (Kind::Bytes(items), size)
+                }
+                "DW" => {
+                    let words = split_args(args)
+                        .iter()
+                        .map(|arg| parse_expr(arg))
+                        .collect::<Result<Vec<_>, _>>()
+                        .map_err(err)?;
+                    let size = 2 * words.len();
+                    (Kind::Words(words), size)
+                }
+                mnemonic => {
+                    let operands = split_args(args)
+                        .iter()
+                        .map(|arg| parse_operand(arg))
+                        .collect::<Result<Vec<_>, _>>()
+                        .map_err(err)?;
+                    let size = if operands.iter().any(|op| matches!(op, Operand::Long(_))) {
+                        4
+                    } else {
+                        2
+                    };
+                    let kind = Kind::Inst {
+                        mnemonic: mnemonic.to_string(),
+                        operands,
+                    };
+                    (kind, size)
+                }
+            };
+            if self.addr + size as u32 > u16::MAX as u32 + 1 {
+                return Err(err(String::from("program does not fit below 0x10000")));
+            }
+            self.statements.push(Statement {
+                file: file.to_string(),
+                line,
+                addr: here,
+                kind,
+            });
+            self.addr += size as u32;
+        }
+        Ok(())
+    }
+
+    fn eval(&self, expr: &Expr, here: u16, depth: usize) -> Result<i64, String> {
+        if depth > MAX_EVAL_DEPTH {
+            return Err(String::from("constants refer to each other in a cycle"));
+        }
+        let value = match expr {
+            Expr::Num(n) => *n,
+            Expr::Here => here as i64,
+            Expr::Symbol(name) => match (self.labels.get(name), self.constants.get(name)) {
+                (Some(addr), _) => *addr as i64,
+                (None, Some((expr, at))) => self.eval(expr, *at, depth + 1)?,
+                (None, None) => return Err(format!("undefined symbol: {name}")),
+            },
+            Expr::Unary(op, expr) => {
+                let value = self.eval(expr, here, depth)?;
+                match *op {
+                    "-" => value.wrapping_neg(),
+                    "~" => !value,
+                    _ => value,
+                }
+            }
+            Expr::Binary(op, lhs, rhs) => {
+                let (lhs, rhs) = (self.eval(lhs, here, depth)?, self.eval(rhs, here, depth)?);
+                match *op {
+                    "|" => lhs | rhs,
+                    "^" => lhs ^ rhs,
+                    "&" => lhs & rhs,
+                    "<<" => lhs.checked_shl(rhs as u32).unwrap_or(0),
+                    ">>" => lhs.checked_shr(rhs as u32).unwrap_or(0),
+                    "+" => lhs.wrapping_add(rhs),
+                    "-" => lhs.wrapping_sub(rhs),
+                    "*" => lhs.wrapping_mul(rhs),
+                    "/" => lhs.checked_div(rhs).ok_or("division by zero")?,
+                    _ => lhs.checked_rem(rhs).ok_or("division by zero")?,
+                }
+            }
+        };
+        Ok(value)
+    }
+
+    fn ranged(&self, expr: &Expr, here: u16, min: i64, max: i64) -> Result<i64, String> {
+        let value = self.eval(expr, here, 0)?;
+        if value < min || value > max {
+            return Err(format!("value {value} out of range {min}..={max}"));
+        }
+        Ok(value)
+    }
+
+    fn addr12(&self, expr: &Expr, here: u16) -> Result<u16, String> {
+        Ok(self.ranged(expr, here, 0, 0xFFF)? as u16)
+    }
+
+    // Negative bytes are two's complement
+    fn byte(&self, expr: &Expr, here: u16) -> Result<u8, String> {
+        Ok(self.ranged(expr, here, -128, 0xFF)? as u8)
+    }
+
+    fn word(&self, expr: &Expr, here: u16) -> Result<u16, String> {
+        Ok(self.ranged(expr, here, -32768, 0xFFFF)? as u16)
+    }
+
+    fn nibble(&self, expr: &Expr, here: u16) -> Result<u8, String> {
+        Ok(self.ranged(expr, here, 0, 0xF)? as u8)
+    }
+
+    fn encode(&self, mnemonic: &str, operands: &[Operand], here: u16) -> Result<Vec<u8>, String> {
+        use Operand::*;
+        let inst = match (mnemonic, operands) {
+            ("SYS", []) => Inst::Empty,
+            // 0NNN is not an Inst, hex_to_inst decodes every 0NNN it does not know as Empty
+            ("SYS", [Expr(e)]) => return Ok(self.addr12(e, here)?.to_be_bytes().to_vec()),
+            ("CLS", []) => Inst::Cls,
+            ("RET", []) => Inst::Ret,
+            ("JP", [Expr(e)]) => Inst::Jmp {
+                addr: self.addr12(e, here)?,
+            },
+            ("JP", [V(0), Expr(e)]) => Inst::JpV0 {
+                addr: self.addr12(e, here)?,
+            },
+            ("CALL", [Expr(e)]) => Inst::Call {
+                addr: self.addr12(e, here)?,
+            },
+            ("SE", [V(vx), V(vy)]) => Inst::SR { vx: *vx, vy: *vy },
+            ("SE", [V(vx), Expr(e)]) => Inst::SV {
+                vx: *vx,
+                byte: self.byte(e, here)?,
+            },
+            ("SNE", [V(vx), V(vy)]) => Inst::SnR { vx: *vx, vy: *vy },
+            ("SNE", [V(vx), Expr(e)]) => Inst::SnV {
+                vx: *vx,
+                byte: self.byte(e, here)?,
+            },
+            ("LD", [V(vx), V(vy)]) => Inst::LdR { vx: *vx, vy: *vy },
+            ("LD", [V(vx), Expr(e)]) => Inst::LdV {
+                vx: *vx,
+                byte: self.byte(e, here)?,
+            },
+            ("LD", [I, Expr(e)]) => Inst::LdI {
+                addr: self.addr12(e, here)?,
+            },
+            ("LD", [I, Long(e)]) => {
+                let mut bytes = inst_to_hex(Inst::LdIL).to_be_bytes().to_vec();
+                bytes.extend(self.word(e, here)?.to_be_bytes());
+                return Ok(bytes);
+            }
+            ("LD", [V(vx), Dt]) => Inst::LdRDt { vx: *vx },
+            ("LD", [V(vx), K]) => Inst::LdRKp { vx: *vx },
+            ("LD", [Dt, V(vx)]) => Inst::LdDtR { vx: *vx },
+            ("LD", [St, V(vx)]) => Inst::LdStR { vx: *vx },
+            ("LD", [F, V(vx)]) => Inst::LdIF { vx: *vx },
+            ("LD", [Hf, V(vx)]) => Inst::LdIBigF { vx: *vx },
+            ("LD", [B, V(vx)]) => Inst::LdBCDR { vx: *vx },
+            ("LD", [IndI, V(vx)]) => Inst::LdIR { vx: *vx },
+            ("LD", [V(vx), IndI]) => Inst::LdRI { vx: *vx },
+            ("LD", [R, V(vx)]) => Inst::LdRplR { vx: *vx },
+            ("LD", [V(vx), R]) => Inst::LdRRpl { vx: *vx },
+            ("ADD", [V(vx), V(vy)]) => Inst::AddR { vx: *vx, vy: *vy },
+            ("ADD", [V(vx), Expr(e)]) => Inst::AddV {
+                vx: *vx,
+                byte: self.byte(e, here)?,
+            },
+            ("ADD", [I, V(vx)]) => Inst::AddRI { vx: *vx },
+            ("OR", [V(vx), V(vy)]) => Inst::OrR { vx: *vx, vy: *vy },
+            ("AND", [V(vx), V(vy)]) => Inst::AndR { vx: *vx, vy: *vy },
+            ("XOR", [V(vx), V(vy)]) => Inst::XorR { vx: *vx, vy: *vy },
+            ("SUB", [V(vx), V(vy)]) => Inst::SubR { vx: *vx, vy: *vy },
+            ("SUBN", [V(vx), V(vy)]) => Inst::SubnR { vx: *vx, vy: *vy },
+            ("SHR", [V(vx), V(vy)]) => Inst::Shr { vx: *vx, vy: *vy },
+            ("SHR", [V(vx)]) => Inst::Shr { vx: *vx, vy: *vx },
+            ("SHL", [V(vx), V(vy)]) => Inst::Shl { vx: *vx, vy: *vy },
+            ("SHL", [V(vx)]) => Inst::Shl { vx: *vx, vy: *vx },
+            ("RND", [V(vx), Expr(e)]) => Inst::Rnd {
+                vx: *vx,
+                byte: self.byte(e, here)?,
+            },
+            ("DRW", [V(vx), V(vy), Expr(e)]) => Inst::Disp {
+                vx: *vx,
+                vy: *vy,
+                n: self.nibble(e, here)?,
+            },
+            ("SKP", [V(vx)]) => Inst::SKp { vx: *vx },
+            ("SKNP", [V(vx)]) => Inst::SKnp { vx: *vx },
+            ("SCD", [Expr(e)]) => Inst::ScrollDown {
+                n: self.nibble(e, here)?,
+            },
+            ("SCU", [Expr(e)]) => Inst::ScrollUp {
+                n: self.nibble(e, here)?,
+            },
+            ("SCR", []) => Inst::ScrollRight,
+            ("SCL", []) => Inst::ScrollLeft,
+            ("EXIT", []) => Inst::Exit,
+            ("LOW", []) => Inst::Low,
+            ("HIGH", []) => Inst::High,
+            ("SAVE", [V(vx), V(vy)]) => Inst::SaveR { vx: *vx, vy: *vy },
+            ("LOAD", [V(vx), V(vy)]) => Inst::LoadR { vx: *vx, vy: *vy },
+            ("PLANE", [Expr(e)]) => Inst::Plane {
+                n: self.nibble(e, here)?,
+            },
+            ("AUDIO", []) => Inst::Audio,
+            ("PITCH", [V(vx)]) => Inst::Pitch { vx: *vx },
+            (
+                "SYS" | "CLS" | "RET" | "JP" | "CALL" | "SE" | "SNE" | "LD" | "ADD" | "OR" | "AND"
+                | "XOR" | "SUB" | "SUBN" | "SHR" | "SHL" | "RND" | "DRW" | "SKP" | "SKNP" | "SCD"
+                | "SCU" | "SCR" | "SCL" | "EXIT" | "LOW" | "HIGH" | "SAVE" | "LOAD" | "PLANE"
+                | "AUDIO" | "PITCH",
+                _,
+            ) => return Err(format!("invalid operands for {mnemonic}")),
+            _ => return Err(format!("unknown mnemonic: {mnemonic}")),
+        };
+        Ok(inst_to_hex(inst).to_be_bytes().to_vec())
+    }
+
+    // Second pass, evaluates the operands now that every label is known
+    fn emit(&self) -> Result<Vec<u8>, AsmError> {
+        let mut rom = vec![0; self.addr.saturating_sub(self.origin as u32) as usize];
+        for statement in self.statements.iter() {
+            let here = statement.addr;
+            let bytes = match &statement.kind {
+                Kind::Inst { mnemonic, operands } => self.encode(mnemonic, operands, here),
+                Kind::Bytes(items) => items.iter().try_fold(Vec::new(), |mut bytes, item| {
+                    match item {
+                        DataItem::Str(text) => bytes.extend(text),
+                        DataItem::Expr(e) => bytes.push(self.byte(e, here)?),
+                    }
+                    Ok(bytes)
+                }),
+                Kind::Words(words) => words.iter().try_fold(Vec::new(), |mut bytes, e| {
+                    bytes.extend(self.word(e, here)?.to_be_bytes());
+                    Ok(bytes)
+                }),
+            }
+            .map_err(|message| AsmError {
+                file: statement.file.clone(),
+                line: statement.line,
+                message,
+            })?;
+            let offset = (here - self.origin) as usize;
+            rom[offset..offset + bytes.len()].copy_from_slice(&bytes);
+        }
+        Ok(rom)
+    }
+}
+
+fn run(file: &str, source: &str, dir: &Path, origin: u16) -> Result<Vec<u8>, AsmError> {
+    let mut assembler = Assembler {
+        origin,
+        addr: origin as u32,
+        ..Assembler::default()
+    };
+    assembler.load(file, source, dir, 0)?;
+    assembler.emit()
+}
+
+// Assembles source loaded at origin, includes are relative to the working directory
+pub fn assemble(source: &str, origin: u16) -> Result<Vec<u8>, AsmError> {
+    run("<source>", source, Path::new(""), origin)
+}
+
+// Assembles the file at path, includes are relative to the including file
+pub fn assemble_file(path: &Path, origin: u16) -> Result<Vec<u8>, AsmError> {
+    let source = fs::read_to_string(path).map_err(|e| AsmError {
+        file: path.display().to_string(),
+        line: 0,
+        message: e.to_string(),
+    })?;
+    let dir: PathBuf = path.parent().map(Path::to_path_buf).unwrap_or_default();
+    run(&path.display().to_string(), &source, &dir, origin)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use super::*;
+    use crate::{
+        config::PROG_POS_START,
+        disasm::{disassemble, flow, Options},
+    };
+
+    #[test]
+    fn disassembly_reassembles_to_the_same_bytes() {
+        // Listings with and without the address and raw byte columns
+        let no_columns = Options {
+            columns: false,
+            ..Options::default()
+        };
+        for options in [Options::default(), no_columns] {
+            // Every word, one rom per high byte so the addresses stay below 0x10000
+            for high in 0..=0xFFu8 {
+                let rom: Vec<u8> = (0..=0xFFu8).flat_map(|low| [high, low]).collect();
+                let source = disassemble(&rom, &options);
+                assert_eq!(
+                    assemble(&source, PROG_POS_START).unwrap(),
+                    rom,
+                    "{high:02X}xx"
+                );
+            }
+
+            let rom = [
+                0x22, 0x08, 0xA2, 0x0C, 0xD0, 0x15, 0x12, 0x04, 0x00, 0xEE, 0xFF, 0x01, 0xF0, 0x90,
+                0xF0, 0x00, 0xAB, 0xCD,
+            ];
+            let source = flow::analyze(&rom, PROG_POS_START).listing(&rom, &options);
+            assert_eq!(assemble(&source, PROG_POS_START).unwrap(), rom);
+        }
+        assert_eq!(
+            assemble("0200: 00E0      CLS", PROG_POS_START).unwrap(),
+            [0x00, 0xE0]
+        );
+    }
+
+    #[test]
+    fn labels_constants_data_and_includes() {
+        let dir = env::temp_dir().join(format!("chip8-asm-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(
+            dir.join("sprite.inc"),
+            "sprite: DB 0b11110000, 0x90, \"AB\"\n",
+        )
+        .unwrap();
+        let source = "\
+            SPEED EQU 2 * 3 ; a comment\n\
+            start:  LD V0, SPEED + 1\n\
+            loop:   LD I, sprite\n\
+                    ADD V0, -1\n\
+                    JP loop\n\
+                    LD I, LONG end - start\n\
+                    DW $, ~0 & 0xFF\n\
+                    INCLUDE \"sprite.inc\"\n\
+                    ORG start + 0x20\n\
+            end:    DB 'x'\n";
+        let path = dir.join("main.asm");
+        fs::write(&path, source).unwrap();
+        let rom = assemble_file(&path, PROG_POS_START).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        let mut expected = vec![
+            0x60, 0x07, 0xA2, 0x10, 0x70, 0xFF, 0x12, 0x02, 0xF0, 0x00, 0x00, 0x20, 0x02, 0x0C,
+            0x00, 0xFF, 0xF0, 0x90, b'A', b'B',
+        ];
+        expected.resize(0x20, 0);
+        expected.push(b'x');
+        assert_eq!(rom, expected);
+
+        let err = assemble("JP nowhere", PROG_POS_START).unwrap_err();
+        assert_eq!(err.to_string(), "<source>:1: undefined symbol: nowhere");
+    }
+}
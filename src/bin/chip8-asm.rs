@@ -0,0 +1,82 @@
+use std::{env, fs, path::PathBuf, process};
+
+use chip_8::{asm::assemble_file, config::PROG_POS_START};
+
+const USAGE: &str = "usage: chip8-asm [options] <source>
+
+options:
+  -o <file>            output rom, defaults to the source with a .ch8 extension
+  --origin <addr>      address the rom is loaded at, hex (default 200)
+
+syntax:
+  label:               labels end with a colon, any number per line
+  NAME EQU <expr>      constant, may refer to labels defined later
+  DB <expr|\"str\">, ..  bytes
+  DW <expr>, ..        big-endian words
+  ORG <expr>           continue at a higher address, the gap is zero filled
+  INCLUDE \"file\"       assemble another file, relative to the including one
+  ; comment
+
+  Mnemonics are those printed by chip8-disasm, e.g. LD V0, [I] or LD I, LONG <expr>.
+  Its address and raw word columns are skipped, so listings assemble as they are.
+  Expressions use + - * / % & | ^ ~ << >> and parentheses on decimal, 0x hex and
+  0b binary numbers, 'c' characters, symbols and $ for the address of the line.";
+
+struct Args {
+    source: PathBuf,
+    output: PathBuf,
+    origin: u16,
+}
+
+fn parse_args(args: &[String]) -> Result<Args, String> {
+    let mut source: Option<PathBuf> = None;
+    let mut output: Option<PathBuf> = None;
+    let mut origin = PROG_POS_START;
+
+    let mut iter = args.iter().skip(1);
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "-o" => output = Some(PathBuf::from(iter.next().ok_or("missing value for -o")?)),
+            "--origin" => {
+                let value = iter.next().ok_or("missing value for --origin")?;
+                let digits = value.trim_start_matches("0x");
+                origin = u16::from_str_radix(digits, 16)
+                    .map_err(|_| format!("invalid origin: {value}"))?;
+            }
+            _ if arg.starts_with('-') => return Err(format!("unknown option: {arg}")),
+            _ if source.is_none() => source = Some(PathBuf::from(arg)),
+            _ => return Err(format!("unexpected argument: {arg}")),
+        }
+    }
+
+    let source = source.ok_or("missing filepath argument")?;
+    let output = output.unwrap_or_else(|| source.with_extension("ch8"));
+    Ok(Args {
+        source,
+        output,
+        origin,
+    })
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    let args = match parse_args(&args) {
+        Ok(args) => args,
+        Err(err) => {
+            eprintln!("{err}\n\n{USAGE}");
+            process::exit(2);
+        }
+    };
+
+    let rom = match assemble_file(&args.source, args.origin) {
+        Ok(rom) => rom,
+        Err(err) => {
+            eprintln!("{err}");
+            process::exit(1);
+        }
+    };
+    if let Err(err) = fs::write(&args.output, rom) {
+        eprintln!("{}: {err}", args.output.display());
+        process::exit(1);
+    }
+}
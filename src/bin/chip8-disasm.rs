@@ -0,0 +1,75 @@
+use std::{env, fs, process};
+
+use chip_8::disasm::{disassemble, flow, Options, Syntax};
+
+const USAGE: &str = "usage: chip8-disasm [options] <rom>
+
+options:
+  --octo               print Octo syntax instead of conventional mnemonics
+  --no-columns         leave out the address and raw word columns
+  --origin <addr>      address the rom is loaded at, hex (default 200)
+  --recursive          follow the control flow from the origin, unreached bytes are data
+  --cfg                print the basic blocks as a Graphviz DOT graph
+  --calls              print the call graph as a Graphviz DOT graph";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Output {
+    Linear,
+    Recursive,
+    Cfg,
+    Calls,
+}
+
+fn parse_args(args: &[String]) -> Result<(String, Options, Output), String> {
+    let mut rom: Option<String> = None;
+    let mut options = Options::default();
+    let mut output = Output::Linear;
+
+    let mut iter = args.iter().skip(1);
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--octo" => options.syntax = Syntax::Octo,
+            "--no-columns" => options.columns = false,
+            "--recursive" => output = Output::Recursive,
+            "--cfg" => output = Output::Cfg,
+            "--calls" => output = Output::Calls,
+            "--origin" => {
+                let value = iter.next().ok_or("missing value for --origin")?;
+                let digits = value.trim_start_matches("0x");
+                options.origin = u16::from_str_radix(digits, 16)
+                    .map_err(|_| format!("invalid origin: {value}"))?;
+            }
+            _ if arg.starts_with("--") => return Err(format!("unknown option: {arg}")),
+            _ if rom.is_none() => rom = Some(arg.clone()),
+            _ => return Err(format!("unexpected argument: {arg}")),
+        }
+    }
+
+    Ok((rom.ok_or("missing filepath argument")?, options, output))
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    let (rom, options, output) = match parse_args(&args) {
+        Ok(args) => args,
+        Err(err) => {
+            eprintln!("{err}\n\n{USAGE}");
+            process::exit(2);
+        }
+    };
+
+    let rom = match fs::read(&rom) {
+        Ok(rom) => rom,
+        Err(err) => {
+            eprintln!("{rom}: {err}");
+            process::exit(1);
+        }
+    };
+    let text = match output {
+        Output::Linear => disassemble(&rom, &options),
+        Output::Recursive => flow::analyze(&rom, options.origin).listing(&rom, &options),
+        Output::Cfg => flow::analyze(&rom, options.origin).cfg_dot(),
+        Output::Calls => flow::analyze(&rom, options.origin).call_graph_dot(),
+    };
+    print!("{text}");
+}
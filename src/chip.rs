@@ -1,13 +1,35 @@
-use std::time::{Duration, Instant};
+mod debugger;
+mod gdb;
+mod movie;
+mod recording;
+mod rewind;
+mod savestate;
+mod screenshot;
+#[cfg(test)]
+mod tests;
+mod watch;
 
-use rand::Rng;
+use std::{
+    path::PathBuf,
+    time::{Duration, Instant},
+};
 
 use crate::{
     config::*,
+    error::ChipError,
     inst::{hex_to_inst, Inst},
-    screen::Interface,
+    quirks::Quirks,
+    rng::{RandomSource, RngKind},
+    screen::{Hotkey, Interface},
 };
 
+pub use debugger::Debugger;
+pub use gdb::GdbStub;
+pub use movie::{Movie, MovieMode};
+pub use recording::Clip;
+pub use rewind::RewindBuffer;
+pub use screenshot::ScheduledScreenshot;
+
 #[allow(dead_code)]
 pub struct Register {
     v0: u8,
@@ -31,7 +53,7 @@ pub struct Register {
 
 #[allow(dead_code)]
 impl Register {
-    pub fn set_reg_v(&mut self, reg: u8, val: u8) {
+    pub fn set_reg_v(&mut self, reg: u8, val: u8) -> Result<(), ChipError> {
         match reg {
             0x0 => self.v0 = val,
             0x1 => self.v1 = val,
@@ -49,39 +71,49 @@ impl Register {
             0xd => self.vd = val,
             0xe => self.ve = val,
             0xf => self.vf = val,
-            _ => panic!("Invalid register set access, reg: v{reg:x}"),
+            _ => return Err(ChipError::InvalidRegister { reg }),
         };
+        Ok(())
     }
 
-    pub fn get_reg_v(&self, reg: u8) -> u8 {
+    pub fn get_reg_v(&self, reg: u8) -> Result<u8, ChipError> {
         match reg {
-            0x0 => self.v0,
-            0x1 => self.v1,
-            0x2 => self.v2,
-            0x3 => self.v3,
-            0x4 => self.v4,
-            0x5 => self.v5,
-            0x6 => self.v6,
-            0x7 => self.v7,
-            0x8 => self.v8,
-            0x9 => self.v9,
-            0xA => self.va,
-            0xB => self.vb,
-            0xC => self.vc,
-            0xD => self.vd,
-            0xE => self.ve,
-            0xF => self.vf,
-            _ => panic!("Invalid register get access, reg: v {reg:02x}"),
+            0x0 => Ok(self.v0),
+            0x1 => Ok(self.v1),
+            0x2 => Ok(self.v2),
+            0x3 => Ok(self.v3),
+            0x4 => Ok(self.v4),
+            0x5 => Ok(self.v5),
+            0x6 => Ok(self.v6),
+            0x7 => Ok(self.v7),
+            0x8 => Ok(self.v8),
+            0x9 => Ok(self.v9),
+            0xA => Ok(self.va),
+            0xB => Ok(self.vb),
+            0xC => Ok(self.vc),
+            0xD => Ok(self.vd),
+            0xE => Ok(self.ve),
+            0xF => Ok(self.vf),
+            _ => Err(ChipError::InvalidRegister { reg }),
         }
     }
 }
 
+// Registers vx through vy, descending if vx > vy
+fn reg_range(vx: u8, vy: u8) -> Vec<u8> {
+    if vx <= vy {
+        (vx..=vy).collect()
+    } else {
+        (vy..=vx).rev().collect()
+    }
+}
+
 pub struct Chip<T>
 where
     T: Interface,
 {
     pub running: bool,
-    pub memory: [u8; MEMSIZE],
+    pub memory: Vec<u8>,
     pub pc: u16,
     pub registers: Register,
     pub stack: [u16; 16],
@@ -90,15 +122,50 @@ where
     pub delay_timer: u8,
     pub sound_timer: u8,
     pub keyboard: Option<u8>,
+    // Bit n is set while key n is held, sampled once per frame
+    pub keypad: u16,
+    pub rpl_flags: [u8; 16],
+    pub audio_pattern: [u8; 16],
+    pub pitch: u8,
+    pub quirks: Quirks,
+    pub rom_path: Option<PathBuf>,
+    // History for the rewind hotkey, off unless set since every frame is saved into it
+    pub rewind: Option<RewindBuffer>,
+    pub movie: Option<MovieMode>,
+    pub debugger: Option<Debugger>,
+    // Shows the debugger panels next to the game screen
+    pub debug_view: bool,
+    pub gdb: Option<GdbStub>,
+    // Pixel size of screenshots
+    pub screenshot_scale: usize,
+    pub scheduled_screenshot: Option<ScheduledScreenshot>,
+    pub clip: Option<Clip>,
+    // Pixel size of clips
+    pub clip_scale: usize,
+    // Frames run since the start or the last movie start
+    pub frame: u32,
+    pub seed: u64,
+    pub rng_kind: RngKind,
+    rng: Box<dyn RandomSource>,
+    // Interpreter page for the COSMAC VIP rng, zeros until set_vip_page
+    vip_page: [u8; 256],
     release_key_wait: Option<u8>,
+    vblank_wait: bool,
+    rewinding: bool,
 }
 
 #[allow(dead_code)]
 impl<T: Interface> Chip<T> {
-    pub fn new(prog_counter: u16, interface: T) -> Self {
-        Chip {
+    pub fn new(prog_counter: u16, interface: T, quirks: Quirks) -> Self {
+        let memsize = if quirks.xo_chip {
+            XO_CHIP_MEMSIZE
+        } else {
+            MEMSIZE
+        };
+        let seed = rand::random();
+        let mut chip = Chip {
             running: false,
-            memory: [0; MEMSIZE],
+            memory: vec![0; memsize],
             pc: prog_counter,
             registers: Register {
                 v0: 0,
@@ -125,11 +192,35 @@ impl<T: Interface> Chip<T> {
             delay_timer: 0,
             sound_timer: 0,
             keyboard: None,
+            keypad: 0,
+            rpl_flags: [0; 16],
+            audio_pattern: [0; 16],
+            pitch: 64,
+            quirks,
+            rom_path: None,
+            rewind: None,
+            movie: None,
+            debugger: None,
+            debug_view: false,
+            gdb: None,
+            screenshot_scale: 1,
+            scheduled_screenshot: None,
+            clip: None,
+            clip_scale: 1,
+            frame: 0,
+            seed,
+            rng_kind: RngKind::Std,
+            rng: RngKind::Std.create(seed, &[0; 256]),
+            vip_page: [0; 256],
             release_key_wait: None,
-        }
+            vblank_wait: false,
+            rewinding: false,
+        };
+        chip.load_font();
+        chip
     }
 
-    pub fn run(&mut self) {
+    pub fn run(&mut self) -> Result<(), ChipError> {
         self.running = true;
         let target_frame_time = Duration::from_secs_f64(1f64 / SCREEN_REFRESH_RATE as f64);
         let mut last_frame = Instant::now();
@@ -137,24 +228,120 @@ impl<T: Interface> Chip<T> {
         let decrement = |timer: &mut u8| *timer = (*timer).saturating_sub(1);
 
         while self.running && !self.interface.get_close_window() {
-            // Execute next instructions for frame
-            for _ in 0..(INSTRUCTION_FREQUENCY / SCREEN_REFRESH_RATE) {
-                self.execute_inst();
+            // Step back one frame instead of running while the rewind hotkey is held
+            let rewound = self.rewinding && self.rewind_frame();
+            self.rewinding = false;
+
+            if !rewound {
+                self.gdb_poll();
+                self.keypad = self.sample_keypad();
+                if !self.running {
+                    // Movie playback has ended
+                    break;
+                }
+                self.keyboard = (0..16).find(|key| self.key_down(*key));
+
+                // Execute next instructions for frame
+                for _ in 0..(INSTRUCTION_FREQUENCY / SCREEN_REFRESH_RATE) {
+                    if self.debug_break() {
+                        self.debug_prompt();
+                        if !self.running {
+                            break;
+                        }
+                    }
+                    let pc = self.pc;
+                    if let Err(err) = self.execute_inst() {
+                        self.gdb_fault(&err);
+                        return Err(err);
+                    }
+                    self.debug_watch(pc);
+                    if self.vblank_wait || !self.running {
+                        break;
+                    }
+                }
+                self.vblank_wait = false;
             }
 
             // Update Screen
+            if self.debug_view {
+                self.interface.set_side_panels(self.debug_panels());
+            }
             self.interface.update_screen();
+            self.take_scheduled_screenshot();
+            self.capture_clip_frame();
 
-            // Update Sound and Delay timer
-            decrement(&mut self.delay_timer);
-            decrement(&mut self.sound_timer);
+            for hotkey in self.interface.get_hotkeys() {
+                self.handle_hotkey(hotkey);
+            }
+
+            if !rewound {
+                // Update Sound and Delay timer
+                decrement(&mut self.delay_timer);
+                decrement(&mut self.sound_timer);
+                self.rng.tick();
+
+                self.record_rewind_frame();
+                self.frame += 1;
+            }
 
             let now_frame = Instant::now();
-            if (now_frame - last_frame) < target_frame_time {
+            if self.interface.paced() && (now_frame - last_frame) < target_frame_time {
                 std::thread::sleep(target_frame_time - (now_frame - last_frame));
             }
             last_frame = now_frame;
         }
+        self.gdb_exit();
+        Ok(())
+    }
+
+    fn handle_hotkey(&mut self, hotkey: Hotkey) {
+        // There is no place to report errors while the interface is running,
+        // a failed save or load leaves the machine untouched
+        match hotkey {
+            Hotkey::SaveState(slot) => {
+                let _ = self.save_state_slot(slot);
+            }
+            // Going back in time would desync a recorded or replayed movie
+            Hotkey::LoadState(_) | Hotkey::Rewind if self.movie.is_some() => {}
+            Hotkey::LoadState(slot) => {
+                let _ = self.load_state_slot(slot);
+            }
+            Hotkey::Rewind => self.rewinding = true,
+            Hotkey::Pause => self.debugger.get_or_insert_with(Debugger::new).pause(),
+            Hotkey::Screenshot => {
+                let _ = self.save_screenshot();
+            }
+            Hotkey::Record => {
+                let _ = self.toggle_clip();
+            }
+        }
+    }
+
+    // Reseeds the rng used by Rnd
+    pub fn set_seed(&mut self, seed: u64) {
+        self.seed = seed;
+        self.rng.reseed(seed);
+    }
+
+    // Replaces the rng used by Rnd with a built-in one, seeded with the current seed
+    pub fn set_rng_kind(&mut self, kind: RngKind) {
+        self.rng_kind = kind;
+        self.rng = kind.create(self.seed, &self.vip_page);
+    }
+
+    // Sets the interpreter page the COSMAC VIP rng reads, see rng::vip_page
+    pub fn set_vip_page(&mut self, page: [u8; 256]) {
+        self.vip_page = page;
+        self.set_rng_kind(self.rng_kind);
+    }
+
+    // Injects a custom random source, it is reseeded by set_seed
+    pub fn set_rng(&mut self, rng: Box<dyn RandomSource>) {
+        self.rng = rng;
+    }
+
+    pub fn key_down(&self, key: u8) -> bool {
+        key < 16 && self.keypad & (1 << key) != 0
     }
 
     pub fn init_interface(&self) {
@@ -165,229 +352,382 @@ impl<T: Interface> Chip<T> {
         self.interface.stop();
     }
 
-    pub fn load_prog(&mut self, prog: Vec<u8>) {
+    pub fn load_prog(&mut self, prog: Vec<u8>) -> Result<(), ChipError> {
+        let max = self.memory.len() - PROG_POS_START as usize;
+        if prog.len() > max {
+            return Err(ChipError::ProgramTooLarge {
+                size: prog.len(),
+                max,
+            });
+        }
         for (i, inst_part) in prog.iter().enumerate() {
             self.memory[PROG_POS_START as usize + i] = *inst_part;
         }
+        Ok(())
     }
 
-    pub fn execute_inst(&mut self) {
+    pub fn execute_inst(&mut self) -> Result<(), ChipError> {
+        if self.pc as usize + 1 >= self.memory.len() {
+            return Err(ChipError::PcOutOfRange { pc: self.pc });
+        }
         let val: u16 = ((self.memory[self.pc as usize] as u16) << 8)
             | self.memory[(self.pc + 1) as usize] as u16;
-        let inst: Inst = hex_to_inst(val);
+        let inst: Inst = hex_to_inst(val).ok_or(ChipError::IllegalInstruction {
+            addr: self.pc,
+            opcode: val,
+        })?;
         match inst {
-            Inst::Empty => self.pc += 2,
+            Inst::Empty => self.advance_pc(2)?,
             Inst::Cls => {
                 self.interface.clear_screen();
-                self.pc += 2;
+                self.advance_pc(2)?;
             }
             Inst::Ret => {
+                if self.stackpointer == 0 {
+                    return Err(ChipError::StackUnderflow { addr: self.pc });
+                }
                 self.stackpointer -= 1;
                 self.pc = self.stack[self.stackpointer as usize];
-                self.pc += 2;
+                self.advance_pc(2)?;
             }
             Inst::Jmp { addr } => {
                 self.pc = addr;
             }
             Inst::Call { addr } => {
+                if self.stackpointer as usize >= self.stack.len() {
+                    return Err(ChipError::StackOverflow { addr: self.pc });
+                }
                 self.stack[self.stackpointer as usize] = self.pc;
                 self.stackpointer += 1;
                 self.pc = addr;
             }
             Inst::SV { vx, byte } => {
-                if self.registers.get_reg_v(vx) == byte {
-                    self.pc += 2;
-                }
-                self.pc += 2;
+                self.skip_if(self.registers.get_reg_v(vx)? == byte)?;
             }
             Inst::SnV { vx, byte } => {
-                if self.registers.get_reg_v(vx) != byte {
-                    self.pc += 2;
-                }
-                self.pc += 2;
+                self.skip_if(self.registers.get_reg_v(vx)? != byte)?;
             }
             Inst::SR { vx, vy } => {
-                if self.registers.get_reg_v(vx) == self.registers.get_reg_v(vy) {
-                    self.pc += 2;
-                }
-                self.pc += 2;
+                self.skip_if(self.registers.get_reg_v(vx)? == self.registers.get_reg_v(vy)?)?;
             }
             Inst::LdV { vx, byte } => {
-                self.registers.set_reg_v(vx, byte);
-                self.pc += 2;
+                self.registers.set_reg_v(vx, byte)?;
+                self.advance_pc(2)?;
             }
             Inst::AddV { vx, byte } => {
-                let (res, _) = self.registers.get_reg_v(vx).overflowing_add(byte);
-                self.registers.set_reg_v(vx, res);
-                self.pc += 2;
+                let (res, _) = self.registers.get_reg_v(vx)?.overflowing_add(byte);
+                self.registers.set_reg_v(vx, res)?;
+                self.advance_pc(2)?;
             }
             Inst::LdR { vx, vy } => {
-                self.registers.set_reg_v(vx, self.registers.get_reg_v(vy));
-                self.pc += 2;
+                self.registers
+                    .set_reg_v(vx, self.registers.get_reg_v(vy)?)?;
+                self.advance_pc(2)?;
             }
             Inst::OrR { vx, vy } => {
-                let val = self.registers.get_reg_v(vx) | self.registers.get_reg_v(vy);
-                self.registers.set_reg_v(vx, val);
-                self.registers.set_reg_v(0xF, 0);
-                self.pc += 2;
+                let val = self.registers.get_reg_v(vx)? | self.registers.get_reg_v(vy)?;
+                self.registers.set_reg_v(vx, val)?;
+                if self.quirks.vf_reset {
+                    self.registers.set_reg_v(0xF, 0)?;
+                }
+                self.advance_pc(2)?;
             }
             Inst::AndR { vx, vy } => {
-                let val = self.registers.get_reg_v(vx) & self.registers.get_reg_v(vy);
-                self.registers.set_reg_v(vx, val);
-                self.registers.set_reg_v(0xF, 0);
-                self.pc += 2;
+                let val = self.registers.get_reg_v(vx)? & self.registers.get_reg_v(vy)?;
+                self.registers.set_reg_v(vx, val)?;
+                if self.quirks.vf_reset {
+                    self.registers.set_reg_v(0xF, 0)?;
+                }
+                self.advance_pc(2)?;
             }
             Inst::XorR { vx, vy } => {
-                let val = self.registers.get_reg_v(vx) ^ self.registers.get_reg_v(vy);
-                self.registers.set_reg_v(vx, val);
-                self.registers.set_reg_v(0xF, 0);
-                self.pc += 2;
+                let val = self.registers.get_reg_v(vx)? ^ self.registers.get_reg_v(vy)?;
+                self.registers.set_reg_v(vx, val)?;
+                if self.quirks.vf_reset {
+                    self.registers.set_reg_v(0xF, 0)?;
+                }
+                self.advance_pc(2)?;
             }
             Inst::AddR { vx, vy } => {
                 let (res, vf) = self
                     .registers
-                    .get_reg_v(vx)
-                    .overflowing_add(self.registers.get_reg_v(vy));
-                self.registers.set_reg_v(vx, res);
-                self.registers.set_reg_v(0xF, vf as u8);
-                self.pc += 2;
+                    .get_reg_v(vx)?
+                    .overflowing_add(self.registers.get_reg_v(vy)?);
+                self.registers.set_reg_v(vx, res)?;
+                self.registers.set_reg_v(0xF, vf as u8)?;
+                self.advance_pc(2)?;
             }
             Inst::SubR { vx, vy } => {
                 let (res, vf) = self
                     .registers
-                    .get_reg_v(vx)
-                    .overflowing_sub(self.registers.get_reg_v(vy));
-                self.registers.set_reg_v(vx, res);
-                self.registers.set_reg_v(0xF, !vf as u8);
-                self.pc += 2;
+                    .get_reg_v(vx)?
+                    .overflowing_sub(self.registers.get_reg_v(vy)?);
+                self.registers.set_reg_v(vx, res)?;
+                self.registers.set_reg_v(0xF, !vf as u8)?;
+                self.advance_pc(2)?;
             }
             Inst::Shr { vx, vy } => {
-                let val = self.registers.get_reg_v(vy);
-                self.registers.set_reg_v(vx, val >> 1);
-                self.registers.set_reg_v(0xF, val & 0x1);
-                self.pc += 2;
+                let src = if self.quirks.shift_vy { vy } else { vx };
+                let val = self.registers.get_reg_v(src)?;
+                self.registers.set_reg_v(vx, val >> 1)?;
+                self.registers.set_reg_v(0xF, val & 0x1)?;
+                self.advance_pc(2)?;
             }
             Inst::SubnR { vx, vy } => {
                 let (res, vf) = self
                     .registers
-                    .get_reg_v(vy)
-                    .overflowing_sub(self.registers.get_reg_v(vx));
-                self.registers.set_reg_v(vx, res);
-                self.registers.set_reg_v(0xF, !vf as u8);
-                self.pc += 2;
+                    .get_reg_v(vy)?
+                    .overflowing_sub(self.registers.get_reg_v(vx)?);
+                self.registers.set_reg_v(vx, res)?;
+                self.registers.set_reg_v(0xF, !vf as u8)?;
+                self.advance_pc(2)?;
             }
             Inst::Shl { vx, vy } => {
-                let val = self.registers.get_reg_v(vy);
-                self.registers.set_reg_v(vx, val << 1);
-                self.registers.set_reg_v(0xF, (val >> 7) & 0x1);
-                self.pc += 2;
+                let src = if self.quirks.shift_vy { vy } else { vx };
+                let val = self.registers.get_reg_v(src)?;
+                self.registers.set_reg_v(vx, val << 1)?;
+                self.registers.set_reg_v(0xF, (val >> 7) & 0x1)?;
+                self.advance_pc(2)?;
             }
             Inst::SnR { vx, vy } => {
-                self.pc += 2;
-                if self.registers.get_reg_v(vx) != self.registers.get_reg_v(vy) {
-                    self.pc += 2;
-                }
+                self.skip_if(self.registers.get_reg_v(vx)? != self.registers.get_reg_v(vy)?)?;
             }
             Inst::LdI { addr } => {
                 self.registers.i = addr;
-                self.pc += 2;
+                self.advance_pc(2)?;
             }
             Inst::JpV0 { addr } => {
-                self.pc = addr + self.registers.get_reg_v(0x0) as u16;
+                let reg = if self.quirks.jump_vx {
+                    ((addr & 0x0F00) >> 8) as u8
+                } else {
+                    0x0
+                };
+                self.pc = addr + self.registers.get_reg_v(reg)? as u16;
             }
             Inst::Rnd { vx, byte } => {
-                let val = (rand::thread_rng().gen_range(0..=255) as u8) & byte;
-                self.registers.set_reg_v(vx, val);
-                self.pc += 2;
+                let val = self.rng.next_byte() & byte;
+                self.registers.set_reg_v(vx, val)?;
+                self.advance_pc(2)?;
             }
             Inst::Disp { vx, vy, n } => {
+                // n = 0 draws a 16x16 sprite with two bytes per row
+                let (width, len) = if n == 0 { (16, 32) } else { (8, n as usize) };
+                let len = self.interface.framebuffer().sprite_len(len);
                 let mut sprite_buffer: Vec<u8> = Vec::new();
-                for i in 0..n {
-                    sprite_buffer.push(self.memory[(self.registers.i + i as u16) as usize]);
+                for i in 0..len {
+                    sprite_buffer.push(self.get_addr(self.registers.i as usize + i)?);
                 }
                 if self.interface.draw_sprite(
-                    self.registers.get_reg_v(vx),
-                    self.registers.get_reg_v(vy),
+                    self.registers.get_reg_v(vx)?,
+                    self.registers.get_reg_v(vy)?,
                     sprite_buffer,
+                    width,
+                    self.quirks.clip,
                 ) {
-                    self.registers.set_reg_v(0xF, 1);
+                    self.registers.set_reg_v(0xF, 1)?;
                 } else {
-                    self.registers.set_reg_v(0xF, 0);
+                    self.registers.set_reg_v(0xF, 0)?;
                 }
-                self.pc += 2;
+                self.vblank_wait = self.quirks.display_wait;
+                self.advance_pc(2)?;
             }
             Inst::SKp { vx } => {
-                let target = self.registers.get_reg_v(vx);
-                if self.interface.get_key(target) {
-                    self.pc += 2;
-                }
-                self.pc += 2;
+                let target = self.registers.get_reg_v(vx)?;
+                self.skip_if(self.key_down(target))?;
             }
             Inst::SKnp { vx } => {
-                let target = self.registers.get_reg_v(vx);
-                if !self.interface.get_key(target) {
-                    self.pc += 2;
-                }
-                self.pc += 2;
+                let target = self.registers.get_reg_v(vx)?;
+                self.skip_if(!self.key_down(target))?;
             }
             Inst::LdRDt { vx } => {
-                self.registers.set_reg_v(vx, self.delay_timer);
-                self.pc += 2;
+                self.registers.set_reg_v(vx, self.delay_timer)?;
+                self.advance_pc(2)?;
             }
             Inst::LdRKp { vx } => {
                 if let Some(key) = self.release_key_wait {
-                    if !self.interface.get_key(key) {
+                    if !self.key_down(key) {
                         self.release_key_wait = None;
-                        self.pc += 2;
+                        self.advance_pc(2)?;
                     }
                 } else if let Some(key) = self.keyboard {
-                    self.registers.set_reg_v(vx, key);
+                    self.registers.set_reg_v(vx, key)?;
                     self.release_key_wait = Some(key);
                 }
             }
             Inst::LdDtR { vx } => {
-                self.delay_timer = self.registers.get_reg_v(vx);
-                self.pc += 2;
+                self.delay_timer = self.registers.get_reg_v(vx)?;
+                self.advance_pc(2)?;
             }
             Inst::LdStR { vx } => {
-                self.sound_timer = self.registers.get_reg_v(vx);
-                self.pc += 2;
+                self.sound_timer = self.registers.get_reg_v(vx)?;
+                self.advance_pc(2)?;
             }
             Inst::AddRI { vx } => {
-                let val = self.registers.i + self.registers.get_reg_v(vx) as u16;
+                let val = self
+                    .registers
+                    .i
+                    .wrapping_add(self.registers.get_reg_v(vx)? as u16);
                 self.registers.i = val;
-                self.pc += 2;
+                self.advance_pc(2)?;
             }
             Inst::LdIF { vx } => {
-                let x = self.registers.get_reg_v(vx);
+                let x = self.registers.get_reg_v(vx)?;
                 self.registers.i = (FONT_POS_START + 5 * x as usize) as u16;
-                self.pc += 2;
+                self.advance_pc(2)?;
             }
             Inst::LdBCDR { vx } => {
-                let val = self.registers.get_reg_v(vx);
-                self.memory[self.registers.i as usize] = val / 100;
-                self.memory[(self.registers.i + 1) as usize] = (val % 100) / 10;
-                self.memory[(self.registers.i + 2) as usize] = (val % 100) % 10;
-                self.pc += 2;
+                let val = self.registers.get_reg_v(vx)?;
+                let i = self.registers.i as usize;
+                self.set_byte(i, val / 100)?;
+                self.set_byte(i + 1, (val % 100) / 10)?;
+                self.set_byte(i + 2, (val % 100) % 10)?;
+                self.advance_pc(2)?;
             }
             Inst::LdIR { vx } => {
                 let i = self.registers.i;
                 for x in 0..=vx {
-                    self.memory[(i + x as u16) as usize] = self.registers.get_reg_v(x);
+                    self.set_byte(i as usize + x as usize, self.registers.get_reg_v(x)?)?;
                 }
-                self.registers.i = i + vx as u16 + 1;
-                self.pc += 2;
+                self.registers.i = i.wrapping_add(self.quirks.load_store.increment(vx));
+                self.advance_pc(2)?;
             }
             Inst::LdRI { vx } => {
                 let i = self.registers.i;
                 for x in 0..=vx {
-                    self.registers
-                        .set_reg_v(x, self.memory[(i + x as u16) as usize]);
+                    let val = self.get_addr(i as usize + x as usize)?;
+                    self.registers.set_reg_v(x, val)?;
                 }
-                self.registers.i = i + vx as u16 + 1;
-                self.pc += 2;
+                self.registers.i = i.wrapping_add(self.quirks.load_store.increment(vx));
+                self.advance_pc(2)?;
+            }
+            Inst::ScrollDown { n } => {
+                self.interface.framebuffer_mut().scroll_down(n);
+                self.advance_pc(2)?;
+            }
+            Inst::ScrollRight => {
+                self.interface.framebuffer_mut().scroll_right(4);
+                self.advance_pc(2)?;
+            }
+            Inst::ScrollLeft => {
+                self.interface.framebuffer_mut().scroll_left(4);
+                self.advance_pc(2)?;
+            }
+            Inst::Exit => {
+                self.running = false;
+            }
+            Inst::Low => {
+                self.interface.framebuffer_mut().set_hires(false);
+                self.advance_pc(2)?;
+            }
+            Inst::High => {
+                self.interface.framebuffer_mut().set_hires(true);
+                self.advance_pc(2)?;
+            }
+            Inst::LdIBigF { vx } => {
+                let x = self.registers.get_reg_v(vx)?;
+                self.registers.i = (BIG_FONT_POS_START + 10 * (x & 0xF) as usize) as u16;
+                self.advance_pc(2)?;
+            }
+            Inst::LdRplR { vx } => {
+                for x in 0..=vx {
+                    self.rpl_flags[x as usize] = self.registers.get_reg_v(x)?;
+                }
+                self.advance_pc(2)?;
+            }
+            Inst::LdRRpl { vx } => {
+                for x in 0..=vx {
+                    self.registers.set_reg_v(x, self.rpl_flags[x as usize])?;
+                }
+                self.advance_pc(2)?;
+            }
+            Inst::ScrollUp { n } => {
+                self.require_xo_chip(val)?;
+                self.interface.framebuffer_mut().scroll_up(n);
+                self.advance_pc(2)?;
+            }
+            Inst::SaveR { vx, vy } => {
+                self.require_xo_chip(val)?;
+                let i = self.registers.i as usize;
+                for (offset, x) in reg_range(vx, vy).into_iter().enumerate() {
+                    self.set_byte(i + offset, self.registers.get_reg_v(x)?)?;
+                }
+                self.advance_pc(2)?;
+            }
+            Inst::LoadR { vx, vy } => {
+                self.require_xo_chip(val)?;
+                let i = self.registers.i as usize;
+                for (offset, x) in reg_range(vx, vy).into_iter().enumerate() {
+                    let val = self.get_addr(i + offset)?;
+                    self.registers.set_reg_v(x, val)?;
+                }
+                self.advance_pc(2)?;
+            }
+            Inst::LdIL => {
+                self.require_xo_chip(val)?;
+                let addr = self.pc as usize + 2;
+                self.registers.i =
+                    ((self.get_addr(addr)? as u16) << 8) | self.get_addr(addr + 1)? as u16;
+                self.advance_pc(4)?;
+            }
+            Inst::Plane { n } => {
+                self.require_xo_chip(val)?;
+                self.interface.framebuffer_mut().select_planes(n);
+                self.advance_pc(2)?;
+            }
+            Inst::Audio => {
+                self.require_xo_chip(val)?;
+                for x in 0..self.audio_pattern.len() {
+                    self.audio_pattern[x] = self.get_addr(self.registers.i as usize + x)?;
+                }
+                self.advance_pc(2)?;
+            }
+            Inst::Pitch { vx } => {
+                self.require_xo_chip(val)?;
+                self.pitch = self.registers.get_reg_v(vx)?;
+                self.advance_pc(2)?;
             }
         }
+        Ok(())
+    }
+
+    // Moves pc past the current instruction and the next one when skip is set
+    fn skip_if(&mut self, skip: bool) -> Result<(), ChipError> {
+        let len = if skip { 2 + self.next_inst_len() } else { 2 };
+        self.advance_pc(len)
+    }
+
+    // Moves pc past the current instruction, running off the end of the address space is a fault
+    fn advance_pc(&mut self, len: u16) -> Result<(), ChipError> {
+        self.pc = self
+            .pc
+            .checked_add(len)
+            .ok_or(ChipError::PcOutOfRange { pc: self.pc })?;
+        Ok(())
+    }
+
+    // XO-CHIP instructions are illegal unless the machine runs in XO-CHIP mode
+    fn require_xo_chip(&self, opcode: u16) -> Result<(), ChipError> {
+        if self.quirks.xo_chip {
+            Ok(())
+        } else {
+            Err(ChipError::IllegalInstruction {
+                addr: self.pc,
+                opcode,
+            })
+        }
+    }
+
+    // Size of the instruction following pc, XO-CHIP long I loads take 4 bytes
+    fn next_inst_len(&self) -> u16 {
+        let next = self.pc as usize + 2;
+        if self.quirks.xo_chip
+            && self.memory.get(next) == Some(&0xF0)
+            && self.memory.get(next + 1) == Some(&0x00)
+        {
+            4
+        } else {
+            2
+        }
     }
 
     fn load_font(&mut self) {
@@ -412,25 +752,31 @@ impl<T: Interface> Chip<T> {
         for (i, byte) in font.iter().enumerate() {
             self.memory[FONT_POS_START + i] = *byte;
         }
-    }
-
-    fn set_pc(&mut self, value: u16) {
-        self.pc = value;
-    }
 
-    fn set_byte(&mut self, addr: u16, val: u8) {
-        if (addr as usize) < MEMSIZE {
-            self.memory[addr as usize] = val;
-        } else {
-            panic!("Tried to access memory out of bounds, Memsize: {MEMSIZE}, addr: {addr}");
+        let big_font: [u8; 160] = [
+            0xFF, 0xFF, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, // 0
+            0x18, 0x78, 0x78, 0x18, 0x18, 0x18, 0x18, 0x18, 0xFF, 0xFF, // 1
+            0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, // 2
+            0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 3
+            0xC3, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, 0x03, 0x03, 0x03, 0x03, // 4
+            0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 5
+            0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, // 6
+            0xFF, 0xFF, 0x03, 0x03, 0x06, 0x0C, 0x18, 0x18, 0x18, 0x18, // 7
+            0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, // 8
+            0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 9
+            0x7E, 0xFF, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xC3, // a
+            0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC, // b
+            0x3C, 0xFF, 0xC3, 0xC0, 0xC0, 0xC0, 0xC0, 0xC3, 0xFF, 0x3C, // c
+            0xFC, 0xFE, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFE, 0xFC, // d
+            0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, // e
+            0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xC0, 0xC0, // f
+        ];
+        for (i, byte) in big_font.iter().enumerate() {
+            self.memory[BIG_FONT_POS_START + i] = *byte;
         }
     }
 
-    fn get_addr(&self, addr: u16) -> u8 {
-        if (addr as usize) < MEMSIZE {
-            self.memory[addr as usize]
-        } else {
-            panic!("Tried to access memory out of bounds, Memsize: {MEMSIZE}, addr: {addr}");
-        }
+    fn set_pc(&mut self, value: u16) {
+        self.pc = value;
     }
 }
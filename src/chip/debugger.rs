@@ -0,0 +1,728 @@
+use std::{collections::BTreeSet, fmt::Write as _};
+
+use super::{
+    watch::{parse_hex, parse_watchpoint, Access, Watches, Watchpoint},
+    Chip,
+};
+use crate::{
+    config::*,
+    inst::{hex_to_inst, Inst},
+    screen::{prompt_stdin, Interface, SidePanel},
+};
+
+pub const DEBUG_HELP: &str = "commands:
+  s, step [n]          execute n instructions (default 1)
+  n, next              step over a subroutine call
+  f, finish            run until the current subroutine returns
+  c, continue          run until a breakpoint is hit
+  b, break <addr>      set a breakpoint, addresses are hex
+  d, delete <addr>     remove a breakpoint
+  w, watch <addr>[-<end>]
+                       break after memory in the range is written
+  rwatch <addr>[-<end>]
+                       break after memory in the range is read
+  awatch <addr>[-<end>]
+                       break after memory in the range is read or written
+  w, watch <reg>       break after the register changes (v0-vf, i, pc, sp, dt, st)
+  w, watch <reg> <op> <value>
+                       break when the comparison becomes true, op is == != < <= > >=
+  u, unwatch <n>       remove watchpoint n
+  i, info              list breakpoints and watchpoints
+  r, regs              show registers, stack and the next instruction
+  q, quit              stop the emulator
+an empty line repeats the last command";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Command {
+    Step(u32),
+    Next,
+    Finish,
+    Continue,
+    Break(u16),
+    Delete(u16),
+    Watch(Watchpoint),
+    Unwatch(usize),
+    Info,
+    Regs,
+    Help,
+    Quit,
+}
+
+fn parse_addr(arg: Option<&str>) -> Result<u16, String> {
+    parse_hex(arg.ok_or("missing address")?)
+}
+
+pub fn parse_command(line: &str) -> Result<Command, String> {
+    let mut words = line.split_whitespace();
+    let watch = |words: std::str::SplitWhitespace, access| {
+        parse_watchpoint(&words.collect::<Vec<_>>(), access).map(Command::Watch)
+    };
+    let command = match words.next().unwrap_or_default() {
+        "s" | "step" => match words.next() {
+            Some(count) => Command::Step(
+                count
+                    .parse()
+                    .map_err(|_| format!("invalid step count: {count}"))?,
+            ),
+            None => Command::Step(1),
+        },
+        "n" | "next" => Command::Next,
+        "f" | "finish" => Command::Finish,
+        "c" | "continue" => Command::Continue,
+        "b" | "break" => Command::Break(parse_addr(words.next())?),
+        "d" | "delete" => Command::Delete(parse_addr(words.next())?),
+        "w" | "watch" => watch(words, Access::Write)?,
+        "rwatch" => watch(words, Access::Read)?,
+        "awatch" => watch(words, Access::ReadWrite)?,
+        "u" | "unwatch" => {
+            let index = words.next().ok_or("missing watchpoint number")?;
+            Command::Unwatch(
+                index
+                    .parse()
+                    .map_err(|_| format!("invalid watchpoint number: {index}"))?,
+            )
+        }
+        "i" | "info" => Command::Info,
+        "r" | "regs" => Command::Regs,
+        "h" | "help" => Command::Help,
+        "q" | "quit" => Command::Quit,
+        word => return Err(format!("unknown command: {word} (try help)")),
+    };
+    Ok(command)
+}
+
+// Condition that pauses the machine again after it was resumed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum StopAt {
+    // Run until a breakpoint
+    Never,
+    // Pause after n more instructions, 0 pauses before the next one
+    Steps(u32),
+    // Pause once the call at the resume point has returned to pc with the stack at sp
+    Return { pc: u16, sp: u8 },
+    // Pause once the stack drops below sp
+    Out { sp: u8 },
+}
+
+pub struct Debugger {
+    breakpoints: BTreeSet<u16>,
+    pub(super) watches: Watches,
+    stop: StopAt,
+    // The breakpoint at the pc the machine was resumed at is skipped once
+    resume_pc: Option<u16>,
+    last_command: Option<Command>,
+}
+
+#[allow(dead_code)]
+impl Debugger {
+    // A new debugger pauses before the next instruction
+    pub fn new() -> Self {
+        Debugger {
+            breakpoints: BTreeSet::new(),
+            watches: Watches::default(),
+            stop: StopAt::Steps(0),
+            resume_pc: None,
+            last_command: None,
+        }
+    }
+
+    // A debugger that only stops at breakpoints and watchpoints
+    pub fn running() -> Self {
+        Debugger {
+            stop: StopAt::Never,
+            ..Debugger::new()
+        }
+    }
+
+    pub fn pause(&mut self) {
+        self.stop = StopAt::Steps(0);
+    }
+
+    pub fn add_breakpoint(&mut self, addr: u16) {
+        self.breakpoints.insert(addr);
+    }
+
+    pub fn remove_breakpoint(&mut self, addr: u16) -> bool {
+        self.breakpoints.remove(&addr)
+    }
+
+    pub fn breakpoints(&self) -> impl Iterator<Item = u16> + '_ {
+        self.breakpoints.iter().copied()
+    }
+
+    // Called before every instruction, returns true if the machine should pause
+    pub fn should_break(&mut self, pc: u16, sp: u8) -> bool {
+        let resumed_here = self.resume_pc.take() == Some(pc);
+        if self.watches.triggered() || (!resumed_here && self.breakpoints.contains(&pc)) {
+            return true;
+        }
+        match &mut self.stop {
+            StopAt::Never => false,
+            StopAt::Steps(0) => true,
+            StopAt::Steps(remaining) => {
+                *remaining -= 1;
+                false
+            }
+            StopAt::Return {
+                pc: ret_pc,
+                sp: ret_sp,
+            } => (pc == *ret_pc && sp == *ret_sp) || sp < *ret_sp,
+            StopAt::Out { sp: out_sp } => sp < *out_sp,
+        }
+    }
+
+    pub(super) fn resume(&mut self, pc: u16, stop: StopAt) {
+        self.stop = stop;
+        self.resume_pc = Some(pc);
+    }
+
+    // Resumes for count instructions. The instruction at pc already passed its check,
+    // so it counts as the first one
+    pub(super) fn step(&mut self, pc: u16, count: u32) {
+        self.resume(pc, StopAt::Steps(count.saturating_sub(1)));
+    }
+}
+
+impl Default for Debugger {
+    fn default() -> Self {
+        Debugger::new()
+    }
+}
+
+#[allow(dead_code)]
+impl<T: Interface> Chip<T> {
+    // Breakpoints from :breakpoint directives, without a debugger one that only stops at
+    // breakpoints and watchpoints is added
+    pub fn add_breakpoints(&mut self, breakpoints: &[(String, u16)]) {
+        if breakpoints.is_empty() {
+            return;
+        }
+        let debugger = self.debugger.get_or_insert_with(Debugger::running);
+        for (_, addr) in breakpoints {
+            debugger.add_breakpoint(*addr);
+        }
+    }
+
+    // Returns true if the debugger wants to pause before the instruction at pc
+    pub(super) fn debug_break(&mut self) -> bool {
+        let (pc, sp) = (self.pc, self.stackpointer);
+        match self.debugger.as_mut() {
+            Some(debugger) => debugger.should_break(pc, sp),
+            None => false,
+        }
+    }
+
+    // Pauses the machine and reads debugger commands until it is resumed. Without the debug
+    // view the interface is stopped meanwhile, so the output is not hidden by the alternate screen.
+    // A connected gdb takes the place of the prompt
+    pub(super) fn debug_prompt(&mut self) {
+        if self.gdb.is_some() {
+            self.gdb_prompt();
+            return;
+        }
+        let mut output = self
+            .debugger
+            .as_mut()
+            .and_then(|d| d.watches.take_triggered())
+            .unwrap_or_default();
+        if !self.debug_view {
+            self.interface.stop();
+            if !output.is_empty() {
+                output.push('\n');
+            }
+            output.push_str(&self.debug_status());
+        }
+
+        loop {
+            let line = match self.debug_read_line(&output) {
+                Some(line) => line,
+                None => {
+                    // stdin was closed
+                    self.running = false;
+                    break;
+                }
+            };
+            let last_command = self.debugger.as_ref().and_then(|d| d.last_command);
+            let command = match (line.trim(), last_command) {
+                ("", Some(command)) => Ok(command),
+                (line, _) => parse_command(line),
+            };
+            match command {
+                Ok(command) => {
+                    if let Some(debugger) = self.debugger.as_mut() {
+                        debugger.last_command = Some(command);
+                    }
+                    let resume;
+                    (resume, output) = self.debug_command(command);
+                    if resume {
+                        break;
+                    }
+                }
+                Err(err) => output = err,
+            }
+        }
+
+        if !self.debug_view && self.running {
+            self.interface.init();
+        }
+    }
+
+    fn debug_read_line(&mut self, output: &str) -> Option<String> {
+        if self.debug_view {
+            self.interface.set_side_panels(self.debug_panels());
+            self.interface.update_screen();
+            self.interface.read_command(output)
+        } else {
+            prompt_stdin(output)
+        }
+    }
+
+    // Runs a debugger command, returns whether the machine resumes and the text to show
+    pub fn debug_command(&mut self, command: Command) -> (bool, String) {
+        let pc = self.pc;
+        let sp = self.stackpointer;
+        let at_call = matches!(self.inst_at(pc), Some(Inst::Call { .. }));
+        let watched = match command {
+            Command::Watch(point) => point.reg().map(|reg| self.reg_value(reg)),
+            _ => None,
+        };
+        let debugger = self.debugger.get_or_insert_with(Debugger::new);
+
+        match command {
+            Command::Step(count) => debugger.step(pc, count),
+            Command::Next if at_call => debugger.resume(
+                pc,
+                StopAt::Return {
+                    pc: pc.wrapping_add(2),
+                    sp,
+                },
+            ),
+            Command::Next => debugger.step(pc, 1),
+            Command::Finish if sp == 0 => {
+                return (false, String::from("not inside a subroutine"));
+            }
+            Command::Finish => debugger.resume(pc, StopAt::Out { sp }),
+            Command::Continue => debugger.resume(pc, StopAt::Never),
+            Command::Break(addr) => {
+                debugger.add_breakpoint(addr);
+                return (false, format!("breakpoint at {addr:#06x}"));
+            }
+            Command::Delete(addr) => {
+                let output = if debugger.remove_breakpoint(addr) {
+                    format!("deleted breakpoint at {addr:#06x}")
+                } else {
+                    format!("no breakpoint at {addr:#06x}")
+                };
+                return (false, output);
+            }
+            Command::Watch(point) => {
+                debugger.watches.add(point, watched.unwrap_or_default());
+                return (false, format!("watchpoint: {point}"));
+            }
+            Command::Unwatch(index) => {
+                let output = match debugger.watches.remove(index) {
+                    Some(point) => format!("deleted watchpoint {index}: {point}"),
+                    None => format!("no watchpoint {index}"),
+                };
+                return (false, output);
+            }
+            Command::Info => {
+                let breakpoints: Vec<String> = debugger
+                    .breakpoints()
+                    .map(|addr| format!("{addr:#06x}"))
+                    .collect();
+                let mut output = if breakpoints.is_empty() {
+                    String::from("no breakpoints")
+                } else {
+                    format!("breakpoints: {}", breakpoints.join(" "))
+                };
+                for (index, point) in debugger.watches.list().enumerate() {
+                    let _ = write!(output, "\nwatchpoint {index}: {point}");
+                }
+                return (false, output);
+            }
+            Command::Regs => return (false, self.debug_status()),
+            Command::Help => return (false, String::from(DEBUG_HELP)),
+            Command::Quit => self.running = false,
+        }
+        (true, String::new())
+    }
+
+    // Decodes the instruction at addr, None if it is illegal or outside of memory
+    pub fn inst_at(&self, addr: u16) -> Option<Inst> {
+        let high = *self.memory.get(addr as usize)?;
+        let low = *self.memory.get(addr as usize + 1)?;
+        hex_to_inst(u16::from_be_bytes([high, low]))
+    }
+
+    // Registers, timers, stack and the instruction at pc
+    pub fn debug_status(&self) -> String {
+        let mut out = String::new();
+        let _ = writeln!(
+            out,
+            "pc {:#06x}  I {:#06x}  DT {:02x}  ST {:02x}  SP {}",
+            self.pc, self.registers.i, self.delay_timer, self.sound_timer, self.stackpointer
+        );
+        for row in 0..2 {
+            let regs: Vec<String> = (row * 8..row * 8 + 8)
+                .map(|reg| {
+                    let val = self.registers.get_reg_v(reg).unwrap_or_default();
+                    format!("V{reg:X} {val:02x}")
+                })
+                .collect();
+            let _ = writeln!(out, "{}", regs.join("  "));
+        }
+        let stack: Vec<String> = self.stack[..self.stackpointer as usize]
+            .iter()
+            .map(|addr| format!("{addr:#06x}"))
+            .collect();
+        let _ = writeln!(out, "stack: {}", stack.join(" "));
+
+        let opcode = match (
+            self.memory.get(self.pc as usize),
+            self.memory.get(self.pc as usize + 1),
+        ) {
+            (Some(high), Some(low)) => u16::from_be_bytes([*high, *low]),
+            _ => {
+                let _ = write!(out, "{:#06x}: outside of memory", self.pc);
+                return out;
+            }
+        };
+        let _ = match hex_to_inst(opcode) {
+            Some(inst) => write!(out, "{:#06x}: {opcode:04X}  {inst}", self.pc),
+            None => write!(out, "{:#06x}: {opcode:04X}  illegal instruction", self.pc),
+        };
+        out
+    }
+
+    // Registers, stack, disassembly around pc and memory around I for the debug view
+    pub fn debug_panels(&self) -> Vec<SidePanel> {
+        let mut registers = vec![
+            format!("pc {:#06x}  I {:#06x}", self.pc, self.registers.i),
+            format!(
+                "DT {:02x}  ST {:02x}  SP {}",
+                self.delay_timer, self.sound_timer, self.stackpointer
+            ),
+        ];
+        for row in 0..4 {
+            let regs: Vec<String> = (row * 4..row * 4 + 4)
+                .map(|reg| {
+                    let val = self.registers.get_reg_v(reg).unwrap_or_default();
+                    format!("V{reg:X} {val:02x}")
+                })
+                .collect();
+            registers.push(regs.join("  "));
+        }
+
+        let mut stack: Vec<String> = self.stack[..self.stackpointer as usize]
+            .iter()
+            .enumerate()
+            .map(|(depth, addr)| format!("{depth:2}  {addr:#06x}"))
+            .collect();
+        if stack.is_empty() {
+            stack.push(String::from("(empty)"));
+        }
+
+        let breakpoints: Vec<u16> = self
+            .debugger
+            .as_ref()
+            .map(|d| d.breakpoints().collect())
+            .unwrap_or_default();
+        let first = self.pc.saturating_sub(2 * DEBUG_VIEW_CONTEXT);
+        let mut disassembly = Vec::new();
+        let mut current = None;
+        for addr in (first..=self.pc.saturating_add(2 * DEBUG_VIEW_CONTEXT)).step_by(2) {
+            let (high, low) = match (
+                self.memory.get(addr as usize),
+                self.memory.get(addr as usize + 1),
+            ) {
+                (Some(high), Some(low)) => (*high, *low),
+                _ => break,
+            };
+            let opcode = u16::from_be_bytes([high, low]);
+            let text = match hex_to_inst(opcode) {
+                Some(inst) => inst.to_string(),
+                None => String::from("illegal"),
+            };
+            let marker = if breakpoints.contains(&addr) {
+                '*'
+            } else {
+                ' '
+            };
+            if addr == self.pc {
+                current = Some(disassembly.len());
+            }
+            disassembly.push(format!("{marker}{addr:#06x}  {opcode:04X}  {text}"));
+        }
+
+        let i = self.registers.i as usize;
+        let first_row = (i & !0x7).saturating_sub(8 * DEBUG_VIEW_MEMORY_ROWS / 2);
+        let mut memory = Vec::new();
+        let mut current_row = None;
+        for row in (first_row..).step_by(8).take(DEBUG_VIEW_MEMORY_ROWS) {
+            let bytes = match self.memory.get(row..row + 8) {
+                Some(bytes) => bytes,
+                None => break,
+            };
+            if (row..row + 8).contains(&i) {
+                current_row = Some(memory.len());
+            }
+            let hex: Vec<String> = bytes.iter().map(|byte| format!("{byte:02x}")).collect();
+            memory.push(format!("{row:#06x}  {}", hex.join(" ")));
+        }
+
+        vec![
+            SidePanel {
+                title: String::from("registers"),
+                lines: registers,
+                highlight: None,
+            },
+            SidePanel {
+                title: String::from("stack"),
+                lines: stack,
+                highlight: None,
+            },
+            SidePanel {
+                title: String::from("disassembly"),
+                lines: disassembly,
+                highlight: current,
+            },
+            SidePanel {
+                title: String::from("memory at I"),
+                lines: memory,
+                highlight: current_row,
+            },
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        chip::watch::{CmpOp, Reg},
+        quirks::Quirks,
+        screen::headless::Headless,
+    };
+
+    // Calls a subroutine that adds to v0 twice, then loops
+    const PROGRAM: [u16; 7] = [
+        0x6001, // 0x200 LD V0, 1
+        0x2208, // 0x202 CALL 0x208
+        0x7001, // 0x204 ADD V0, 1
+        0x1206, // 0x206 JP 0x206
+        0x7010, // 0x208 ADD V0, 0x10
+        0x7020, // 0x20A ADD V0, 0x20
+        0x00EE, // 0x20C RET
+    ];
+
+    fn chip(debugger: Debugger) -> Chip<Headless> {
+        let mut chip = Chip::new(PROG_POS_START, Headless::new(), Quirks::default());
+        let rom = PROGRAM.iter().flat_map(|word| word.to_be_bytes()).collect();
+        chip.load_prog(rom).unwrap();
+        chip.debugger = Some(debugger);
+        chip
+    }
+
+    fn execute(chip: &mut Chip<Headless>) {
+        let pc = chip.pc;
+        chip.execute_inst().unwrap();
+        chip.debug_watch(pc);
+    }
+
+    // Runs instructions like the run loop until the debugger pauses, returns the pc
+    fn run_until_break(chip: &mut Chip<Headless>) -> u16 {
+        for _ in 0..100 {
+            if chip.debug_break() {
+                return chip.pc;
+            }
+            execute(chip);
+        }
+        panic!("the debugger did not pause");
+    }
+
+    // Runs the command at a pause, the run loop executes the instruction at pc right after
+    fn resume(chip: &mut Chip<Headless>, command: Command) -> u16 {
+        assert!(chip.debug_command(command).0, "{command:?} did not resume");
+        execute(chip);
+        run_until_break(chip)
+    }
+
+    #[test]
+    fn commands_are_parsed() {
+        let cases = [
+            ("s", Command::Step(1)),
+            ("step 5", Command::Step(5)),
+            ("n", Command::Next),
+            ("next", Command::Next),
+            ("f", Command::Finish),
+            ("finish", Command::Finish),
+            ("c", Command::Continue),
+            ("continue", Command::Continue),
+            ("b 2a0", Command::Break(0x2A0)),
+            ("break 0x2A0", Command::Break(0x2A0)),
+            ("d 0x2a0", Command::Delete(0x2A0)),
+            ("delete 2a0", Command::Delete(0x2A0)),
+            ("u 1", Command::Unwatch(1)),
+            ("unwatch 0", Command::Unwatch(0)),
+            ("i", Command::Info),
+            ("info", Command::Info),
+            ("r", Command::Regs),
+            ("regs", Command::Regs),
+            ("h", Command::Help),
+            ("help", Command::Help),
+            ("q", Command::Quit),
+            ("quit", Command::Quit),
+            (
+                "watch 300-30f",
+                Command::Watch(Watchpoint::Memory {
+                    start: 0x300,
+                    end: 0x30F,
+                    access: Access::Write,
+                }),
+            ),
+            ("w v3", Command::Watch(Watchpoint::Change(Reg::V(3)))),
+            (
+                "watch v3 == 10",
+                Command::Watch(Watchpoint::Condition {
+                    reg: Reg::V(3),
+                    op: CmpOp::Eq,
+                    value: 0x10,
+                }),
+            ),
+            (
+                "rwatch 0x300",
+                Command::Watch(Watchpoint::Memory {
+                    start: 0x300,
+                    end: 0x300,
+                    access: Access::Read,
+                }),
+            ),
+            (
+                "awatch 300",
+                Command::Watch(Watchpoint::Memory {
+                    start: 0x300,
+                    end: 0x300,
+                    access: Access::ReadWrite,
+                }),
+            ),
+        ];
+        for (line, expected) in cases {
+            assert_eq!(parse_command(line), Ok(expected), "{line}");
+        }
+
+        let errors = [
+            ("", "unknown command:  (try help)"),
+            ("jump", "unknown command: jump (try help)"),
+            ("step x", "invalid step count: x"),
+            ("b", "missing address"),
+            ("b 12g", "invalid number: 12g"),
+            ("u", "missing watchpoint number"),
+            ("u one", "invalid watchpoint number: one"),
+            ("w 30f-300", "invalid range: 30f-300"),
+            ("rwatch v3", "invalid number: v3"),
+        ];
+        for (line, expected) in errors {
+            assert_eq!(parse_command(line), Err(String::from(expected)), "{line}");
+        }
+    }
+
+    #[test]
+    fn step_next_and_finish() {
+        let mut chip = chip(Debugger::new());
+        assert_eq!(run_until_break(&mut chip), 0x200);
+        assert_eq!(resume(&mut chip, Command::Step(1)), 0x202);
+
+        // Next runs the whole subroutine
+        assert_eq!(resume(&mut chip, Command::Next), 0x204);
+        assert_eq!(chip.registers.get_reg_v(0), Ok(0x31));
+        assert_eq!(chip.stackpointer, 0);
+        assert_eq!(
+            chip.debug_command(Command::Finish),
+            (false, String::from("not inside a subroutine"))
+        );
+
+        // Step enters it, finish stops after the matching return
+        let mut chip = self::chip(Debugger::new());
+        run_until_break(&mut chip);
+        assert_eq!(resume(&mut chip, Command::Step(2)), 0x208);
+        assert_eq!(chip.stackpointer, 1);
+        assert_eq!(resume(&mut chip, Command::Finish), 0x204);
+        assert_eq!(chip.stackpointer, 0);
+        assert_eq!(chip.registers.get_reg_v(0), Ok(0x31));
+    }
+
+    #[test]
+    fn breakpoints_pause_once_per_hit() {
+        let mut debugger = Debugger::running();
+        debugger.add_breakpoint(0x20A);
+        let mut chip = chip(debugger);
+        assert_eq!(run_until_break(&mut chip), 0x20A);
+        assert_eq!(chip.registers.get_reg_v(0), Ok(0x11));
+
+        // Continuing from a breakpoint does not stop at it again
+        assert_eq!(
+            chip.debug_command(Command::Break(0x206)),
+            (false, String::from("breakpoint at 0x0206"))
+        );
+        assert_eq!(resume(&mut chip, Command::Continue), 0x206);
+        assert_eq!(chip.registers.get_reg_v(0), Ok(0x32));
+        assert_eq!(
+            chip.debug_command(Command::Info).1,
+            "breakpoints: 0x0206 0x020a"
+        );
+        assert_eq!(
+            chip.debug_command(Command::Delete(0x206)),
+            (false, String::from("deleted breakpoint at 0x0206"))
+        );
+        assert_eq!(
+            chip.debug_command(Command::Delete(0x206)),
+            (false, String::from("no breakpoint at 0x0206"))
+        );
+    }
+
+    #[test]
+    fn panels_follow_pc_and_i() {
+        let mut chip = chip(Debugger::running());
+        chip.debugger.as_mut().unwrap().add_breakpoint(0x208);
+        chip.pc = 0x20C;
+        chip.registers.i = 0x305;
+        chip.memory[0x305] = 0xAB;
+        let panels = chip.debug_panels();
+
+        // The window holds DEBUG_VIEW_CONTEXT instructions on either side of pc
+        let disassembly = &panels[2];
+        let context = DEBUG_VIEW_CONTEXT as usize;
+        assert_eq!(disassembly.lines.len(), 2 * context + 1);
+        assert_eq!(disassembly.highlight, Some(context));
+        assert!(disassembly.lines[0].starts_with(" 0x0202  2208"));
+        assert!(disassembly.lines[context - 2].starts_with("*0x0208  7010"));
+        assert!(disassembly.lines[context].starts_with(" 0x020c  00EE"));
+
+        let memory = &panels[3];
+        assert_eq!(memory.lines.len(), DEBUG_VIEW_MEMORY_ROWS);
+        assert_eq!(memory.highlight, Some(DEBUG_VIEW_MEMORY_ROWS / 2));
+        assert_eq!(
+            memory.lines[DEBUG_VIEW_MEMORY_ROWS / 2],
+            "0x0300  00 00 00 00 00 ab 00 00"
+        );
+
+        chip.registers.i = 0x40A;
+        let memory = &chip.debug_panels()[3];
+        let row = memory.highlight.unwrap();
+        assert!(
+            memory.lines[row].starts_with("0x0408"),
+            "{}",
+            memory.lines[row]
+        );
+
+        // At the start of memory pc and I are in the first line
+        chip.pc = 0;
+        chip.registers.i = 3;
+        let panels = chip.debug_panels();
+        assert_eq!(panels[2].highlight, Some(0));
+        assert!(panels[2].lines[0].starts_with(" 0x0000"));
+        assert_eq!(panels[3].highlight, Some(0));
+        assert!(panels[3].lines[0].starts_with("0x0000"));
+    }
+}
@@ -0,0 +1,618 @@
+use std::{
+    collections::VecDeque,
+    io::{self, Read, Write},
+    net::{TcpListener, TcpStream},
+};
+
+use super::{
+    debugger::{Debugger, StopAt},
+    watch::{Access, Reg, Watchpoint},
+    Chip,
+};
+use crate::{error::ChipError, screen::Interface};
+
+// Signals reported in stop replies
+const SIGILL: u8 = 4;
+const SIGSEGV: u8 = 11;
+
+// Register numbers used by gdb. Words are sent big endian like everywhere else on the
+// machine, so clients need `set endian big`
+const REGISTERS: [Reg; 21] = [
+    Reg::V(0x0),
+    Reg::V(0x1),
+    Reg::V(0x2),
+    Reg::V(0x3),
+    Reg::V(0x4),
+    Reg::V(0x5),
+    Reg::V(0x6),
+    Reg::V(0x7),
+    Reg::V(0x8),
+    Reg::V(0x9),
+    Reg::V(0xA),
+    Reg::V(0xB),
+    Reg::V(0xC),
+    Reg::V(0xD),
+    Reg::V(0xE),
+    Reg::V(0xF),
+    Reg::I,
+    Reg::Pc,
+    Reg::Sp,
+    Reg::Dt,
+    Reg::St,
+];
+
+const TARGET_XML: &str = r#"<?xml version="1.0"?>
+<!DOCTYPE target SYSTEM "gdb-target.dtd">
+<target version="1.0">
+  <feature name="org.chip8.cpu">
+    <reg name="v0" bitsize="8" regnum="0"/>
+    <reg name="v1" bitsize="8"/>
+    <reg name="v2" bitsize="8"/>
+    <reg name="v3" bitsize="8"/>
+    <reg name="v4" bitsize="8"/>
+    <reg name="v5" bitsize="8"/>
+    <reg name="v6" bitsize="8"/>
+    <reg name="v7" bitsize="8"/>
+    <reg name="v8" bitsize="8"/>
+    <reg name="v9" bitsize="8"/>
+    <reg name="va" bitsize="8"/>
+    <reg name="vb" bitsize="8"/>
+    <reg name="vc" bitsize="8"/>
+    <reg name="vd" bitsize="8"/>
+    <reg name="ve" bitsize="8"/>
+    <reg name="vf" bitsize="8"/>
+    <reg name="i" bitsize="16" type="data_ptr"/>
+    <reg name="pc" bitsize="16" type="code_ptr"/>
+    <reg name="sp" bitsize="8"/>
+    <reg name="dt" bitsize="8"/>
+    <reg name="st" bitsize="8"/>
+  </feature>
+</target>
+"#;
+
+fn reg_size(reg: Reg) -> usize {
+    match reg {
+        Reg::I | Reg::Pc => 2,
+        _ => 1,
+    }
+}
+
+fn checksum(data: &[u8]) -> u8 {
+    data.iter().fold(0, |sum, byte| sum.wrapping_add(*byte))
+}
+
+fn hex_encode(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+fn hex_decode(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+fn parse_usize(hex: &str) -> Option<usize> {
+    usize::from_str_radix(hex, 16).ok()
+}
+
+// What the stub does after a packet was handled
+enum Action {
+    Reply(String),
+    // Reply with OK, then stop acknowledging packets
+    NoAck,
+    // Run the machine until it pauses again, the stop reply is sent then
+    Resume,
+    Detach,
+    Kill,
+}
+
+// GDB remote serial protocol connection, the machine is driven through the debugger
+pub struct GdbStub {
+    stream: TcpStream,
+    buffer: VecDeque<u8>,
+    ack: bool,
+    // The machine runs for gdb, a stop reply is owed once it pauses
+    running: bool,
+}
+
+impl GdbStub {
+    // Waits for gdb to connect
+    pub fn accept(listener: &TcpListener) -> io::Result<Self> {
+        let (stream, _) = listener.accept()?;
+        stream.set_nodelay(true)?;
+        Ok(GdbStub {
+            stream,
+            buffer: VecDeque::new(),
+            ack: true,
+            running: false,
+        })
+    }
+
+    fn fill_buffer(&mut self) -> io::Result<()> {
+        let mut chunk = [0; 1024];
+        let len = self.stream.read(&mut chunk)?;
+        if len == 0 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        self.buffer.extend(&chunk[..len]);
+        Ok(())
+    }
+
+    fn read_byte(&mut self) -> io::Result<u8> {
+        loop {
+            if let Some(byte) = self.buffer.pop_front() {
+                return Ok(byte);
+            }
+            self.fill_buffer()?;
+        }
+    }
+
+    // Reads and acknowledges the next packet, bytes outside of packets are skipped
+    fn read_packet(&mut self) -> io::Result<String> {
+        loop {
+            if self.read_byte()? != b'$' {
+                continue;
+            }
+            let mut data = Vec::new();
+            loop {
+                match self.read_byte()? {
+                    b'#' => break,
+                    byte => data.push(byte),
+                }
+            }
+            let sum = [self.read_byte()?, self.read_byte()?];
+            let valid = std::str::from_utf8(&sum)
+                .ok()
+                .and_then(|sum| u8::from_str_radix(sum, 16).ok())
+                == Some(checksum(&data));
+            if self.ack {
+                self.stream.write_all(if valid { b"+" } else { b"-" })?;
+            }
+            if valid {
+                return Ok(String::from_utf8_lossy(&data).into_owned());
+            }
+        }
+    }
+
+    fn send(&mut self, data: &str) -> io::Result<()> {
+        let packet = format!("${data}#{:02x}", checksum(data.as_bytes()));
+        loop {
+            self.stream.write_all(packet.as_bytes())?;
+            if !self.ack {
+                return Ok(());
+            }
+            match self.read_byte()? {
+                b'+' => return Ok(()),
+                b'-' => continue,
+                byte => {
+                    // Not an ack, leave it for the next packet
+                    self.buffer.push_front(byte);
+                    return Ok(());
+                }
+            }
+        }
+    }
+
+    // Checks for a ctrl-c from gdb without blocking
+    fn poll_interrupt(&mut self) -> io::Result<bool> {
+        self.stream.set_nonblocking(true)?;
+        let result = self.fill_buffer();
+        self.stream.set_nonblocking(false)?;
+        match result {
+            Err(err) if err.kind() != io::ErrorKind::WouldBlock => return Err(err),
+            _ => {}
+        }
+        match self.buffer.iter().position(|byte| *byte == 0x03) {
+            Some(index) => {
+                self.buffer.remove(index);
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+}
+
+impl<T: Interface> Chip<T> {
+    // Serves gdb while the machine is paused, returns once gdb resumes it
+    pub(super) fn gdb_prompt(&mut self) {
+        let mut gdb = match self.gdb.take() {
+            Some(gdb) => gdb,
+            None => return,
+        };
+        match self.gdb_serve(&mut gdb) {
+            Ok(true) => self.gdb = Some(gdb),
+            // gdb detached or the connection broke, keep running without it
+            Ok(false) | Err(_) => self.gdb_detach(),
+        }
+    }
+
+    // Called once per frame, a ctrl-c from gdb pauses the machine
+    pub(super) fn gdb_poll(&mut self) {
+        let result = match self.gdb.as_mut() {
+            Some(gdb) if gdb.running => gdb.poll_interrupt(),
+            _ => return,
+        };
+        match result {
+            Ok(true) => self.debugger.get_or_insert_with(Debugger::new).pause(),
+            Ok(false) => {}
+            Err(_) => {
+                self.gdb = None;
+                self.gdb_detach();
+            }
+        }
+    }
+
+    // Tells gdb that the program exited
+    pub(super) fn gdb_exit(&mut self) {
+        if let Some(mut gdb) = self.gdb.take() {
+            let _ = gdb.send("W00");
+        }
+    }
+
+    // Reports a fault as a signal, gdb can inspect the machine until it resumes it, which ends the program
+    pub(super) fn gdb_fault(&mut self, err: &ChipError) {
+        let Some(mut gdb) = self.gdb.take() else {
+            return;
+        };
+        let signal = match err {
+            ChipError::IllegalInstruction { .. } => SIGILL,
+            _ => SIGSEGV,
+        };
+        if gdb.running {
+            gdb.running = false;
+            if gdb.send(&format!("S{signal:02x}")).is_err() {
+                return;
+            }
+            if !matches!(self.gdb_serve(&mut gdb), Ok(true)) {
+                return;
+            }
+        }
+        let _ = gdb.send(&format!("X{signal:02x}"));
+    }
+
+    fn gdb_detach(&mut self) {
+        let pc = self.pc;
+        if let Some(debugger) = self.debugger.as_mut() {
+            debugger.resume(pc, StopAt::Never);
+        }
+    }
+
+    // Returns false once gdb detached or killed the machine
+    fn gdb_serve(&mut self, gdb: &mut GdbStub) -> io::Result<bool> {
+        if gdb.running {
+            gdb.running = false;
+            gdb.send("S05")?;
+        }
+        loop {
+            let packet = gdb.read_packet()?;
+            match self.gdb_command(&packet) {
+                Action::Reply(reply) => gdb.send(&reply)?,
+                Action::NoAck => {
+                    gdb.send("OK")?;
+                    gdb.ack = false;
+                }
+                Action::Resume => {
+                    gdb.running = true;
+                    return Ok(true);
+                }
+                Action::Detach => {
+                    gdb.send("OK")?;
+                    return Ok(false);
+                }
+                Action::Kill => {
+                    self.running = false;
+                    return Ok(false);
+                }
+            }
+        }
+    }
+
+    fn gdb_command(&mut self, packet: &str) -> Action {
+        let error = || Action::Reply(String::from("E01"));
+        let ok = || Action::Reply(String::from("OK"));
+        let (command, args) = match packet.char_indices().nth(1) {
+            Some((index, _)) => packet.split_at(index),
+            None => (packet, ""),
+        };
+
+        match command {
+            "?" => Action::Reply(String::from("S05")),
+            "g" => Action::Reply(REGISTERS.iter().map(|reg| self.gdb_reg(*reg)).collect()),
+            "G" => {
+                let mut rest = args;
+                for reg in REGISTERS {
+                    let len = 2 * reg_size(reg);
+                    match rest
+                        .get(..len)
+                        .and_then(|hex| u16::from_str_radix(hex, 16).ok())
+                    {
+                        Some(val) => self.set_reg_value(reg, val),
+                        None => return error(),
+                    }
+                    rest = &rest[len..];
+                }
+                ok()
+            }
+            "p" => match parse_usize(args).and_then(|n| REGISTERS.get(n)) {
+                Some(reg) => Action::Reply(self.gdb_reg(*reg)),
+                None => error(),
+            },
+            "P" => {
+                let (n, val) = match args.split_once('=') {
+                    Some(assign) => assign,
+                    None => return error(),
+                };
+                match (
+                    parse_usize(n).and_then(|n| REGISTERS.get(n)),
+                    u16::from_str_radix(val, 16),
+                ) {
+                    (Some(reg), Ok(val)) => {
+                        self.set_reg_value(*reg, val);
+                        ok()
+                    }
+                    _ => error(),
+                }
+            }
+            "m" => {
+                let range = args
+                    .split_once(',')
+                    .and_then(|(addr, len)| Some((parse_usize(addr)?, parse_usize(len)?)));
+                let range = range.and_then(|(addr, len)| Some(addr..addr.checked_add(len)?));
+                match range.and_then(|range| self.memory.get(range)) {
+                    Some(bytes) => Action::Reply(hex_encode(bytes)),
+                    None => error(),
+                }
+            }
+            "M" => {
+                let write = args.split_once(':').and_then(|(range, data)| {
+                    let (addr, len) = range.split_once(',')?;
+                    let (addr, len, data) =
+                        (parse_usize(addr)?, parse_usize(len)?, hex_decode(data)?);
+                    (data.len() == len).then_some((addr, data))
+                });
+                let (addr, data) = match write {
+                    Some(write) => write,
+                    None => return error(),
+                };
+                let end = match addr.checked_add(data.len()) {
+                    Some(end) => end,
+                    None => return error(),
+                };
+                match self.memory.get_mut(addr..end) {
+                    Some(memory) => {
+                        memory.copy_from_slice(&data);
+                        ok()
+                    }
+                    None => error(),
+                }
+            }
+            "Z" | "z" => {
+                let mut fields = args.split(',');
+                let (kind, addr, len) = match (
+                    fields.next(),
+                    fields.next().and_then(parse_usize),
+                    fields.next().and_then(parse_usize),
+                ) {
+                    (Some(kind), Some(addr), Some(len)) if addr <= u16::MAX as usize => {
+                        (kind, addr as u16, len.max(1) as u16)
+                    }
+                    _ => return error(),
+                };
+                let access = match kind {
+                    "0" | "1" => None,
+                    "2" => Some(Access::Write),
+                    "3" => Some(Access::Read),
+                    "4" => Some(Access::ReadWrite),
+                    _ => return Action::Reply(String::new()),
+                };
+                let debugger = self.debugger.get_or_insert_with(Debugger::new);
+                match (access, command) {
+                    (None, "Z") => debugger.add_breakpoint(addr),
+                    (None, _) => {
+                        debugger.remove_breakpoint(addr);
+                    }
+                    (Some(access), _) => {
+                        let point = Watchpoint::Memory {
+                            start: addr,
+                            end: addr.saturating_add(len - 1),
+                            access,
+                        };
+                        if command == "Z" {
+                            debugger.watches.add(point, 0);
+                        } else {
+                            debugger.watches.remove_point(&point);
+                        }
+                    }
+                }
+                ok()
+            }
+            "s" | "c" => {
+                if let Some(addr) = parse_usize(args) {
+                    self.pc = addr as u16;
+                }
+                let pc = self.pc;
+                let debugger = self.debugger.get_or_insert_with(Debugger::new);
+                if command == "s" {
+                    debugger.step(pc, 1);
+                } else {
+                    debugger.resume(pc, StopAt::Never);
+                }
+                Action::Resume
+            }
+            "D" => Action::Detach,
+            "k" => Action::Kill,
+            "H" => ok(),
+            "Q" if args == "StartNoAckMode" => Action::NoAck,
+            "q" => Action::Reply(self.gdb_query(args)),
+            _ => Action::Reply(String::new()),
+        }
+    }
+
+    fn gdb_query(&self, query: &str) -> String {
+        if query.starts_with("Supported") {
+            return String::from("PacketSize=4000;qXfer:features:read+;QStartNoAckMode+");
+        }
+        if let Some(range) = query.strip_prefix("Xfer:features:read:target.xml:") {
+            let (offset, len) = match range
+                .split_once(',')
+                .and_then(|(offset, len)| Some((parse_usize(offset)?, parse_usize(len)?)))
+            {
+                Some(range) => range,
+                None => return String::from("E01"),
+            };
+            let start = offset.min(TARGET_XML.len());
+            let end = (start + len).min(TARGET_XML.len());
+            let prefix = if end == TARGET_XML.len() { 'l' } else { 'm' };
+            return format!("{prefix}{}", &TARGET_XML[start..end]);
+        }
+        match query {
+            "Attached" => String::from("1"),
+            "C" => String::from("QC1"),
+            "fThreadInfo" => String::from("m1"),
+            "sThreadInfo" => String::from("l"),
+            _ => String::new(),
+        }
+    }
+
+    fn gdb_reg(&self, reg: Reg) -> String {
+        let val = self.reg_value(reg);
+        match reg_size(reg) {
+            2 => format!("{val:04x}"),
+            _ => format!("{:02x}", val as u8),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{net::TcpListener, thread};
+
+    use super::*;
+    use crate::{config::PROG_POS_START, quirks::Quirks, screen::headless::Headless};
+
+    // Sends a packet and returns the reply, acknowledging both directions
+    fn request(stream: &mut TcpStream, data: &str) -> String {
+        let packet = format!("${data}#{:02x}", checksum(data.as_bytes()));
+        stream.write_all(packet.as_bytes()).unwrap();
+        let mut byte = [0];
+        stream.read_exact(&mut byte).unwrap();
+        assert_eq!(byte[0], b'+', "packet {data} was not acknowledged");
+        if data == "k" {
+            return String::new();
+        }
+
+        loop {
+            stream.read_exact(&mut byte).unwrap();
+            if byte[0] == b'$' {
+                break;
+            }
+        }
+        let mut reply = Vec::new();
+        loop {
+            stream.read_exact(&mut byte).unwrap();
+            if byte[0] == b'#' {
+                break;
+            }
+            reply.push(byte[0]);
+        }
+        let mut sum = [0; 2];
+        stream.read_exact(&mut sum).unwrap();
+        assert_eq!(
+            u8::from_str_radix(std::str::from_utf8(&sum).unwrap(), 16).unwrap(),
+            checksum(&reply)
+        );
+        stream.write_all(b"+").unwrap();
+        String::from_utf8(reply).unwrap()
+    }
+
+    #[test]
+    fn gdb_client_controls_the_machine() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let client = thread::spawn(move || {
+            let mut stream = TcpStream::connect(addr).unwrap();
+            assert!(request(&mut stream, "qSupported:xmlRegisters=i386").contains("qXfer"));
+            assert_eq!(request(&mut stream, "?"), "S05");
+            // v0-vf, I, pc, sp, dt, st
+            assert_eq!(
+                request(&mut stream, "g"),
+                format!("{}0000{:04x}000000", "00".repeat(16), PROG_POS_START)
+            );
+            assert_eq!(request(&mut stream, "m200,4"), "60017001");
+
+            assert_eq!(request(&mut stream, "Z0,204,2"), "OK");
+            assert_eq!(request(&mut stream, "c"), "S05");
+            assert_eq!(request(&mut stream, "p11"), "0204");
+            assert_eq!(request(&mut stream, "p0"), "02");
+            assert_eq!(request(&mut stream, "z0,204,2"), "OK");
+
+            assert_eq!(request(&mut stream, "s"), "S05");
+            assert_eq!(request(&mut stream, "p11"), "0206");
+            assert_eq!(request(&mut stream, "p0"), "03");
+
+            assert_eq!(request(&mut stream, "P0=40"), "OK");
+            assert_eq!(request(&mut stream, "M300,2:abcd"), "OK");
+            assert_eq!(request(&mut stream, "m300,2"), "abcd");
+            assert_eq!(request(&mut stream, "mffffffffffffffff,10"), "E01");
+            assert_eq!(request(&mut stream, "Mffffffffffffffff,1:ab"), "E01");
+            assert_eq!(request(&mut stream, "s"), "S05");
+            assert_eq!(request(&mut stream, "p0"), "41");
+
+            request(&mut stream, "k");
+        });
+
+        let mut interface = Headless::new();
+        // A failing client can not leave the machine running forever
+        interface.close_after(600);
+        let mut chip = Chip::new(PROG_POS_START, interface, Quirks::default());
+        // v0 = 1, then add 1 to v0 three times and loop forever
+        chip.load_prog(vec![
+            0x60, 0x01, 0x70, 0x01, 0x70, 0x01, 0x70, 0x01, 0x12, 0x08,
+        ])
+        .unwrap();
+        chip.debugger = Some(Debugger::new());
+        chip.gdb = Some(GdbStub::accept(&listener).unwrap());
+        chip.run().unwrap();
+
+        client.join().unwrap();
+        assert!(!chip.running);
+        assert_eq!(&chip.memory[0x300..0x302], &[0xAB, 0xCD]);
+    }
+
+    #[test]
+    fn gdb_is_told_about_faults() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let client = thread::spawn(move || {
+            let mut stream = TcpStream::connect(addr).unwrap();
+            assert_eq!(request(&mut stream, "c"), "S04");
+            // The faulting instruction can still be inspected
+            assert_eq!(request(&mut stream, "p11"), "0202");
+            assert_eq!(request(&mut stream, "m202,2"), "ffff");
+            assert_eq!(request(&mut stream, "c"), "X04");
+        });
+
+        let mut interface = Headless::new();
+        interface.close_after(600);
+        let mut chip = Chip::new(PROG_POS_START, interface, Quirks::default());
+        chip.load_prog(vec![0x60, 0x01, 0xFF, 0xFF]).unwrap();
+        chip.debugger = Some(Debugger::new());
+        chip.gdb = Some(GdbStub::accept(&listener).unwrap());
+        let result = chip.run();
+
+        client.join().unwrap();
+        assert_eq!(
+            result,
+            Err(ChipError::IllegalInstruction {
+                addr: 0x202,
+                opcode: 0xFFFF
+            })
+        );
+    }
+}
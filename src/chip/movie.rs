@@ -0,0 +1,345 @@
+use std::{fmt, fs, io, path::Path};
+
+use super::Chip;
+use crate::{config::*, quirks::Quirks, rng::RngKind, screen::Interface};
+
+// Movie files start with the magic and a version, followed by the rom hash (u64),
+// rng seed (u64), rng kind (u8, since version 2), quirks (7 bytes), frame count (u32),
+// event count (u32) and the events as (frame u32, keypad u16). All numbers are little endian.
+const MAGIC: &[u8; 4] = b"CH8M";
+const VERSION: u16 = 2;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MovieError {
+    // Data does not start with the movie magic
+    BadMagic,
+    // Movie was written by a newer, incompatible version
+    UnsupportedVersion(u16),
+    // Data ends before all events were read
+    Truncated,
+    // A field holds a value no movie is written with
+    Invalid(&'static str),
+    // Movie was recorded with a different rom
+    RomMismatch { expected: u64, actual: u64 },
+}
+
+impl fmt::Display for MovieError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MovieError::BadMagic => write!(f, "not a movie file"),
+            MovieError::UnsupportedVersion(version) => {
+                write!(f, "unsupported movie version {version}")
+            }
+            MovieError::Truncated => write!(f, "movie file is truncated"),
+            MovieError::Invalid(field) => write!(f, "movie file has an invalid {field}"),
+            MovieError::RomMismatch { expected, actual } => write!(
+                f,
+                "movie was recorded with a different rom (hash {expected:016x}, rom is {actual:016x})"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for MovieError {}
+
+// 64 bit FNV-1a, identifies the rom a movie was recorded with
+pub fn rom_hash(rom: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in rom {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+// Keypad state changes per frame, together with everything else needed to replay them
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Movie {
+    pub rom_hash: u64,
+    pub seed: u64,
+    pub rng_kind: RngKind,
+    pub quirks: Quirks,
+    pub frames: u32,
+    events: Vec<(u32, u16)>,
+}
+
+#[allow(dead_code)]
+impl Movie {
+    pub fn new(rom_hash: u64, seed: u64, rng_kind: RngKind, quirks: Quirks) -> Self {
+        Movie {
+            rom_hash,
+            seed,
+            rng_kind,
+            quirks,
+            frames: 0,
+            events: Vec::new(),
+        }
+    }
+
+    // Records the keypad state of the frame, only changes are stored
+    pub fn record(&mut self, frame: u32, keypad: u16) {
+        if self.events.last().map(|(_, keys)| *keys) != Some(keypad) {
+            self.events.push((frame, keypad));
+        }
+        self.frames = self.frames.max(frame + 1);
+    }
+
+    // Keypad state during the frame, the last state is held after the final event
+    pub fn keypad_at(&self, frame: u32) -> u16 {
+        match self.events.partition_point(|(f, _)| *f <= frame) {
+            0 => 0,
+            index => self.events[index - 1].1,
+        }
+    }
+
+    pub fn check_rom(&self, rom: &[u8]) -> Result<(), MovieError> {
+        let actual = rom_hash(rom);
+        if actual != self.rom_hash {
+            return Err(MovieError::RomMismatch {
+                expected: self.rom_hash,
+                actual,
+            });
+        }
+        Ok(())
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(38 + 6 * self.events.len());
+        out.extend_from_slice(MAGIC);
+        out.extend_from_slice(&VERSION.to_le_bytes());
+        out.extend_from_slice(&self.rom_hash.to_le_bytes());
+        out.extend_from_slice(&self.seed.to_le_bytes());
+        out.push(self.rng_kind.to_byte());
+        out.extend_from_slice(&self.quirks.to_bytes());
+        out.extend_from_slice(&self.frames.to_le_bytes());
+        out.extend_from_slice(&(self.events.len() as u32).to_le_bytes());
+        for (frame, keypad) in self.events.iter() {
+            out.extend_from_slice(&frame.to_le_bytes());
+            out.extend_from_slice(&keypad.to_le_bytes());
+        }
+        out
+    }
+
+    pub fn from_bytes(data: &[u8]) -> Result<Self, MovieError> {
+        if data.len() < 6 || &data[0..4] != MAGIC {
+            return Err(MovieError::BadMagic);
+        }
+        let version = u16::from_le_bytes([data[4], data[5]]);
+        if version > VERSION {
+            return Err(MovieError::UnsupportedVersion(version));
+        }
+
+        let mut pos = 6;
+        let mut take = |len: usize| -> Result<&[u8], MovieError> {
+            let bytes = data.get(pos..pos + len).ok_or(MovieError::Truncated)?;
+            pos += len;
+            Ok(bytes)
+        };
+        let u64_at = |bytes: &[u8]| u64::from_le_bytes(bytes.try_into().unwrap());
+        let u32_at = |bytes: &[u8]| u32::from_le_bytes(bytes.try_into().unwrap());
+
+        let rom_hash = u64_at(take(8)?);
+        let seed = u64_at(take(8)?);
+        // Version 1 movies were always recorded with the std rng
+        let rng_kind = if version >= 2 {
+            RngKind::from_byte(take(1)?[0]).ok_or(MovieError::Invalid("rng kind"))?
+        } else {
+            RngKind::Std
+        };
+        let quirks = Quirks::from_bytes(take(7)?).ok_or(MovieError::Invalid("quirks"))?;
+        let frames = u32_at(take(4)?);
+        let count = u32_at(take(4)?) as usize;
+        let mut events = Vec::with_capacity(count.min(data.len() / 6));
+        for _ in 0..count {
+            let event = take(6)?;
+            events.push((
+                u32_at(&event[0..4]),
+                u16::from_le_bytes([event[4], event[5]]),
+            ));
+        }
+
+        Ok(Movie {
+            rom_hash,
+            seed,
+            rng_kind,
+            quirks,
+            frames,
+            events,
+        })
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        fs::write(path, self.to_bytes())
+    }
+
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let data = fs::read(path)?;
+        Movie::from_bytes(&data).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+}
+
+pub enum MovieMode {
+    Recording(Movie),
+    Playing(Movie),
+}
+
+#[allow(dead_code)]
+impl<T: Interface> Chip<T> {
+    // Starts recording input from the current frame on, the machine is reseeded
+    // so the movie captures the rng state
+    pub fn start_recording(&mut self, rom: &[u8]) {
+        let movie = Movie::new(rom_hash(rom), self.seed, self.rng_kind, self.quirks);
+        self.set_seed(self.seed);
+        self.frame = 0;
+        self.movie = Some(MovieMode::Recording(movie));
+    }
+
+    // Replays the movie instead of reading the interface, restores its rng and quirks.
+    // Has to be called before the rom is loaded, since quirks decide the memory size
+    pub fn start_playback(&mut self, movie: Movie) {
+        self.seed = movie.seed;
+        self.set_rng_kind(movie.rng_kind);
+        self.quirks = movie.quirks;
+        let memsize = if self.quirks.xo_chip {
+            XO_CHIP_MEMSIZE
+        } else {
+            MEMSIZE
+        };
+        self.memory.resize(memsize, 0);
+        self.frame = 0;
+        self.movie = Some(MovieMode::Playing(movie));
+    }
+
+    // Stops recording or playback, returns the movie
+    pub fn stop_movie(&mut self) -> Option<Movie> {
+        match self.movie.take()? {
+            MovieMode::Recording(movie) | MovieMode::Playing(movie) => Some(movie),
+        }
+    }
+
+    // Keypad state for the current frame, from the movie or the interface
+    pub(super) fn sample_keypad(&mut self) -> u16 {
+        let frame = self.frame;
+        match self.movie.as_mut() {
+            Some(MovieMode::Playing(movie)) => {
+                if frame >= movie.frames {
+                    self.running = false;
+                }
+                movie.keypad_at(frame)
+            }
+            Some(MovieMode::Recording(movie)) => {
+                let keypad = keypad_from_keys(&self.interface.get_keys_pressed());
+                movie.record(frame, keypad);
+                keypad
+            }
+            None => keypad_from_keys(&self.interface.get_keys_pressed()),
+        }
+    }
+}
+
+fn keypad_from_keys(keys: &[u8]) -> u16 {
+    keys.iter()
+        .filter(|key| **key < 16)
+        .fold(0, |keypad, key| keypad | (1 << key))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::screen::headless::Headless;
+
+    // Counts frames with key 1 held in v3 and draws its digit at random places
+    const ROM: [u8; 16] = [
+        0x65, 0x01, // LD V5, 1
+        0xE5, 0xA1, // SKNP V5
+        0x73, 0x01, // ADD V3, 1
+        0xC1, 0x3F, // RND V1, 0x3F
+        0xC2, 0x1F, // RND V2, 0x1F
+        0xF3, 0x29, // LD F, V3
+        0xD1, 0x25, // DRW V1, V2, 5
+        0x12, 0x02, // JP 0x202
+    ];
+
+    fn chip(interface: Headless) -> Chip<Headless> {
+        let mut chip = Chip::new(PROG_POS_START, interface, Quirks::default());
+        chip.seed = 42;
+        chip
+    }
+
+    #[test]
+    fn replays_are_identical() {
+        let mut interface = Headless::new();
+        interface.tap(3, 1, 4);
+        interface.tap(20, 1, 10);
+        interface.close_after(40);
+        let mut recorded = chip(interface);
+        recorded.load_prog(ROM.to_vec()).unwrap();
+        recorded.start_recording(&ROM);
+        recorded.run().unwrap();
+        let movie = recorded.stop_movie().unwrap();
+        assert_eq!(movie.frames, 40);
+        assert_eq!((movie.keypad_at(2), movie.keypad_at(3)), (0, 0b10));
+
+        // Nothing is pressed on the replaying interface, the keys come from the movie
+        let mut replayed = chip(Headless::new());
+        replayed.seed = 7;
+        replayed.start_playback(movie);
+        replayed.load_prog(ROM.to_vec()).unwrap();
+        replayed.run().unwrap();
+
+        assert_eq!(replayed.frame, recorded.frame);
+        assert_eq!(
+            (replayed.pc, replayed.registers.i),
+            (recorded.pc, recorded.registers.i)
+        );
+        for reg in 0..16 {
+            assert_eq!(
+                replayed.registers.get_reg_v(reg),
+                recorded.registers.get_reg_v(reg),
+                "v{reg:X}"
+            );
+        }
+        assert_ne!(recorded.registers.get_reg_v(3), Ok(0));
+        assert_eq!(
+            replayed.interface.framebuffer().pixels(),
+            recorded.interface.framebuffer().pixels()
+        );
+    }
+
+    #[test]
+    fn movies_round_trip_and_reject_bad_data() {
+        let mut movie = Movie::new(rom_hash(&ROM), 42, RngKind::CosmacVip, Quirks::default());
+        movie.record(0, 0);
+        movie.record(5, 0b10);
+        movie.record(9, 0);
+        let data = movie.to_bytes();
+        assert_eq!(Movie::from_bytes(&data), Ok(movie.clone()));
+
+        assert_eq!(movie.check_rom(&ROM), Ok(()));
+        let mut other = ROM;
+        other[1] = 2;
+        assert_eq!(
+            movie.check_rom(&other),
+            Err(MovieError::RomMismatch {
+                expected: rom_hash(&ROM),
+                actual: rom_hash(&other),
+            })
+        );
+
+        // The rng kind follows the header, rom hash and seed, the quirks follow it
+        let mut bad_rng = data.clone();
+        bad_rng[22] = 9;
+        let mut bad_quirks = data.clone();
+        bad_quirks[23] = 2;
+        let cases = [
+            (&data[..3], MovieError::BadMagic),
+            (&data[..22], MovieError::Truncated),
+            (&data[..data.len() - 1], MovieError::Truncated),
+            (&bad_rng[..], MovieError::Invalid("rng kind")),
+            (&bad_quirks[..], MovieError::Invalid("quirks")),
+        ];
+        for (data, expected) in cases {
+            assert_eq!(Movie::from_bytes(data), Err(expected.clone()), "{expected}");
+        }
+    }
+}
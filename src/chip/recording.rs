@@ -0,0 +1,50 @@
+use std::{io, path::PathBuf};
+
+use super::Chip;
+use crate::screen::{animation::Animation, Interface};
+
+// Gameplay clip, one frame is captured per iteration of the run loop
+pub struct Clip {
+    pub path: PathBuf,
+    pub animation: Animation,
+}
+
+impl Clip {
+    pub fn save(&self, scale: usize) -> io::Result<()> {
+        self.animation.save(&self.path, scale)
+    }
+}
+
+impl<T: Interface> Chip<T> {
+    // Starts capturing frames into a clip written to path, .gif or .png (APNG), in the colours
+    // the interface draws with
+    pub fn start_clip(&mut self, path: PathBuf) {
+        self.clip = Some(Clip {
+            path,
+            animation: Animation::new(self.interface.palette()),
+        });
+    }
+
+    pub fn stop_clip(&mut self) -> Option<Clip> {
+        self.clip.take()
+    }
+
+    // The hotkey starts a clip at <rom>.frame<n>.gif or stops and saves the running one
+    pub(super) fn toggle_clip(&mut self) -> io::Result<()> {
+        if let Some(clip) = self.stop_clip() {
+            return clip.save(self.clip_scale);
+        }
+        let path = self
+            .frame_path(self.frame, "gif")
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no rom path set"))?;
+        self.start_clip(path);
+        Ok(())
+    }
+
+    // Called after every frame is drawn
+    pub(super) fn capture_clip_frame(&mut self) {
+        if let Some(clip) = self.clip.as_mut() {
+            clip.animation.push(self.interface.framebuffer());
+        }
+    }
+}
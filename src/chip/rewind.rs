@@ -0,0 +1,203 @@
+use std::collections::VecDeque;
+
+use super::Chip;
+use crate::screen::Interface;
+
+// Keeps the newest save state in full and every older frame as a delta against the
+// frame after it, so rewinding walks backwards by applying one delta at a time.
+pub struct RewindBuffer {
+    latest: Option<Vec<u8>>,
+    deltas: VecDeque<Vec<u8>>,
+    size: usize,
+    capacity: usize,
+}
+
+// Appends val as a LEB128 varint
+fn push_varint(out: &mut Vec<u8>, mut val: usize) {
+    loop {
+        let byte = (val & 0x7F) as u8;
+        val >>= 7;
+        if val == 0 {
+            out.push(byte);
+            return;
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+fn read_varint(data: &[u8], pos: &mut usize) -> Option<usize> {
+    let mut val = 0usize;
+    let mut shift = 0;
+    loop {
+        let byte = *data.get(*pos)?;
+        *pos += 1;
+        val |= ((byte & 0x7F) as usize) << shift;
+        if byte & 0x80 == 0 {
+            return Some(val);
+        }
+        shift += 7;
+    }
+}
+
+// Encodes the xor of from and to as runs of (zero count, literal count, literals),
+// prefixed with the length of to. Missing bytes of the shorter input count as 0.
+fn encode_delta(from: &[u8], to: &[u8]) -> Vec<u8> {
+    let len = from.len().max(to.len());
+    let xor = |i: usize| from.get(i).copied().unwrap_or(0) ^ to.get(i).copied().unwrap_or(0);
+
+    let mut out = Vec::new();
+    push_varint(&mut out, to.len());
+    let mut i = 0;
+    while i < len {
+        let zeros_start = i;
+        while i < len && xor(i) == 0 {
+            i += 1;
+        }
+        let literals_start = i;
+        while i < len && xor(i) != 0 {
+            i += 1;
+        }
+        push_varint(&mut out, literals_start - zeros_start);
+        push_varint(&mut out, i - literals_start);
+        out.extend((literals_start..i).map(xor));
+    }
+    out
+}
+
+fn apply_delta(from: &[u8], delta: &[u8]) -> Option<Vec<u8>> {
+    let mut pos = 0;
+    let len = read_varint(delta, &mut pos)?;
+    let mut out = from.to_vec();
+    out.resize(out.len().max(len), 0);
+
+    let mut i = 0;
+    while pos < delta.len() {
+        i += read_varint(delta, &mut pos)?;
+        let literals = read_varint(delta, &mut pos)?;
+        for byte in delta.get(pos..pos + literals)? {
+            *out.get_mut(i)? ^= byte;
+            i += 1;
+        }
+        pos += literals;
+    }
+    out.truncate(len);
+    Some(out)
+}
+
+#[allow(dead_code)]
+impl RewindBuffer {
+    // capacity is the memory budget in bytes for the stored deltas
+    pub fn new(capacity: usize) -> Self {
+        RewindBuffer {
+            latest: None,
+            deltas: VecDeque::new(),
+            size: 0,
+            capacity,
+        }
+    }
+
+    pub fn push(&mut self, state: Vec<u8>) {
+        if let Some(latest) = self.latest.take() {
+            let delta = encode_delta(&state, &latest);
+            self.size += delta.len();
+            self.deltas.push_back(delta);
+            while self.size > self.capacity {
+                match self.deltas.pop_front() {
+                    Some(oldest) => self.size -= oldest.len(),
+                    None => break,
+                }
+            }
+        }
+        self.latest = Some(state);
+    }
+
+    // Removes the newest frame and returns the one before it
+    pub fn pop(&mut self) -> Option<Vec<u8>> {
+        let delta = self.deltas.pop_back()?;
+        self.size -= delta.len();
+        let latest = self.latest.take()?;
+        let previous = apply_delta(&latest, &delta)?;
+        self.latest = Some(previous.clone());
+        Some(previous)
+    }
+
+    // Number of frames that can be stepped back
+    pub fn len(&self) -> usize {
+        self.deltas.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.deltas.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.latest = None;
+        self.deltas.clear();
+        self.size = 0;
+    }
+}
+
+impl<T: Interface> Chip<T> {
+    pub(super) fn record_rewind_frame(&mut self) {
+        if self.rewind.is_some() {
+            let state = self.save_state();
+            if let Some(rewind) = self.rewind.as_mut() {
+                rewind.push(state);
+            }
+        }
+    }
+
+    // Steps the machine back by one frame, returns false if there is no history left
+    pub fn rewind_frame(&mut self) -> bool {
+        let state = match self.rewind.as_mut().and_then(|rewind| rewind.pop()) {
+            Some(state) => state,
+            None => return false,
+        };
+        self.load_state(&state).is_ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deltas_restore_the_previous_state() {
+        let cases: [(&[u8], &[u8]); 6] = [
+            (&[1, 2, 3, 4], &[1, 2, 3, 4]),
+            (&[1, 2, 3, 4], &[5, 6, 7, 8]),
+            (&[0, 1, 0, 0, 2, 0], &[0, 3, 0, 0, 2, 9]),
+            (&[1, 2], &[1, 2, 3, 4]),
+            (&[1, 2, 3, 4], &[1]),
+            (&[], &[7; 300]),
+        ];
+        for (from, to) in cases {
+            let delta = encode_delta(from, to);
+            assert_eq!(
+                apply_delta(from, &delta).as_deref(),
+                Some(to),
+                "{from:?} -> {to:?}"
+            );
+        }
+        // Equal states only store the length and one run of zeros
+        assert_eq!(
+            encode_delta(&[9; 200], &[9; 200]),
+            [0xC8, 0x01, 0xC8, 0x01, 0x00]
+        );
+        assert_eq!(apply_delta(&[1, 2], &[2, 0, 5, 1]), None);
+    }
+
+    #[test]
+    fn oldest_frames_are_dropped_at_capacity() {
+        // Every delta between the states below takes 7 bytes, 2 fit
+        let mut rewind = RewindBuffer::new(20);
+        for frame in 1..=5 {
+            rewind.push(vec![frame; 4]);
+        }
+        assert_eq!(rewind.len(), 2);
+        assert_eq!(rewind.pop(), Some(vec![4; 4]));
+        assert_eq!(rewind.pop(), Some(vec![3; 4]));
+        assert_eq!(rewind.pop(), None);
+        assert!(rewind.is_empty());
+    }
+}
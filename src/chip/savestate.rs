@@ -0,0 +1,340 @@
+use std::{fmt, fs, io, path::PathBuf};
+
+use super::Chip;
+use crate::{config::*, quirks::Quirks, screen::Interface};
+
+// Save states start with the magic and a version, followed by tagged sections:
+// 4 byte tag, u32 length (little endian), data. Unknown sections are skipped when loading.
+const MAGIC: &[u8; 4] = b"CH8S";
+const VERSION: u16 = 1;
+
+const TAG_CPU: &[u8; 4] = b"CPU ";
+const TAG_MEMORY: &[u8; 4] = b"MEM ";
+const TAG_QUIRKS: &[u8; 4] = b"QRKS";
+const TAG_RPL: &[u8; 4] = b"RPL ";
+const TAG_AUDIO: &[u8; 4] = b"AUDI";
+const TAG_FRAMEBUFFER: &[u8; 4] = b"FBUF";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StateError {
+    // Data does not start with the save state magic
+    BadMagic,
+    // Save state was written by a newer, incompatible version
+    UnsupportedVersion(u16),
+    // Data ends in the middle of a section
+    Truncated,
+    // A required section is missing
+    MissingSection(&'static str),
+    // A section has an unexpected length
+    InvalidSection(&'static str),
+}
+
+impl fmt::Display for StateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StateError::BadMagic => write!(f, "not a save state"),
+            StateError::UnsupportedVersion(version) => {
+                write!(f, "unsupported save state version {version}")
+            }
+            StateError::Truncated => write!(f, "save state is truncated"),
+            StateError::MissingSection(tag) => write!(f, "save state is missing section {tag}"),
+            StateError::InvalidSection(tag) => write!(f, "save state section {tag} is invalid"),
+        }
+    }
+}
+
+impl std::error::Error for StateError {}
+
+type Section<'a> = ([u8; 4], &'a [u8]);
+
+fn push_section(out: &mut Vec<u8>, tag: &[u8; 4], data: &[u8]) {
+    out.extend_from_slice(tag);
+    out.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    out.extend_from_slice(data);
+}
+
+// Splits the save state into its (tag, data) sections after checking the header
+fn read_sections(data: &[u8]) -> Result<Vec<Section<'_>>, StateError> {
+    if data.len() < 6 || &data[0..4] != MAGIC {
+        return Err(StateError::BadMagic);
+    }
+    let version = u16::from_le_bytes([data[4], data[5]]);
+    if version > VERSION {
+        return Err(StateError::UnsupportedVersion(version));
+    }
+
+    let mut sections = Vec::new();
+    let mut pos = 6;
+    while pos < data.len() {
+        let header = data.get(pos..pos + 8).ok_or(StateError::Truncated)?;
+        let tag = [header[0], header[1], header[2], header[3]];
+        let len = u32::from_le_bytes([header[4], header[5], header[6], header[7]]) as usize;
+        pos += 8;
+        let section = data.get(pos..pos + len).ok_or(StateError::Truncated)?;
+        sections.push((tag, section));
+        pos += len;
+    }
+    Ok(sections)
+}
+
+fn find_section<'a>(
+    sections: &[Section<'a>],
+    tag: &'static [u8; 4],
+) -> Result<&'a [u8], StateError> {
+    sections
+        .iter()
+        .find(|(t, _)| t == tag)
+        .map(|(_, data)| *data)
+        .ok_or(StateError::MissingSection(tag_name(tag)))
+}
+
+fn tag_name(tag: &'static [u8; 4]) -> &'static str {
+    std::str::from_utf8(tag).unwrap_or("????").trim_end()
+}
+
+#[allow(dead_code)]
+impl<T: Interface> Chip<T> {
+    pub fn save_state(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(self.memory.len() + 1024);
+        out.extend_from_slice(MAGIC);
+        out.extend_from_slice(&VERSION.to_le_bytes());
+
+        let mut cpu = Vec::new();
+        cpu.extend_from_slice(&self.pc.to_le_bytes());
+        cpu.extend_from_slice(&self.registers.i.to_le_bytes());
+        for reg in 0..16 {
+            cpu.push(self.registers.get_reg_v(reg).unwrap_or_default());
+        }
+        for addr in self.stack.iter() {
+            cpu.extend_from_slice(&addr.to_le_bytes());
+        }
+        cpu.push(self.stackpointer);
+        cpu.push(self.delay_timer);
+        cpu.push(self.sound_timer);
+        // Optional keys are stored as 0xFF when not set
+        cpu.push(self.keyboard.unwrap_or(0xFF));
+        cpu.push(self.release_key_wait.unwrap_or(0xFF));
+        cpu.push(self.vblank_wait as u8);
+        push_section(&mut out, TAG_CPU, &cpu);
+
+        push_section(&mut out, TAG_MEMORY, &self.memory);
+        push_section(&mut out, TAG_QUIRKS, &self.quirks.to_bytes());
+        push_section(&mut out, TAG_RPL, &self.rpl_flags);
+
+        let mut audio = self.audio_pattern.to_vec();
+        audio.push(self.pitch);
+        push_section(&mut out, TAG_AUDIO, &audio);
+
+        let framebuffer = self.interface.framebuffer();
+        let mut screen = vec![framebuffer.hires() as u8, framebuffer.planes()];
+        screen.extend_from_slice(framebuffer.pixels());
+        push_section(&mut out, TAG_FRAMEBUFFER, &screen);
+
+        out
+    }
+
+    pub fn load_state(&mut self, data: &[u8]) -> Result<(), StateError> {
+        let sections = read_sections(data)?;
+
+        // Validate every section before touching the machine
+        let cpu = find_section(&sections, TAG_CPU)?;
+        // The stack pointer is at most the stack size, Ret and the debugger index with it
+        if cpu.len() != 2 + 2 + 16 + 32 + 6 || cpu[52] as usize > self.stack.len() {
+            return Err(StateError::InvalidSection(tag_name(TAG_CPU)));
+        }
+        let memory = find_section(&sections, TAG_MEMORY)?;
+        let quirks = Quirks::from_bytes(find_section(&sections, TAG_QUIRKS)?)
+            .ok_or(StateError::InvalidSection(tag_name(TAG_QUIRKS)))?;
+        let memsize = if quirks.xo_chip {
+            XO_CHIP_MEMSIZE
+        } else {
+            MEMSIZE
+        };
+        if memory.len() != memsize {
+            return Err(StateError::InvalidSection(tag_name(TAG_MEMORY)));
+        }
+        let rpl = find_section(&sections, TAG_RPL)?;
+        if rpl.len() != self.rpl_flags.len() {
+            return Err(StateError::InvalidSection(tag_name(TAG_RPL)));
+        }
+        let audio = find_section(&sections, TAG_AUDIO)?;
+        if audio.len() != self.audio_pattern.len() + 1 {
+            return Err(StateError::InvalidSection(tag_name(TAG_AUDIO)));
+        }
+        // Resolution, planes and one colour index (0-3) per pixel of that resolution
+        let screen = find_section(&sections, TAG_FRAMEBUFFER)?;
+        let pixels = match screen.first() {
+            Some(0) => SCREEN_WIDTH as usize * SCREEN_HEIGHT as usize,
+            Some(_) => HIRES_SCREEN_WIDTH as usize * HIRES_SCREEN_HEIGHT as usize,
+            None => 0,
+        };
+        if screen.len() != 2 + pixels || screen[2..].iter().any(|pixel| *pixel > 0b11) {
+            return Err(StateError::InvalidSection(tag_name(TAG_FRAMEBUFFER)));
+        }
+
+        self.pc = u16::from_le_bytes([cpu[0], cpu[1]]);
+        self.registers.i = u16::from_le_bytes([cpu[2], cpu[3]]);
+        for (reg, val) in cpu[4..20].iter().enumerate() {
+            self.registers
+                .set_reg_v(reg as u8, *val)
+                .expect("v0-vf are valid registers");
+        }
+        for (slot, addr) in self.stack.iter_mut().zip(cpu[20..52].chunks(2)) {
+            *slot = u16::from_le_bytes([addr[0], addr[1]]);
+        }
+        self.stackpointer = cpu[52];
+        self.delay_timer = cpu[53];
+        self.sound_timer = cpu[54];
+        self.keyboard = Some(cpu[55]).filter(|key| *key != 0xFF);
+        self.release_key_wait = Some(cpu[56]).filter(|key| *key != 0xFF);
+        self.vblank_wait = cpu[57] != 0;
+
+        self.memory = memory.to_vec();
+        self.quirks = quirks;
+        self.rpl_flags.copy_from_slice(rpl);
+        self.audio_pattern.copy_from_slice(&audio[..16]);
+        self.pitch = audio[16];
+
+        let framebuffer = self.interface.framebuffer_mut();
+        framebuffer.set_hires(screen[0] != 0);
+        framebuffer.select_planes(screen[1]);
+        framebuffer.load_pixels(&screen[2..]);
+
+        Ok(())
+    }
+
+    // Save states are written next to the rom as <rom>.state<slot>
+    fn state_slot_path(&self, slot: u8) -> Option<PathBuf> {
+        let rom = self.rom_path.as_ref()?;
+        let mut path = rom.clone().into_os_string();
+        path.push(format!(".state{slot}"));
+        Some(PathBuf::from(path))
+    }
+
+    pub fn save_state_slot(&self, slot: u8) -> io::Result<()> {
+        let path = self
+            .state_slot_path(slot)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no rom path set"))?;
+        fs::write(path, self.save_state())
+    }
+
+    pub fn load_state_slot(&mut self, slot: u8) -> io::Result<()> {
+        let path = self
+            .state_slot_path(slot)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no rom path set"))?;
+        let data = fs::read(path)?;
+        self.load_state(&data)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{quirks::Preset, screen::headless::Headless};
+
+    fn chip(quirks: Quirks) -> Chip<Headless> {
+        Chip::new(PROG_POS_START, Headless::new(), quirks)
+    }
+
+    // XO-CHIP machine in hires mode with every part of the state changed
+    fn busy_chip() -> Chip<Headless> {
+        let mut chip = chip(Quirks::from_preset(Preset::XoChip));
+        chip.pc = 0x2468;
+        chip.registers.i = 0xFFF0;
+        for reg in 0..16 {
+            chip.registers.set_reg_v(reg, reg * 3 + 1).unwrap();
+        }
+        chip.stack[..2].copy_from_slice(&[0x200, 0x300]);
+        chip.stackpointer = 2;
+        chip.delay_timer = 30;
+        chip.sound_timer = 4;
+        chip.keyboard = Some(0xA);
+        chip.release_key_wait = Some(0x3);
+        chip.memory[0xFFFF] = 0x5A;
+        chip.rpl_flags[7] = 9;
+        chip.audio_pattern[15] = 0xF0;
+        chip.pitch = 100;
+        let framebuffer = chip.interface.framebuffer_mut();
+        framebuffer.set_hires(true);
+        framebuffer.select_planes(3);
+        framebuffer.draw_sprite(120, 60, &[0xFF, 0xFF], 8, true);
+        chip
+    }
+
+    #[test]
+    fn states_round_trip() {
+        let saved = busy_chip();
+        let state = saved.save_state();
+        let mut loaded = chip(Quirks::default());
+        loaded.load_state(&state).unwrap();
+
+        assert_eq!(loaded.save_state(), state);
+        assert_eq!(loaded.memory.len(), XO_CHIP_MEMSIZE);
+        assert_eq!(loaded.memory[0xFFFF], 0x5A);
+        assert!(loaded.quirks.xo_chip);
+        assert_eq!((loaded.pc, loaded.registers.i), (0x2468, 0xFFF0));
+        assert_eq!(loaded.registers.get_reg_v(0xF).unwrap(), 46);
+        assert_eq!(loaded.release_key_wait, Some(0x3));
+        let framebuffer = loaded.interface.framebuffer();
+        assert!(framebuffer.hires());
+        assert_eq!(framebuffer.planes(), 3);
+        assert_eq!(framebuffer.get_pixel(127, 60), 3);
+        assert_eq!(framebuffer.pixels(), saved.interface.framebuffer().pixels());
+    }
+
+    // Copy of the state with the data of the section tag changed by edit
+    fn edit_section(state: &[u8], tag: &[u8; 4], edit: impl Fn(&mut Vec<u8>)) -> Vec<u8> {
+        let mut out = state[..6].to_vec();
+        for (section, data) in read_sections(state).unwrap() {
+            let mut data = data.to_vec();
+            if &section == tag {
+                edit(&mut data);
+            }
+            push_section(&mut out, &section, &data);
+        }
+        out
+    }
+
+    #[test]
+    fn invalid_states_leave_the_machine_untouched() {
+        let state = busy_chip().save_state();
+        let mut wrong_version = state.clone();
+        wrong_version[4..6].copy_from_slice(&(VERSION + 1).to_le_bytes());
+        let mut bad_magic = state.clone();
+        bad_magic[0] = b'X';
+        let short_cpu = edit_section(&state, TAG_CPU, |cpu| {
+            cpu.pop();
+        });
+        let stack_overflow = edit_section(&state, TAG_CPU, |cpu| cpu[52] = 17);
+        let short_screen = edit_section(&state, TAG_FRAMEBUFFER, |screen| {
+            screen.pop();
+        });
+        // Hires pixels with the lores flag
+        let wrong_resolution = edit_section(&state, TAG_FRAMEBUFFER, |screen| screen[0] = 0);
+        let bad_pixel = edit_section(&state, TAG_FRAMEBUFFER, |screen| screen[2] = 4);
+
+        let cases = [
+            (&bad_magic[..], StateError::BadMagic),
+            (&state[..3], StateError::BadMagic),
+            (
+                &wrong_version[..],
+                StateError::UnsupportedVersion(VERSION + 1),
+            ),
+            (&state[..state.len() - 1], StateError::Truncated),
+            (&state[..10], StateError::Truncated),
+            (&short_cpu[..], StateError::InvalidSection("CPU")),
+            (&stack_overflow[..], StateError::InvalidSection("CPU")),
+            (&short_screen[..], StateError::InvalidSection("FBUF")),
+            (&wrong_resolution[..], StateError::InvalidSection("FBUF")),
+            (&bad_pixel[..], StateError::InvalidSection("FBUF")),
+            (&state[..6], StateError::MissingSection("CPU")),
+        ];
+        for (data, expected) in cases {
+            let mut chip = chip(Quirks::default());
+            let before = chip.save_state();
+            assert_eq!(chip.load_state(data), Err(expected.clone()), "{expected}");
+            assert_eq!(chip.save_state(), before, "{expected}");
+        }
+    }
+}
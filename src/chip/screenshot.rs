@@ -0,0 +1,52 @@
+use std::{io, path::PathBuf};
+
+use super::Chip;
+use crate::screen::{image, Interface};
+
+// Screenshot taken once the frame has been drawn, a failure is kept for the caller to report
+pub struct ScheduledScreenshot {
+    pub frame: u32,
+    pub path: PathBuf,
+    pub error: Option<io::Error>,
+}
+
+impl ScheduledScreenshot {
+    pub fn new(frame: u32, path: PathBuf) -> Self {
+        ScheduledScreenshot {
+            frame,
+            path,
+            error: None,
+        }
+    }
+}
+
+impl<T: Interface> Chip<T> {
+    // Screenshots and clips are written next to the rom as <rom>.frame<n>.<extension> by default
+    pub fn frame_path(&self, frame: u32, extension: &str) -> Option<PathBuf> {
+        let rom = self.rom_path.as_ref()?;
+        let mut path = rom.clone().into_os_string();
+        path.push(format!(".frame{frame}.{extension}"));
+        Some(PathBuf::from(path))
+    }
+
+    // Screenshot of the current frame, used by the hotkey
+    pub fn save_screenshot(&self) -> io::Result<()> {
+        let path = self
+            .frame_path(self.frame, "png")
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no rom path set"))?;
+        image::save(self.interface.framebuffer(), &path, self.screenshot_scale)
+    }
+
+    // Called after every frame is drawn
+    pub(super) fn take_scheduled_screenshot(&mut self) {
+        let Some(scheduled) = self.scheduled_screenshot.as_mut() else {
+            return;
+        };
+        if scheduled.frame == self.frame && scheduled.error.is_none() {
+            let framebuffer = self.interface.framebuffer();
+            if let Err(err) = image::save(framebuffer, &scheduled.path, self.screenshot_scale) {
+                scheduled.error = Some(err);
+            }
+        }
+    }
+}
@@ -0,0 +1,626 @@
+// Single instruction tests, each builds a machine, runs one opcode at 0x200 and checks the result
+
+use super::*;
+use crate::{quirks::Preset, screen::headless::Headless};
+
+// Machine state to start an instruction from
+struct Machine {
+    registers: Vec<(u8, u8)>,
+    i: u16,
+    memory: Vec<(u16, Vec<u8>)>,
+    stack: Vec<u16>,
+    keys: u16,
+    delay_timer: u8,
+    quirks: Quirks,
+}
+
+impl Machine {
+    fn new() -> Self {
+        Machine {
+            registers: Vec::new(),
+            i: 0,
+            memory: Vec::new(),
+            stack: Vec::new(),
+            keys: 0,
+            delay_timer: 0,
+            quirks: Quirks::default(),
+        }
+    }
+
+    fn v(mut self, reg: u8, val: u8) -> Self {
+        self.registers.push((reg, val));
+        self
+    }
+
+    fn i(mut self, i: u16) -> Self {
+        self.i = i;
+        self
+    }
+
+    fn mem(mut self, addr: u16, bytes: &[u8]) -> Self {
+        self.memory.push((addr, bytes.to_vec()));
+        self
+    }
+
+    fn stack(mut self, stack: &[u16]) -> Self {
+        self.stack = stack.to_vec();
+        self
+    }
+
+    fn keys(mut self, keys: &[u8]) -> Self {
+        self.keys = keys.iter().fold(0, |mask, key| mask | 1 << key);
+        self
+    }
+
+    fn delay(mut self, delay_timer: u8) -> Self {
+        self.delay_timer = delay_timer;
+        self
+    }
+
+    fn quirks(mut self, preset: Preset) -> Self {
+        self.quirks = Quirks::from_preset(preset);
+        self
+    }
+
+    // Builds the chip with program at PROG_POS_START, memory bytes are written afterwards
+    fn build(self, program: &[u16]) -> Chip<Headless> {
+        let mut chip = Chip::new(PROG_POS_START, Headless::new(), self.quirks);
+        let bytes = program.iter().flat_map(|word| word.to_be_bytes()).collect();
+        chip.load_prog(bytes).unwrap();
+        for (reg, val) in self.registers {
+            chip.registers.set_reg_v(reg, val).unwrap();
+        }
+        chip.registers.i = self.i;
+        for (addr, bytes) in self.memory {
+            let addr = addr as usize;
+            chip.memory[addr..addr + bytes.len()].copy_from_slice(&bytes);
+        }
+        chip.stack[..self.stack.len()].copy_from_slice(&self.stack);
+        chip.stackpointer = self.stack.len() as u8;
+        // Same as the run loop at the start of a frame
+        chip.keypad = self.keys;
+        chip.keyboard = (0..16).find(|key| chip.key_down(*key));
+        chip.delay_timer = self.delay_timer;
+        chip
+    }
+}
+
+// Runs every instruction of program in turn
+fn run(machine: Machine, program: &[u16]) -> Chip<Headless> {
+    let mut chip = machine.build(program);
+    for _ in program {
+        chip.execute_inst().unwrap();
+    }
+    chip
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Expect {
+    V(u8, u8),
+    I(u16),
+    Pc(u16),
+    Mem(u16, &'static [u8]),
+    Sp(u8),
+    // Return address on top of the stack
+    Stack(u16),
+    Delay(u8),
+    Sound(u8),
+    Pixel(u8, u8, u8),
+    Hires(bool),
+    Running(bool),
+}
+
+fn check(name: &str, machine: Machine, opcode: u16, expected: &[Expect]) {
+    let mut chip = machine.build(&[opcode]);
+    if let Err(err) = chip.execute_inst() {
+        panic!("{name}: {opcode:04X} failed with {err}");
+    }
+    for expect in expected {
+        let matches = match *expect {
+            Expect::V(reg, val) => chip.registers.get_reg_v(reg).unwrap() == val,
+            Expect::I(i) => chip.registers.i == i,
+            Expect::Pc(pc) => chip.pc == pc,
+            Expect::Mem(addr, bytes) => {
+                &chip.memory[addr as usize..addr as usize + bytes.len()] == bytes
+            }
+            Expect::Sp(sp) => chip.stackpointer == sp,
+            Expect::Stack(addr) => {
+                chip.stackpointer > 0 && chip.stack[chip.stackpointer as usize - 1] == addr
+            }
+            Expect::Delay(val) => chip.delay_timer == val,
+            Expect::Sound(val) => chip.sound_timer == val,
+            Expect::Pixel(x, y, val) => chip.interface.framebuffer().get_pixel(x, y) == val,
+            Expect::Hires(hires) => chip.interface.framebuffer().hires() == hires,
+            Expect::Running(running) => chip.running == running,
+        };
+        assert!(matches, "{name}: {opcode:04X} expected {expect:?}");
+    }
+}
+
+fn check_err(name: &str, machine: Machine, opcode: u16, expected: ChipError) {
+    let mut chip = machine.build(&[opcode]);
+    assert_eq!(chip.execute_inst(), Err(expected), "{name}: {opcode:04X}");
+}
+
+use Expect::*;
+
+#[test]
+fn flow() {
+    check("sys is a no-op", Machine::new(), 0x0123, &[Pc(0x202)]);
+    let chip = run(Machine::new().i(FONT_POS_START as u16), &[0xD005, 0x00E0]);
+    assert_eq!(chip.interface.framebuffer().get_pixel(0, 0), 0, "cls");
+    assert_eq!(chip.pc, 0x204);
+    check("jp", Machine::new(), 0x1ABC, &[Pc(0xABC)]);
+    check(
+        "call",
+        Machine::new(),
+        0x2ABC,
+        &[Pc(0xABC), Sp(1), Stack(0x200)],
+    );
+    check(
+        "ret",
+        Machine::new().stack(&[0x300, 0x400]),
+        0x00EE,
+        &[Pc(0x402), Sp(1)],
+    );
+    check_err(
+        "ret with an empty stack",
+        Machine::new(),
+        0x00EE,
+        ChipError::StackUnderflow { addr: 0x200 },
+    );
+    check_err(
+        "call with a full stack",
+        Machine::new().stack(&[0x300; 16]),
+        0x2ABC,
+        ChipError::StackOverflow { addr: 0x200 },
+    );
+    check(
+        "jp v0",
+        Machine::new().v(0, 0x10).v(3, 0x20),
+        0xB300,
+        &[Pc(0x310)],
+    );
+    check(
+        "jp vx",
+        Machine::new()
+            .v(0, 0x10)
+            .v(3, 0x20)
+            .quirks(Preset::SuperChip),
+        0xB300,
+        &[Pc(0x320)],
+    );
+    check("exit", Machine::new(), 0x00FD, &[Running(false), Pc(0x200)]);
+}
+
+#[test]
+fn skips() {
+    let v = || Machine::new().v(1, 0x42).v(2, 0x42).v(3, 0x17);
+    check("se taken", v(), 0x3142, &[Pc(0x204)]);
+    check("se not taken", v(), 0x3143, &[Pc(0x202)]);
+    check("sne taken", v(), 0x4143, &[Pc(0x204)]);
+    check("sne not taken", v(), 0x4142, &[Pc(0x202)]);
+    check("se reg taken", v(), 0x5120, &[Pc(0x204)]);
+    check("se reg not taken", v(), 0x5130, &[Pc(0x202)]);
+    check("sne reg taken", v(), 0x9130, &[Pc(0x204)]);
+    check("sne reg not taken", v(), 0x9120, &[Pc(0x202)]);
+    check(
+        "skp taken",
+        Machine::new().v(1, 0xA).keys(&[0xA]),
+        0xE19E,
+        &[Pc(0x204)],
+    );
+    check(
+        "skp not taken",
+        Machine::new().v(1, 0xA).keys(&[0xB]),
+        0xE19E,
+        &[Pc(0x202)],
+    );
+    check("sknp taken", Machine::new().v(1, 0xA), 0xE1A1, &[Pc(0x204)]);
+    check(
+        "sknp not taken",
+        Machine::new().v(1, 0xA).keys(&[0xA]),
+        0xE1A1,
+        &[Pc(0x202)],
+    );
+    // Keys above F are never pressed
+    check(
+        "skp key out of range",
+        Machine::new().v(1, 0x1A).keys(&[0xA]),
+        0xE19E,
+        &[Pc(0x202)],
+    );
+    check(
+        "skip over a long i load",
+        v().quirks(Preset::XoChip).mem(0x202, &[0xF0, 0x00]),
+        0x3142,
+        &[Pc(0x206)],
+    );
+    check(
+        "skip over f000 outside of xo-chip",
+        v().mem(0x202, &[0xF0, 0x00]),
+        0x3142,
+        &[Pc(0x204)],
+    );
+}
+
+#[test]
+fn loads() {
+    check("ld", Machine::new(), 0x6A42, &[V(0xA, 0x42), Pc(0x202)]);
+    check(
+        "add wraps",
+        Machine::new().v(1, 0xFF).v(0xF, 7),
+        0x7102,
+        &[V(1, 1), V(0xF, 7)],
+    );
+    check(
+        "ld reg",
+        Machine::new().v(2, 0x42),
+        0x8120,
+        &[V(1, 0x42), V(2, 0x42)],
+    );
+    check("ld i", Machine::new(), 0xAABC, &[I(0xABC)]);
+    check("ld dt", Machine::new().v(1, 60), 0xF115, &[Delay(60)]);
+    check("ld st", Machine::new().v(1, 60), 0xF118, &[Sound(60)]);
+    check("read dt", Machine::new().delay(33), 0xF107, &[V(1, 33)]);
+}
+
+#[test]
+fn logic() {
+    let v = || Machine::new().v(1, 0x3C).v(2, 0x0F).v(0xF, 5);
+    check("or", v(), 0x8121, &[V(1, 0x3F), V(0xF, 0)]);
+    check("and", v(), 0x8122, &[V(1, 0x0C), V(0xF, 0)]);
+    check("xor", v(), 0x8123, &[V(1, 0x33), V(0xF, 0)]);
+    let chip48 = || v().quirks(Preset::Chip48);
+    check("or keeps vf", chip48(), 0x8121, &[V(1, 0x3F), V(0xF, 5)]);
+    check("and keeps vf", chip48(), 0x8122, &[V(1, 0x0C), V(0xF, 5)]);
+    check("xor keeps vf", chip48(), 0x8123, &[V(1, 0x33), V(0xF, 5)]);
+}
+
+#[test]
+fn arithmetic() {
+    let v = |x, y| Machine::new().v(1, x).v(2, y);
+    check("add", v(0x10, 0x20), 0x8124, &[V(1, 0x30), V(0xF, 0)]);
+    check("add carry", v(0xF0, 0x20), 0x8124, &[V(1, 0x10), V(0xF, 1)]);
+    check(
+        "add 0xff + 1",
+        v(0xFF, 0x01),
+        0x8124,
+        &[V(1, 0x00), V(0xF, 1)],
+    );
+    check("sub", v(0x30, 0x10), 0x8125, &[V(1, 0x20), V(0xF, 1)]);
+    check("sub equal", v(0x30, 0x30), 0x8125, &[V(1, 0x00), V(0xF, 1)]);
+    check(
+        "sub borrow",
+        v(0x10, 0x30),
+        0x8125,
+        &[V(1, 0xE0), V(0xF, 0)],
+    );
+    check("subn", v(0x10, 0x30), 0x8127, &[V(1, 0x20), V(0xF, 1)]);
+    check(
+        "subn borrow",
+        v(0x30, 0x10),
+        0x8127,
+        &[V(1, 0xE0), V(0xF, 0)],
+    );
+
+    // With vx = VF the flag overwrites the result
+    let vf = |x, y| Machine::new().v(0xF, x).v(2, y);
+    check("add into vf", vf(0x10, 0x20), 0x8F24, &[V(0xF, 0)]);
+    check("add carry into vf", vf(0xF0, 0x20), 0x8F24, &[V(0xF, 1)]);
+    check("sub into vf", vf(0x30, 0x10), 0x8F25, &[V(0xF, 1)]);
+    check("sub borrow into vf", vf(0x10, 0x30), 0x8F25, &[V(0xF, 0)]);
+    check("subn into vf", vf(0x10, 0x30), 0x8F27, &[V(0xF, 1)]);
+    check("subn borrow into vf", vf(0x30, 0x10), 0x8F27, &[V(0xF, 0)]);
+    // And vy = VF is read before the flag is written
+    check(
+        "add from vf",
+        Machine::new().v(1, 0xF0).v(0xF, 0x20),
+        0x81F4,
+        &[V(1, 0x10), V(0xF, 1)],
+    );
+}
+
+#[test]
+fn shifts() {
+    let v = || Machine::new().v(1, 0xFF).v(2, 0x81);
+    check("shr vy", v(), 0x8126, &[V(1, 0x40), V(2, 0x81), V(0xF, 1)]);
+    check("shl vy", v(), 0x812E, &[V(1, 0x02), V(2, 0x81), V(0xF, 1)]);
+    let chip48 = || v().quirks(Preset::Chip48);
+    check("shr vx", chip48(), 0x8126, &[V(1, 0x7F), V(0xF, 1)]);
+    check("shl vx", chip48(), 0x812E, &[V(1, 0xFE), V(0xF, 1)]);
+    check(
+        "shr no carry",
+        Machine::new().v(2, 0x80),
+        0x8126,
+        &[V(1, 0x40), V(0xF, 0)],
+    );
+    check(
+        "shl no carry",
+        Machine::new().v(2, 0x01),
+        0x812E,
+        &[V(1, 0x02), V(0xF, 0)],
+    );
+    check(
+        "shr into vf",
+        Machine::new().v(2, 0x02),
+        0x8F26,
+        &[V(0xF, 0)],
+    );
+    check(
+        "shl into vf",
+        Machine::new().v(2, 0x80),
+        0x8F2E,
+        &[V(0xF, 1)],
+    );
+}
+
+#[test]
+fn index() {
+    check(
+        "add i",
+        Machine::new().i(0x300).v(1, 0x10),
+        0xF11E,
+        &[I(0x310)],
+    );
+    // I is 16 bits wide and does not wrap at the end of the 4K address space
+    check(
+        "add i past 0xfff",
+        Machine::new().i(0xFFF).v(1, 0x01),
+        0xF11E,
+        &[I(0x1000), V(0xF, 0)],
+    );
+    check(
+        "add i wraps at 16 bits",
+        Machine::new().i(0xFFFF).v(1, 0x02),
+        0xF11E,
+        &[I(0x0001)],
+    );
+    check(
+        "font",
+        Machine::new().v(1, 0xA),
+        0xF129,
+        &[I(FONT_POS_START as u16 + 50)],
+    );
+    check(
+        "big font",
+        Machine::new().v(1, 0x3),
+        0xF130,
+        &[I(BIG_FONT_POS_START as u16 + 30)],
+    );
+    check(
+        "bcd 255",
+        Machine::new().i(0x300).v(1, 255),
+        0xF133,
+        &[Mem(0x300, &[2, 5, 5]), I(0x300)],
+    );
+    check(
+        "bcd 0",
+        Machine::new().i(0x300).v(1, 0).mem(0x300, &[9, 9, 9]),
+        0xF133,
+        &[Mem(0x300, &[0, 0, 0])],
+    );
+    check(
+        "bcd 107",
+        Machine::new().i(0x300).v(1, 107),
+        0xF133,
+        &[Mem(0x300, &[1, 0, 7])],
+    );
+    check_err(
+        "bcd past the end of memory",
+        Machine::new().i(0xFFE).v(1, 255),
+        0xF133,
+        ChipError::MemoryFault {
+            pc: 0x200,
+            addr: 0x1000,
+        },
+    );
+}
+
+#[test]
+fn load_store() {
+    let v = || Machine::new().i(0x300).v(0, 1).v(1, 2).v(2, 3).v(3, 4);
+    check("store", v(), 0xF255, &[Mem(0x300, &[1, 2, 3, 0]), I(0x303)]);
+    check("store v0", v(), 0xF055, &[Mem(0x300, &[1, 0]), I(0x301)]);
+    check(
+        "store keeps i",
+        v().quirks(Preset::SuperChip),
+        0xF255,
+        &[I(0x300)],
+    );
+    check(
+        "store adds x",
+        v().quirks(Preset::Chip48),
+        0xF255,
+        &[Mem(0x300, &[1, 2, 3, 0]), I(0x302)],
+    );
+    let m = || Machine::new().i(0x300).mem(0x300, &[9, 8, 7, 6]);
+    check(
+        "load",
+        m(),
+        0xF265,
+        &[V(0, 9), V(1, 8), V(2, 7), V(3, 0), I(0x303)],
+    );
+    check(
+        "load keeps i",
+        m().quirks(Preset::SuperChip),
+        0xF265,
+        &[V(2, 7), I(0x300)],
+    );
+    check(
+        "load adds x",
+        m().quirks(Preset::Chip48),
+        0xF265,
+        &[V(2, 7), I(0x302)],
+    );
+    check_err(
+        "load past the end of memory",
+        Machine::new().i(0xFFE),
+        0xF265,
+        ChipError::MemoryFault {
+            pc: 0x200,
+            addr: 0x1000,
+        },
+    );
+    // Saves v0 and v1, clears them and loads them back
+    let chip = run(
+        Machine::new().v(0, 1).v(1, 2),
+        &[0xF175, 0x6000, 0x6100, 0xF085],
+    );
+    assert_eq!(chip.rpl_flags[..3], [1, 2, 0]);
+    assert_eq!(chip.registers.get_reg_v(0), Ok(1));
+    assert_eq!(chip.registers.get_reg_v(1), Ok(0));
+}
+
+#[test]
+fn random() {
+    check(
+        "rnd masked to 0",
+        Machine::new().v(1, 0xFF),
+        0xC100,
+        &[V(1, 0)],
+    );
+    for _ in 0..32 {
+        let chip = run(Machine::new(), &[0xC10F]);
+        assert_eq!(chip.registers.get_reg_v(1).unwrap() & 0xF0, 0);
+    }
+}
+
+#[test]
+fn draw() {
+    // 0xF0 at (62, 0) is clipped to 2 pixels
+    let sprite = || Machine::new().i(0x300).mem(0x300, &[0xF0]).v(1, 62).v(2, 0);
+    check(
+        "drw clips",
+        sprite(),
+        0xD121,
+        &[Pixel(62, 0, 1), Pixel(63, 0, 1), Pixel(0, 0, 0), V(0xF, 0)],
+    );
+    check(
+        "drw wraps",
+        sprite().quirks(Preset::XoChip),
+        0xD121,
+        &[
+            Pixel(62, 0, 1),
+            Pixel(0, 0, 1),
+            Pixel(1, 0, 1),
+            Pixel(2, 0, 0),
+        ],
+    );
+    check(
+        "drw start coordinates wrap",
+        Machine::new()
+            .i(0x300)
+            .mem(0x300, &[0x80])
+            .v(1, 64 + 3)
+            .v(2, 32 + 4),
+        0xD121,
+        &[Pixel(3, 4, 1)],
+    );
+
+    let chip = run(sprite(), &[0xD121]);
+    assert!(chip.vblank_wait, "drw waits for the next frame");
+    // Drawing it twice erases it and collides
+    let chip = run(sprite(), &[0xD121, 0xD121]);
+    assert_eq!(chip.registers.get_reg_v(0xF), Ok(1));
+    assert_eq!(chip.interface.framebuffer().get_pixel(62, 0), 0);
+}
+
+#[test]
+fn wait_for_key() {
+    check("ld k waits", Machine::new(), 0xF10A, &[Pc(0x200), V(1, 0)]);
+    check(
+        "ld k with a key held",
+        Machine::new().keys(&[0x7]),
+        0xF10A,
+        &[Pc(0x200), V(1, 7)],
+    );
+    let mut chip = Machine::new().keys(&[0x7]).build(&[0xF10A]);
+    chip.execute_inst().unwrap();
+    chip.execute_inst().unwrap();
+    assert_eq!(chip.pc, 0x200, "ld k waits for the release");
+    chip.keypad = 0;
+    chip.execute_inst().unwrap();
+    assert_eq!(chip.pc, 0x202);
+}
+
+#[test]
+fn schip_xo_chip() {
+    check("low", Machine::new(), 0x00FE, &[Hires(false)]);
+    check("high", Machine::new(), 0x00FF, &[Hires(true)]);
+    // A dot at 8, 8 is scrolled down 2, right 4, left 4 and up 1
+    let xo = || Machine::new().quirks(Preset::XoChip);
+    let dot = || xo().i(0x300).mem(0x300, &[0x80]).v(1, 8).v(2, 8);
+    let pixel = |program: &[u16], x, y| {
+        let chip = run(dot(), program);
+        chip.interface.framebuffer().get_pixel(x, y) == 1
+    };
+    assert!(pixel(&[0xD121, 0x00C2], 8, 10), "scroll down");
+    assert!(pixel(&[0xD121, 0x00FB], 12, 8), "scroll right");
+    assert!(pixel(&[0xD121, 0x00FC], 4, 8), "scroll left");
+    assert!(pixel(&[0xD121, 0x00D1], 8, 7), "scroll up");
+    check(
+        "save range",
+        xo().i(0x300).v(1, 1).v(2, 2).v(3, 3),
+        0x5132,
+        &[Mem(0x300, &[1, 2, 3]), I(0x300)],
+    );
+    check(
+        "save range descending",
+        xo().i(0x300).v(1, 1).v(2, 2).v(3, 3),
+        0x5312,
+        &[Mem(0x300, &[3, 2, 1])],
+    );
+    check(
+        "load range",
+        xo().i(0x300).mem(0x300, &[7, 8]),
+        0x5453,
+        &[V(4, 7), V(5, 8), I(0x300)],
+    );
+    check(
+        "long i",
+        xo().mem(0x202, &[0xAB, 0xCD]),
+        0xF000,
+        &[I(0xABCD), Pc(0x204)],
+    );
+    check("plane", xo(), 0xF201, &[Pc(0x202)]);
+    check("audio", xo().i(0x300), 0xF002, &[Pc(0x202)]);
+    check("pitch", xo().v(1, 100), 0xF13A, &[Pc(0x202)]);
+
+    for opcode in [0x00D2, 0x5132, 0x5133, 0xF000, 0xF201, 0xF002, 0xF13A] {
+        check_err(
+            "xo-chip only",
+            Machine::new(),
+            opcode,
+            ChipError::IllegalInstruction {
+                addr: 0x200,
+                opcode,
+            },
+        );
+    }
+}
+
+#[test]
+fn end_of_memory() {
+    // Instructions at the top of XO-CHIP memory can not move pc past 0xFFFF
+    let at = |pc: u16, opcode: u16| {
+        let mut chip = Machine::new()
+            .quirks(Preset::XoChip)
+            .mem(pc, &opcode.to_be_bytes())
+            .build(&[]);
+        chip.pc = pc;
+        chip.execute_inst()
+    };
+    let fault = |pc| Err(ChipError::PcOutOfRange { pc });
+    assert_eq!(at(0xFFFE, 0x6001), fault(0xFFFE), "ld");
+    assert_eq!(at(0xFFFC, 0x3000), fault(0xFFFC), "skip");
+    assert_eq!(at(0xFFFC, 0xF000), fault(0xFFFC), "long i");
+    assert_eq!(at(0xFFFA, 0x3000), Ok(()), "skip to the last word");
+
+    let mut chip = Machine::new().stack(&[0xFFFE]).build(&[0x00EE]);
+    assert_eq!(
+        chip.execute_inst(),
+        Err(ChipError::PcOutOfRange { pc: 0xFFFE }),
+        "ret"
+    );
+}
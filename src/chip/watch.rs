@@ -0,0 +1,478 @@
+use std::{fmt, str::FromStr};
+
+use super::Chip;
+use crate::{error::ChipError, screen::Interface};
+
+// Registers the debugger can inspect
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Reg {
+    V(u8),
+    I,
+    Pc,
+    Sp,
+    Dt,
+    St,
+}
+
+impl FromStr for Reg {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let lower = s.to_ascii_lowercase();
+        let reg = match lower.as_str() {
+            "i" => Reg::I,
+            "pc" => Reg::Pc,
+            "sp" => Reg::Sp,
+            "dt" => Reg::Dt,
+            "st" => Reg::St,
+            _ => match lower.strip_prefix('v').map(|x| u8::from_str_radix(x, 16)) {
+                Some(Ok(x)) if x < 16 && lower.len() == 2 => Reg::V(x),
+                _ => return Err(format!("unknown register: {s}")),
+            },
+        };
+        Ok(reg)
+    }
+}
+
+impl fmt::Display for Reg {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Reg::V(x) => write!(f, "V{x:X}"),
+            Reg::I => write!(f, "I"),
+            Reg::Pc => write!(f, "pc"),
+            Reg::Sp => write!(f, "sp"),
+            Reg::Dt => write!(f, "DT"),
+            Reg::St => write!(f, "ST"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    Read,
+    Write,
+    ReadWrite,
+}
+
+impl Access {
+    fn matches(self, write: bool) -> bool {
+        match self {
+            Access::Read => !write,
+            Access::Write => write,
+            Access::ReadWrite => true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CmpOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl CmpOp {
+    fn eval(self, lhs: u16, rhs: u16) -> bool {
+        match self {
+            CmpOp::Eq => lhs == rhs,
+            CmpOp::Ne => lhs != rhs,
+            CmpOp::Lt => lhs < rhs,
+            CmpOp::Le => lhs <= rhs,
+            CmpOp::Gt => lhs > rhs,
+            CmpOp::Ge => lhs >= rhs,
+        }
+    }
+}
+
+impl FromStr for CmpOp {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "==" => Ok(CmpOp::Eq),
+            "!=" => Ok(CmpOp::Ne),
+            "<" => Ok(CmpOp::Lt),
+            "<=" => Ok(CmpOp::Le),
+            ">" => Ok(CmpOp::Gt),
+            ">=" => Ok(CmpOp::Ge),
+            _ => Err(format!("unknown comparison: {s}")),
+        }
+    }
+}
+
+impl fmt::Display for CmpOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let op = match self {
+            CmpOp::Eq => "==",
+            CmpOp::Ne => "!=",
+            CmpOp::Lt => "<",
+            CmpOp::Le => "<=",
+            CmpOp::Gt => ">",
+            CmpOp::Ge => ">=",
+        };
+        write!(f, "{op}")
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Watchpoint {
+    // Break after an instruction accessed memory in start..=end
+    Memory {
+        start: u16,
+        end: u16,
+        access: Access,
+    },
+    // Break after an instruction changed the register
+    Change(Reg),
+    // Break when the comparison of the register with value becomes true
+    Condition {
+        reg: Reg,
+        op: CmpOp,
+        value: u16,
+    },
+}
+
+impl Watchpoint {
+    pub fn reg(&self) -> Option<Reg> {
+        match self {
+            Watchpoint::Memory { .. } => None,
+            Watchpoint::Change(reg) | Watchpoint::Condition { reg, .. } => Some(*reg),
+        }
+    }
+}
+
+impl fmt::Display for Watchpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Watchpoint::Memory { start, end, access } => {
+                let access = match access {
+                    Access::Read => "read",
+                    Access::Write => "write",
+                    Access::ReadWrite => "access",
+                };
+                if start == end {
+                    write!(f, "{access} {start:#06x}")
+                } else {
+                    write!(f, "{access} {start:#06x}-{end:#06x}")
+                }
+            }
+            Watchpoint::Change(reg) => write!(f, "change {reg}"),
+            Watchpoint::Condition { reg, op, value } => write!(f, "{reg} {op} {value:#x}"),
+        }
+    }
+}
+
+// Hex number with an optional 0x prefix
+pub fn parse_hex(arg: &str) -> Result<u16, String> {
+    let digits = arg.trim_start_matches("0x").trim_start_matches("0X");
+    u16::from_str_radix(digits, 16).map_err(|_| format!("invalid number: {arg}"))
+}
+
+// Parses "<reg>", "<reg> <op> <value>" or "<addr>[-<end>]", memory watchpoints use access
+pub fn parse_watchpoint(args: &[&str], access: Access) -> Result<Watchpoint, String> {
+    match args {
+        [reg] if access == Access::Write && reg.parse::<Reg>().is_ok() => {
+            Ok(Watchpoint::Change(reg.parse()?))
+        }
+        [reg, op, value] if access == Access::Write => Ok(Watchpoint::Condition {
+            reg: reg.parse()?,
+            op: op.parse()?,
+            value: parse_hex(value)?,
+        }),
+        [range] => {
+            let (start, end) = match range.split_once('-') {
+                Some((start, end)) => (parse_hex(start)?, parse_hex(end)?),
+                None => (parse_hex(range)?, parse_hex(range)?),
+            };
+            if start > end {
+                return Err(format!("invalid range: {range}"));
+            }
+            Ok(Watchpoint::Memory { start, end, access })
+        }
+        _ => Err(String::from(
+            "expected <addr>[-<end>], <reg> or <reg> <op> <value>",
+        )),
+    }
+}
+
+// A watchpoint with the register value seen after the last instruction
+struct Watch {
+    point: Watchpoint,
+    last: u16,
+}
+
+#[derive(Default)]
+pub struct Watches {
+    watches: Vec<Watch>,
+    // Why the machine should pause before the next instruction
+    triggered: Option<String>,
+}
+
+#[allow(dead_code)]
+impl Watches {
+    pub fn add(&mut self, point: Watchpoint, current: u16) {
+        self.watches.push(Watch {
+            point,
+            last: current,
+        });
+    }
+
+    // Removes the watchpoint with the index shown by list
+    pub fn remove(&mut self, index: usize) -> Option<Watchpoint> {
+        (index < self.watches.len()).then(|| self.watches.remove(index).point)
+    }
+
+    pub fn remove_point(&mut self, point: &Watchpoint) -> bool {
+        match self.watches.iter().position(|watch| watch.point == *point) {
+            Some(index) => {
+                self.watches.remove(index);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn list(&self) -> impl Iterator<Item = &Watchpoint> {
+        self.watches.iter().map(|watch| &watch.point)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.watches.is_empty()
+    }
+
+    pub fn triggered(&self) -> bool {
+        self.triggered.is_some()
+    }
+
+    pub fn take_triggered(&mut self) -> Option<String> {
+        self.triggered.take()
+    }
+
+    pub fn memory_access(&mut self, pc: u16, addr: usize, val: u8, write: bool) {
+        if self.triggered.is_some() {
+            return;
+        }
+        for (index, watch) in self.watches.iter().enumerate() {
+            if let Watchpoint::Memory { start, end, access } = watch.point {
+                if (start as usize..=end as usize).contains(&addr) && access.matches(write) {
+                    let kind = if write { "write" } else { "read" };
+                    self.triggered = Some(format!(
+                        "watchpoint {index}: {kind} {addr:#06x} = {val:#04x} at pc {pc:#06x}"
+                    ));
+                    return;
+                }
+            }
+        }
+    }
+
+    // Compares the watched registers with their values after the last instruction
+    pub fn check_registers(&mut self, pc: u16, value: impl Fn(Reg) -> u16) {
+        for (index, watch) in self.watches.iter_mut().enumerate() {
+            let reg = match watch.point.reg() {
+                Some(reg) => reg,
+                None => continue,
+            };
+            let (old, new) = (watch.last, value(reg));
+            watch.last = new;
+            let hit = match watch.point {
+                Watchpoint::Change(_) => old != new,
+                Watchpoint::Condition { op, value, .. } => {
+                    !op.eval(old, value) && op.eval(new, value)
+                }
+                Watchpoint::Memory { .. } => false,
+            };
+            if hit && self.triggered.is_none() {
+                self.triggered = Some(format!(
+                    "watchpoint {index}: {reg} {old:#x} -> {new:#x} at pc {pc:#06x}"
+                ));
+            }
+        }
+    }
+}
+
+impl<T: Interface> Chip<T> {
+    pub fn reg_value(&self, reg: Reg) -> u16 {
+        match reg {
+            Reg::V(x) => self.registers.get_reg_v(x).unwrap_or_default() as u16,
+            Reg::I => self.registers.i,
+            Reg::Pc => self.pc,
+            Reg::Sp => self.stackpointer as u16,
+            Reg::Dt => self.delay_timer as u16,
+            Reg::St => self.sound_timer as u16,
+        }
+    }
+
+    pub fn set_reg_value(&mut self, reg: Reg, val: u16) {
+        match reg {
+            Reg::V(x) => {
+                let _ = self.registers.set_reg_v(x, val as u8);
+            }
+            Reg::I => self.registers.i = val,
+            Reg::Pc => self.pc = val,
+            Reg::Sp => self.stackpointer = (val as u8).min(self.stack.len() as u8),
+            Reg::Dt => self.delay_timer = val as u8,
+            Reg::St => self.sound_timer = val as u8,
+        }
+    }
+
+    // Checks register watchpoints after the instruction at pc
+    pub(super) fn debug_watch(&mut self, pc: u16) {
+        if let Some(mut debugger) = self.debugger.take() {
+            if !debugger.watches.is_empty() {
+                debugger
+                    .watches
+                    .check_registers(pc, |reg| self.reg_value(reg));
+            }
+            self.debugger = Some(debugger);
+        }
+    }
+
+    // Reports a data access to the memory watchpoints
+    fn watch_memory(&mut self, addr: usize, val: u8, write: bool) {
+        if let Some(debugger) = self.debugger.as_mut() {
+            debugger.watches.memory_access(self.pc, addr, val, write);
+        }
+    }
+
+    // Instrumented memory accessors, every data access of an instruction goes through these
+    pub(super) fn set_byte(&mut self, addr: usize, val: u8) -> Result<(), ChipError> {
+        if addr < self.memory.len() {
+            self.watch_memory(addr, val, true);
+            self.memory[addr] = val;
+            Ok(())
+        } else {
+            Err(ChipError::MemoryFault { pc: self.pc, addr })
+        }
+    }
+
+    pub(super) fn get_addr(&mut self, addr: usize) -> Result<u8, ChipError> {
+        if addr < self.memory.len() {
+            let val = self.memory[addr];
+            self.watch_memory(addr, val, false);
+            Ok(val)
+        } else {
+            Err(ChipError::MemoryFault { pc: self.pc, addr })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        chip::{debugger::Command, Debugger},
+        config::PROG_POS_START,
+        quirks::Quirks,
+        screen::headless::Headless,
+    };
+
+    fn chip(program: &[u16]) -> Chip<Headless> {
+        let mut chip = Chip::new(PROG_POS_START, Headless::new(), Quirks::default());
+        let rom = program.iter().flat_map(|word| word.to_be_bytes()).collect();
+        chip.load_prog(rom).unwrap();
+        chip.debugger = Some(Debugger::running());
+        chip
+    }
+
+    fn watch(chip: &mut Chip<Headless>, point: Watchpoint) {
+        chip.debug_command(Command::Watch(point));
+    }
+
+    // Runs the next instruction like the run loop, returns why the debugger would pause
+    fn step(chip: &mut Chip<Headless>) -> Option<String> {
+        let pc = chip.pc;
+        chip.execute_inst().unwrap();
+        chip.debug_watch(pc);
+        chip.debugger.as_mut().unwrap().watches.take_triggered()
+    }
+
+    fn memory(start: u16, end: u16, access: Access) -> Watchpoint {
+        Watchpoint::Memory { start, end, access }
+    }
+
+    #[test]
+    fn memory_watchpoints_see_every_access() {
+        let mut chip = chip(&[
+            0xF255, // LD [I], V2
+            0xA300, // LD I, 0x300
+            0xF333, // LD B, V3
+            0xD015, // DRW V0, V1, 5
+            0xF265, // LD V2, [I]
+            0xA300, // LD I, 0x300
+            0xF055, // LD [I], V0
+        ]);
+        chip.registers.i = 0x300;
+        for (reg, val) in [(0, 1), (1, 2), (2, 3), (3, 123)] {
+            chip.registers.set_reg_v(reg, val).unwrap();
+        }
+        watch(&mut chip, memory(0x301, 0x302, Access::Write));
+        watch(&mut chip, memory(0x302, 0x302, Access::Read));
+
+        // The first access in a watched range is reported
+        assert_eq!(
+            step(&mut chip).as_deref(),
+            Some("watchpoint 0: write 0x0301 = 0x02 at pc 0x0200")
+        );
+        assert_eq!(step(&mut chip), None);
+        assert_eq!(
+            step(&mut chip).as_deref(),
+            Some("watchpoint 0: write 0x0301 = 0x02 at pc 0x0204")
+        );
+        // Sprite and register loads are reads, the write watchpoint ignores them
+        assert_eq!(
+            step(&mut chip).as_deref(),
+            Some("watchpoint 1: read 0x0302 = 0x03 at pc 0x0206")
+        );
+        assert_eq!(
+            step(&mut chip).as_deref(),
+            Some("watchpoint 1: read 0x0302 = 0x03 at pc 0x0208")
+        );
+        watch(&mut chip, memory(0x300, 0x300, Access::ReadWrite));
+        assert_eq!(step(&mut chip), None);
+        assert_eq!(
+            step(&mut chip).as_deref(),
+            Some("watchpoint 2: write 0x0300 = 0x01 at pc 0x020c")
+        );
+    }
+
+    #[test]
+    fn register_watchpoints_fire_on_changes_and_edges() {
+        let mut chip = chip(&[
+            0x6310, // LD V3, 0x10
+            0x6310, // LD V3, 0x10
+            0x6311, // LD V3, 0x11
+            0x6310, // LD V3, 0x10
+            0xA123, // LD I, 0x123
+            0xA123, // LD I, 0x123
+        ]);
+        watch(
+            &mut chip,
+            Watchpoint::Condition {
+                reg: Reg::V(3),
+                op: CmpOp::Eq,
+                value: 0x10,
+            },
+        );
+        watch(&mut chip, Watchpoint::Change(Reg::I));
+
+        assert_eq!(
+            step(&mut chip).as_deref(),
+            Some("watchpoint 0: V3 0x0 -> 0x10 at pc 0x0200")
+        );
+        // Still equal, the condition has not become true again
+        assert_eq!(step(&mut chip), None);
+        assert_eq!(step(&mut chip), None);
+        assert_eq!(
+            step(&mut chip).as_deref(),
+            Some("watchpoint 0: V3 0x11 -> 0x10 at pc 0x0206")
+        );
+        assert_eq!(
+            step(&mut chip).as_deref(),
+            Some("watchpoint 1: I 0x0 -> 0x123 at pc 0x0208")
+        );
+        assert_eq!(step(&mut chip), None);
+    }
+}
@@ -0,0 +1,215 @@
+use std::path::PathBuf;
+
+use chip_8::{
+    config::{TuiOutputMode, TUI_OUTPUT_MODE},
+    quirks::{Preset, Quirks},
+    rng::RngKind,
+    screen::{animation::AnimationFormat, graphics::GraphicsMode, image::ImageFormat},
+};
+
+pub const USAGE: &str = "usage: chip_8 [options] <rom>
+
+  <rom> is a binary rom or Octo source (.8o), which is compiled before loading.
+  Its :breakpoint directives become debugger breakpoints.
+
+options:
+  --quirks <preset>    cosmac-vip (default), chip-48, super-chip or xo-chip,
+                       xo-chip also enables the XO-CHIP machine (64K memory, bitplanes)
+  --seed <n>           seed for the random number source, random by default
+  --rng <kind>         std (default) or cosmac-vip, which needs --vip-interpreter
+  --vip-interpreter <file>
+                       512 byte dump of the COSMAC VIP CHIP-8 interpreter, the
+                       cosmac-vip rng reads its second page like the original
+  --movie-record <file>
+                       record keypad input per frame into a movie file
+  --movie-play <file>  replay a recorded movie instead of reading the keyboard
+  --debug              start paused in the debugger, type help at its prompt
+  --debug-view         show registers, stack, disassembly and memory next to the game
+  --rewind             keep the last frames for the rewind hotkey
+  --gdb <port>         wait for a gdb remote connection on 127.0.0.1:<port>
+  --screenshot-at-frame <n>
+                       save a screenshot once frame n (counted from 0) has been drawn
+  --screenshot <file>  file for --screenshot-at-frame, .png or .pbm
+                       (default <rom>.frame<n>.png)
+  --screenshot-scale <n>
+                       pixel size of screenshots (default 1)
+  --record <file>      record a clip of the screen from the start, .gif or .png (APNG)
+  --record-scale <n>   pixel size of clips (default 1)
+  --cast <file>        record the terminal output as an asciicast v2 file
+  --render <mode>      ascii (default), block, half-block or braille,
+                       half-block and braille fit more pixels into each character
+  --graphics <protocol>
+                       draw the screen as a bitmap with auto, sixel or kitty,
+                       auto asks the terminal and falls back to --render characters
+  --graphics-scale <n> pixel size of the bitmap (default 4)
+
+hotkeys:
+  F1-F4                save state to slot 1-4 (<rom>.state<slot>)
+  F5-F8                load state from slot 1-4
+  Backspace (hold)     rewind, with --rewind
+  F9                   break into the debugger
+  F11                  start or stop a clip (<rom>.frame<n>.gif)
+  F12                  save a screenshot (<rom>.frame<n>.png)
+  Esc                  quit";
+
+pub struct Args {
+    pub rom: String,
+    pub quirks: Quirks,
+    pub seed: Option<u64>,
+    pub rng: RngKind,
+    pub vip_interpreter: Option<PathBuf>,
+    pub movie_record: Option<PathBuf>,
+    pub movie_play: Option<PathBuf>,
+    pub debug: bool,
+    pub debug_view: bool,
+    pub rewind: bool,
+    pub gdb_port: Option<u16>,
+    pub screenshot_at: Option<u32>,
+    pub screenshot: Option<PathBuf>,
+    pub screenshot_scale: usize,
+    pub record: Option<PathBuf>,
+    pub record_scale: usize,
+    pub cast: Option<PathBuf>,
+    pub render: TuiOutputMode,
+    pub graphics: Option<GraphicsMode>,
+    pub graphics_scale: usize,
+}
+
+pub fn parse_args(args: &[String]) -> Result<Args, String> {
+    let mut rom: Option<String> = None;
+    let mut quirks = Quirks::default();
+    let mut seed: Option<u64> = None;
+    let mut rng = RngKind::Std;
+    let mut vip_interpreter: Option<PathBuf> = None;
+    let mut movie_record: Option<PathBuf> = None;
+    let mut movie_play: Option<PathBuf> = None;
+    let mut debug = false;
+    let mut debug_view = false;
+    let mut rewind = false;
+    let mut gdb_port: Option<u16> = None;
+    let mut screenshot_at: Option<u32> = None;
+    let mut screenshot: Option<PathBuf> = None;
+    let mut screenshot_scale = 1;
+    let mut record: Option<PathBuf> = None;
+    let mut record_scale = 1;
+    let mut cast: Option<PathBuf> = None;
+    let mut render = TUI_OUTPUT_MODE;
+    let mut graphics: Option<GraphicsMode> = None;
+    let mut graphics_scale = 4;
+
+    let mut iter = args.iter().skip(1);
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--quirks" => {
+                let preset: Preset = next_value(&mut iter, arg)?.parse()?;
+                quirks = Quirks::from_preset(preset);
+            }
+            "--seed" => {
+                let value = next_value(&mut iter, arg)?;
+                seed = Some(
+                    value
+                        .parse()
+                        .map_err(|_| format!("invalid seed: {value}"))?,
+                );
+            }
+            "--rng" => rng = next_value(&mut iter, arg)?.parse()?,
+            "--vip-interpreter" => vip_interpreter = Some(next_value(&mut iter, arg)?.into()),
+            "--movie-record" => movie_record = Some(next_value(&mut iter, arg)?.into()),
+            "--movie-play" => movie_play = Some(next_value(&mut iter, arg)?.into()),
+            "--debug" => debug = true,
+            "--debug-view" => debug_view = true,
+            "--rewind" => rewind = true,
+            "--gdb" => {
+                let value = next_value(&mut iter, arg)?;
+                gdb_port = Some(
+                    value
+                        .parse()
+                        .map_err(|_| format!("invalid port: {value}"))?,
+                );
+            }
+            "--screenshot-at-frame" => {
+                let value = next_value(&mut iter, arg)?;
+                screenshot_at = Some(
+                    value
+                        .parse()
+                        .map_err(|_| format!("invalid frame: {value}"))?,
+                );
+            }
+            "--screenshot" => screenshot = Some(next_value(&mut iter, arg)?.into()),
+            "--screenshot-scale" => screenshot_scale = parse_scale(next_value(&mut iter, arg)?)?,
+            "--record" => record = Some(next_value(&mut iter, arg)?.into()),
+            "--record-scale" => record_scale = parse_scale(next_value(&mut iter, arg)?)?,
+            "--cast" => cast = Some(next_value(&mut iter, arg)?.into()),
+            "--render" => render = next_value(&mut iter, arg)?.parse()?,
+            "--graphics" => graphics = Some(next_value(&mut iter, arg)?.parse()?),
+            "--graphics-scale" => graphics_scale = parse_scale(next_value(&mut iter, arg)?)?,
+            _ if arg.starts_with("--") => return Err(format!("unknown option: {arg}")),
+            _ if rom.is_none() => rom = Some(arg.clone()),
+            _ => return Err(format!("unexpected argument: {arg}")),
+        }
+    }
+
+    if movie_record.is_some() && movie_play.is_some() {
+        return Err(String::from(
+            "--movie-record and --movie-play can not be used together",
+        ));
+    }
+
+    if rng == RngKind::CosmacVip && vip_interpreter.is_none() {
+        return Err(String::from("--rng cosmac-vip needs --vip-interpreter"));
+    }
+
+    if let Some(path) = &screenshot {
+        if screenshot_at.is_none() {
+            return Err(String::from("--screenshot needs --screenshot-at-frame"));
+        }
+        if ImageFormat::from_path(path).is_none() {
+            return Err(format!("unknown image format: {}", path.display()));
+        }
+    }
+
+    if let Some(path) = &record {
+        if AnimationFormat::from_path(path).is_none() {
+            return Err(format!("unknown animation format: {}", path.display()));
+        }
+    }
+
+    Ok(Args {
+        rom: rom.ok_or("missing filepath argument")?,
+        quirks,
+        seed,
+        rng,
+        vip_interpreter,
+        movie_record,
+        movie_play,
+        debug,
+        debug_view,
+        rewind,
+        gdb_port,
+        screenshot_at,
+        screenshot,
+        screenshot_scale,
+        record,
+        record_scale,
+        cast,
+        render,
+        graphics,
+        graphics_scale,
+    })
+}
+
+fn next_value<'a>(
+    iter: &mut impl Iterator<Item = &'a String>,
+    option: &str,
+) -> Result<&'a String, String> {
+    iter.next()
+        .ok_or_else(|| format!("missing value for {option}"))
+}
+
+fn parse_scale(value: &str) -> Result<usize, String> {
+    value
+        .parse()
+        .ok()
+        .filter(|scale| *scale > 0)
+        .ok_or_else(|| format!("invalid scale: {value}"))
+}
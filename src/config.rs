@@ -1,26 +1,69 @@
+use std::str::FromStr;
+
 // Tui Specific
-#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum TuiOutputMode {
     VecU8,
     String,
+    // One column per pixel and two pixel rows per terminal row
+    HalfBlock,
+    // 2x4 pixels per braille character
+    Braille,
+}
+
+impl FromStr for TuiOutputMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "ascii" => Ok(TuiOutputMode::VecU8),
+            "block" => Ok(TuiOutputMode::String),
+            "half-block" => Ok(TuiOutputMode::HalfBlock),
+            "braille" => Ok(TuiOutputMode::Braille),
+            _ => Err(format!(
+                "unknown render mode: {s} (expected ascii, block, half-block or braille)"
+            )),
+        }
+    }
 }
 
+// Default render mode, --render picks another one
 pub const TUI_OUTPUT_MODE: TuiOutputMode = TuiOutputMode::VecU8;
 pub const PIXEL_ON_VEC_U8: char = '@'; // For VecU8
 pub const PIXEL_ON_STRING: char = '█'; // For String
 pub const PIXEL_OFF_VEC_U8: char = ' ';
 pub const PIXEL_OFF_STRING: char = ' ';
+pub const PIXEL_UPPER_HALF: char = '▀'; // For HalfBlock
+pub const PIXEL_LOWER_HALF: char = '▄';
+pub const PIXEL_FULL: char = '█';
+// Terminals only report key presses, a key counts as held this long after its last press
+pub const KEY_HOLD_TIME_MS: u64 = 200;
+// RGB colours for pixel values 0-3 (XO-CHIP bitplanes), 0 is the background
+pub const PALETTE: [(u8, u8, u8); 4] = [
+    (0x00, 0x00, 0x00),
+    (0xFF, 0xFF, 0xFF),
+    (0xFF, 0x66, 0x00),
+    (0x66, 0x22, 0x00),
+];
+// Debug view, instructions shown before and after pc and rows of 8 bytes shown around I
+pub const DEBUG_VIEW_CONTEXT: u16 = 5;
+pub const DEBUG_VIEW_MEMORY_ROWS: usize = 6;
 
 // Can change
 pub const SCREEN_REFRESH_RATE: usize = 60; // 0<FPS<256 // Default: 60
 pub const INSTRUCTION_FREQUENCY: usize = 500; // 0<IPS<n // Default: 500
+pub const REWIND_BUFFER_SIZE: usize = 8 * 1024 * 1024; // Bytes of rewind history // Default: 8 MiB
 
 //  Do not change
 pub const MEMSIZE: usize = 4096;
+pub const XO_CHIP_MEMSIZE: usize = 65536;
 pub const FONT_POS_START: usize = 0x50;
+pub const BIG_FONT_POS_START: usize = 0xA0;
 pub const PROG_POS_START: u16 = 0x200;
 pub const SCREEN_WIDTH: u8 = 64;
 pub const SCREEN_HEIGHT: u8 = 32;
+pub const HIRES_SCREEN_WIDTH: u8 = 128;
+pub const HIRES_SCREEN_HEIGHT: u8 = 64;
 
 // --- Keys ---
 
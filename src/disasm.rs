@@ -0,0 +1,197 @@
+pub mod flow;
+
+use crate::{
+    config::PROG_POS_START,
+    inst::{hex_to_inst, inst_to_hex, Inst},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Syntax {
+    // Conventional mnemonics, see the Display impl of Inst
+    Chip8,
+    Octo,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Options {
+    pub syntax: Syntax,
+    // Show the address and raw word columns
+    pub columns: bool,
+    // Address the rom is loaded at
+    pub origin: u16,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            syntax: Syntax::Chip8,
+            columns: true,
+            origin: PROG_POS_START,
+        }
+    }
+}
+
+// An instruction or, if inst is None, data bytes at addr
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Line {
+    pub addr: u16,
+    pub bytes: Vec<u8>,
+    pub inst: Option<Inst>,
+}
+
+impl Line {
+    fn word(&self, index: usize) -> u16 {
+        u16::from_be_bytes([self.bytes[2 * index], self.bytes[2 * index + 1]])
+    }
+
+    pub fn is_data(&self) -> bool {
+        self.inst.is_none()
+    }
+}
+
+// Decodes a single line at offset, undecodable words and 0NNN become data
+pub fn decode_at(rom: &[u8], offset: usize, origin: u16) -> Option<Line> {
+    let addr = origin.wrapping_add(offset as u16);
+    let bytes = rom.get(offset..)?;
+    let line = match bytes {
+        [] => return None,
+        [byte] => Line {
+            addr,
+            bytes: vec![*byte],
+            inst: None,
+        },
+        [high, low, rest @ ..] => match hex_to_inst(u16::from_be_bytes([*high, *low])) {
+            // F000 NNNN takes 4 bytes
+            Some(Inst::LdIL) if rest.len() >= 2 => Line {
+                addr,
+                bytes: bytes[..4].to_vec(),
+                inst: Some(Inst::LdIL),
+            },
+            None | Some(Inst::Empty) | Some(Inst::LdIL) => Line {
+                addr,
+                bytes: vec![*high, *low],
+                inst: None,
+            },
+            // Words like 9XY1 decode but would reassemble differently, keep them as data
+            Some(inst) if inst_to_hex(inst) != u16::from_be_bytes([*high, *low]) => Line {
+                addr,
+                bytes: vec![*high, *low],
+                inst: None,
+            },
+            Some(inst) => Line {
+                addr,
+                bytes: vec![*high, *low],
+                inst: Some(inst),
+            },
+        },
+    };
+    Some(line)
+}
+
+// Linear sweep over the whole rom
+pub fn decode(rom: &[u8], origin: u16) -> Vec<Line> {
+    let mut lines = Vec::new();
+    let mut offset = 0;
+    while let Some(line) = decode_at(rom, offset, origin) {
+        offset += line.bytes.len();
+        lines.push(line);
+    }
+    lines
+}
+
+fn octo_inst(inst: &Inst) -> String {
+    match inst {
+        Inst::Empty => String::new(),
+        Inst::Cls => String::from("clear"),
+        Inst::Ret => String::from("return"),
+        Inst::Jmp { addr } => format!("jump 0x{addr:03X}"),
+        Inst::Call { addr } => format!(":call 0x{addr:03X}"),
+        // Octo conditions run the next instruction when true, so they are inverted skips
+        Inst::SV { vx, byte } => format!("if v{vx:x} != 0x{byte:02X} then"),
+        Inst::SnV { vx, byte } => format!("if v{vx:x} == 0x{byte:02X} then"),
+        Inst::SR { vx, vy } => format!("if v{vx:x} != v{vy:x} then"),
+        Inst::SnR { vx, vy } => format!("if v{vx:x} == v{vy:x} then"),
+        Inst::SKp { vx } => format!("if v{vx:x} -key then"),
+        Inst::SKnp { vx } => format!("if v{vx:x} key then"),
+        Inst::LdV { vx, byte } => format!("v{vx:x} := 0x{byte:02X}"),
+        Inst::AddV { vx, byte } => format!("v{vx:x} += 0x{byte:02X}"),
+        Inst::LdR { vx, vy } => format!("v{vx:x} := v{vy:x}"),
+        Inst::OrR { vx, vy } => format!("v{vx:x} |= v{vy:x}"),
+        Inst::AndR { vx, vy } => format!("v{vx:x} &= v{vy:x}"),
+        Inst::XorR { vx, vy } => format!("v{vx:x} ^= v{vy:x}"),
+        Inst::AddR { vx, vy } => format!("v{vx:x} += v{vy:x}"),
+        Inst::SubR { vx, vy } => format!("v{vx:x} -= v{vy:x}"),
+        Inst::Shr { vx, vy } => format!("v{vx:x} >>= v{vy:x}"),
+        Inst::SubnR { vx, vy } => format!("v{vx:x} =- v{vy:x}"),
+        Inst::Shl { vx, vy } => format!("v{vx:x} <<= v{vy:x}"),
+        Inst::LdI { addr } => format!("i := 0x{addr:03X}"),
+        Inst::JpV0 { addr } => format!("jump0 0x{addr:03X}"),
+        Inst::Rnd { vx, byte } => format!("v{vx:x} := random 0x{byte:02X}"),
+        Inst::Disp { vx, vy, n } => format!("sprite v{vx:x} v{vy:x} {n}"),
+        Inst::LdRDt { vx } => format!("v{vx:x} := delay"),
+        Inst::LdRKp { vx } => format!("v{vx:x} := key"),
+        Inst::LdDtR { vx } => format!("delay := v{vx:x}"),
+        Inst::LdStR { vx } => format!("buzzer := v{vx:x}"),
+        Inst::AddRI { vx } => format!("i += v{vx:x}"),
+        Inst::LdIF { vx } => format!("i := hex v{vx:x}"),
+        Inst::LdBCDR { vx } => format!("bcd v{vx:x}"),
+        Inst::LdIR { vx } => format!("save v{vx:x}"),
+        Inst::LdRI { vx } => format!("load v{vx:x}"),
+        Inst::ScrollDown { n } => format!("scroll-down {n}"),
+        Inst::ScrollRight => String::from("scroll-right"),
+        Inst::ScrollLeft => String::from("scroll-left"),
+        Inst::Exit => String::from("exit"),
+        Inst::Low => String::from("lores"),
+        Inst::High => String::from("hires"),
+        Inst::LdIBigF { vx } => format!("i := bighex v{vx:x}"),
+        Inst::LdRplR { vx } => format!("saveflags v{vx:x}"),
+        Inst::LdRRpl { vx } => format!("loadflags v{vx:x}"),
+        Inst::ScrollUp { n } => format!("scroll-up {n}"),
+        Inst::SaveR { vx, vy } => format!("save v{vx:x} - v{vy:x}"),
+        Inst::LoadR { vx, vy } => format!("load v{vx:x} - v{vy:x}"),
+        Inst::LdIL => String::from("i := long"),
+        Inst::Plane { n } => format!("plane {n}"),
+        Inst::Audio => String::from("audio"),
+        Inst::Pitch { vx } => format!("pitch := v{vx:x}"),
+    }
+}
+
+// Text of the line without columns
+pub fn line_text(line: &Line, syntax: Syntax) -> String {
+    match (line.inst, syntax) {
+        (Some(Inst::LdIL), Syntax::Chip8) => format!("LD I, LONG 0x{:04X}", line.word(1)),
+        (Some(Inst::LdIL), Syntax::Octo) => format!("i := long 0x{:04X}", line.word(1)),
+        (Some(inst), Syntax::Chip8) => inst.to_string(),
+        (Some(inst), Syntax::Octo) => octo_inst(&inst),
+        (None, Syntax::Chip8) if line.bytes.len() == 2 => format!("DW 0x{:04X}", line.word(0)),
+        (None, Syntax::Chip8) => {
+            let bytes: Vec<String> = line.bytes.iter().map(|b| format!("0x{b:02X}")).collect();
+            format!("DB {}", bytes.join(", "))
+        }
+        (None, Syntax::Octo) => {
+            let bytes: Vec<String> = line.bytes.iter().map(|b| format!("0x{b:02X}")).collect();
+            bytes.join(" ")
+        }
+    }
+}
+
+pub fn format_line(line: &Line, options: &Options) -> String {
+    let text = line_text(line, options.syntax);
+    if !options.columns {
+        return text;
+    }
+    let raw: String = line.bytes.iter().map(|b| format!("{b:02X}")).collect();
+    match options.syntax {
+        Syntax::Chip8 => format!("{:04X}: {raw:<8}  {text}", line.addr),
+        Syntax::Octo => format!("{text:<28} # {:04X}: {raw}", line.addr),
+    }
+}
+
+pub fn disassemble(rom: &[u8], options: &Options) -> String {
+    let mut out = String::new();
+    for line in decode(rom, options.origin) {
+        out.push_str(&format_line(&line, options));
+        out.push('\n');
+    }
+    out
+}
@@ -0,0 +1,374 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::Write as _,
+};
+
+use super::{decode_at, format_line, line_text, Line, Options, Syntax};
+use crate::inst::Inst;
+
+// Bytes per data row in listings
+const DATA_ROW_LEN: usize = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum EdgeKind {
+    // Fall through to the next instruction, or the instruction after a call
+    Next,
+    Jump,
+    // Taken when a skip instruction skips
+    Skip,
+    // JP V0, addr with V0 = 0, other targets are unknown
+    Indirect,
+}
+
+// Straight line code, only the last instruction changes the flow
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Block {
+    pub start: u16,
+    pub lines: Vec<Line>,
+    pub successors: Vec<(u16, EdgeKind)>,
+}
+
+impl Block {
+    // Address after the last instruction
+    pub fn end(&self) -> u16 {
+        self.lines
+            .last()
+            .map(|line| line.addr.wrapping_add(line.bytes.len() as u16))
+            .unwrap_or(self.start)
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Analysis {
+    pub origin: u16,
+    // Decoded instructions reached from the entry point
+    pub code: BTreeMap<u16, Line>,
+    pub blocks: BTreeMap<u16, Block>,
+    // Subroutine entries, including the entry point, with the subroutines they call
+    pub calls: BTreeMap<u16, BTreeSet<u16>>,
+    // LD I targets that are not code, most likely sprites or other data
+    pub data_refs: BTreeSet<u16>,
+}
+
+// Flow successors of the instruction, calls continue at the next instruction
+fn successors(rom: &[u8], origin: u16, line: &Line) -> Vec<(u16, EdgeKind)> {
+    let next = line.addr.wrapping_add(line.bytes.len() as u16);
+    match line.inst {
+        Some(Inst::Jmp { addr }) => vec![(addr, EdgeKind::Jump)],
+        Some(Inst::JpV0 { addr }) => vec![(addr, EdgeKind::Indirect)],
+        Some(Inst::Ret) | Some(Inst::Exit) | None => Vec::new(),
+        Some(
+            Inst::SV { .. }
+            | Inst::SnV { .. }
+            | Inst::SR { .. }
+            | Inst::SnR { .. }
+            | Inst::SKp { .. }
+            | Inst::SKnp { .. },
+        ) => {
+            let skipped = decode_at(rom, next.wrapping_sub(origin) as usize, origin)
+                .map(|line| line.bytes.len() as u16)
+                .unwrap_or(2);
+            vec![
+                (next, EdgeKind::Next),
+                (next.wrapping_add(skipped), EdgeKind::Skip),
+            ]
+        }
+        Some(_) => vec![(next, EdgeKind::Next)],
+    }
+}
+
+// Follows the control flow from origin, so data between code is not decoded as instructions
+pub fn analyze(rom: &[u8], origin: u16) -> Analysis {
+    let mut analysis = Analysis {
+        origin,
+        ..Analysis::default()
+    };
+    let in_rom = |addr: u16| (addr.wrapping_sub(origin) as usize) < rom.len();
+
+    // Leaders start basic blocks
+    let mut leaders = BTreeSet::from([origin]);
+    let mut subroutines = BTreeSet::from([origin]);
+    let mut ld_i_targets = BTreeSet::new();
+    let mut work = vec![origin];
+    while let Some(addr) = work.pop() {
+        if analysis.code.contains_key(&addr) || !in_rom(addr) {
+            continue;
+        }
+        let line = match decode_at(rom, addr.wrapping_sub(origin) as usize, origin) {
+            Some(line) if !line.is_data() => line,
+            _ => continue,
+        };
+        let succ = successors(rom, origin, &line);
+        let ends_block = !matches!(succ.as_slice(), [(_, EdgeKind::Next)]);
+        for (target, _) in succ.iter() {
+            if ends_block {
+                leaders.insert(*target);
+            }
+            work.push(*target);
+        }
+        match line.inst {
+            Some(Inst::Call { addr }) => {
+                leaders.insert(addr);
+                subroutines.insert(addr);
+                work.push(addr);
+            }
+            Some(Inst::LdI { addr }) => {
+                ld_i_targets.insert(addr);
+            }
+            _ => {}
+        }
+        analysis.code.insert(addr, line);
+    }
+
+    // Split the reached instructions into blocks, a block ends before a leader or a gap
+    let mut current: Option<Block> = None;
+    for (addr, line) in analysis.code.iter() {
+        let contiguous = current.as_ref().is_some_and(|block| block.end() == *addr);
+        if !contiguous || leaders.contains(addr) {
+            if let Some(block) = current.take() {
+                analysis.blocks.insert(block.start, block);
+            }
+            current = Some(Block {
+                start: *addr,
+                lines: Vec::new(),
+                successors: Vec::new(),
+            });
+        }
+        if let Some(block) = current.as_mut() {
+            block.lines.push(line.clone());
+            block.successors = successors(rom, origin, line);
+        }
+    }
+    if let Some(block) = current.take() {
+        analysis.blocks.insert(block.start, block);
+    }
+
+    // Call graph, each subroutine owns the blocks reachable from its entry without calls
+    for entry in subroutines {
+        let mut callees = BTreeSet::new();
+        let mut seen = BTreeSet::new();
+        let mut work = vec![entry];
+        while let Some(start) = work.pop() {
+            let block = match analysis.blocks.get(&start) {
+                Some(block) if seen.insert(start) => block,
+                _ => continue,
+            };
+            for line in block.lines.iter() {
+                if let Some(Inst::Call { addr }) = line.inst {
+                    callees.insert(addr);
+                }
+            }
+            work.extend(block.successors.iter().map(|(target, _)| *target));
+        }
+        analysis.calls.insert(entry, callees);
+    }
+
+    analysis.data_refs = ld_i_targets
+        .into_iter()
+        .filter(|addr| in_rom(*addr) && !analysis.code.contains_key(addr))
+        .collect();
+    analysis
+}
+
+fn dot_escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+impl Analysis {
+    // Basic blocks as nodes labelled with their instructions, edges are the control flow
+    pub fn cfg_dot(&self) -> String {
+        let mut out = String::from("digraph cfg {\n    node [shape=box fontname=monospace];\n");
+        for block in self.blocks.values() {
+            let mut label = String::new();
+            for line in block.lines.iter() {
+                let text = line_text(line, Syntax::Chip8);
+                let _ = write!(label, "{:04X}: {}\\l", line.addr, dot_escape(&text));
+            }
+            let _ = writeln!(out, "    b{:04X} [label=\"{label}\"];", block.start);
+        }
+        for block in self.blocks.values() {
+            for (target, kind) in block.successors.iter() {
+                let style = match kind {
+                    EdgeKind::Next => "",
+                    EdgeKind::Jump => " [label=\"jump\"]",
+                    EdgeKind::Skip => " [label=\"skip\"]",
+                    EdgeKind::Indirect => " [label=\"+V0\" style=dashed]",
+                };
+                if self.blocks.contains_key(target) {
+                    let _ = writeln!(out, "    b{:04X} -> b{target:04X}{style};", block.start);
+                }
+            }
+        }
+        for block in self.blocks.values() {
+            for line in block.lines.iter() {
+                if let Some(Inst::Call { addr }) = line.inst {
+                    let _ = writeln!(
+                        out,
+                        "    b{:04X} -> b{addr:04X} [label=\"call\" style=dotted];",
+                        block.start
+                    );
+                }
+            }
+        }
+        out.push_str("}\n");
+        out
+    }
+
+    // Subroutines as nodes, edges point from caller to callee
+    pub fn call_graph_dot(&self) -> String {
+        let mut out =
+            String::from("digraph calls {\n    node [shape=ellipse fontname=monospace];\n");
+        for entry in self.calls.keys() {
+            let name = if *entry == self.origin {
+                String::from("entry")
+            } else {
+                format!("sub_{entry:04X}")
+            };
+            let _ = writeln!(out, "    f{entry:04X} [label=\"{name}\"];");
+        }
+        for (entry, callees) in self.calls.iter() {
+            for callee in callees {
+                let _ = writeln!(out, "    f{entry:04X} -> f{callee:04X};");
+            }
+        }
+        out.push_str("}\n");
+        out
+    }
+
+    // Listing with only the reached instructions decoded, everything else is shown as data.
+    // Data that is a LD I target gets its own row with a comment
+    pub fn listing(&self, rom: &[u8], options: &Options) -> String {
+        let comment = match options.syntax {
+            Syntax::Chip8 => ";",
+            Syntax::Octo => "#",
+        };
+        let mut out = String::new();
+        let mut offset = 0;
+        while offset < rom.len() {
+            let addr = self.origin.wrapping_add(offset as u16);
+            if let Some(line) = self.code.get(&addr) {
+                if self.blocks.contains_key(&addr) && addr != self.origin {
+                    out.push('\n');
+                }
+                out.push_str(&format_line(line, options));
+                out.push('\n');
+                offset += line.bytes.len();
+                continue;
+            }
+
+            // Data runs up to the next instruction, rows are split at referenced addresses
+            let mut len = 1;
+            while offset + len < rom.len() && len < DATA_ROW_LEN {
+                let next = addr.wrapping_add(len as u16);
+                if self.code.contains_key(&next) || self.data_refs.contains(&next) {
+                    break;
+                }
+                len += 1;
+            }
+            if self.data_refs.contains(&addr) {
+                let _ = writeln!(out, "{comment} likely sprite/data, loaded into I");
+            }
+            let line = Line {
+                addr,
+                bytes: rom[offset..offset + len].to_vec(),
+                inst: None,
+            };
+            out.push_str(&format_line(&line, options));
+            out.push('\n');
+            offset += len;
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::PROG_POS_START;
+
+    // Calls a subroutine, skips over one instruction, draws a sprite and ends in an indirect jump
+    const ROM: [u8; 28] = [
+        0x22, 0x10, // 200: CALL 0x210
+        0x30, 0x00, // 202: SE V0, 0x00
+        0x12, 0x08, // 204: JP 0x208
+        0x61, 0x01, // 206: LD V1, 0x01
+        0xA2, 0x14, // 208: LD I, 0x214
+        0xD0, 0x15, // 20A: DRW V0, V1, 5
+        0xB2, 0x1A, // 20C: JP V0, 0x21A
+        0xFF, 0xFF, // 20E: never reached
+        0x60, 0x05, // 210: LD V0, 0x05
+        0x00, 0xEE, // 212: RET
+        0xF0, 0x90, 0x90, 0x90, 0xF0, 0x00, // 214: sprite
+        0x12, 0x1A, // 21A: JP 0x21A
+    ];
+
+    #[test]
+    fn blocks_follow_calls_skips_and_jumps() {
+        let analysis = analyze(&ROM, PROG_POS_START);
+        let code: Vec<u16> = analysis.code.keys().copied().collect();
+        assert_eq!(
+            code,
+            [0x200, 0x202, 0x204, 0x206, 0x208, 0x20A, 0x20C, 0x210, 0x212, 0x21A]
+        );
+
+        let blocks: Vec<_> = analysis
+            .blocks
+            .values()
+            .map(|block| (block.start, block.lines.len(), block.successors.clone()))
+            .collect();
+        assert_eq!(
+            blocks,
+            [
+                // The skip splits the block after it
+                (
+                    0x200,
+                    2,
+                    vec![(0x204, EdgeKind::Next), (0x206, EdgeKind::Skip)]
+                ),
+                (0x204, 1, vec![(0x208, EdgeKind::Jump)]),
+                (0x206, 1, vec![(0x208, EdgeKind::Next)]),
+                // Nothing after JP V0 is followed
+                (0x208, 3, vec![(0x21A, EdgeKind::Indirect)]),
+                (0x210, 2, vec![]),
+                (0x21A, 1, vec![(0x21A, EdgeKind::Jump)]),
+            ]
+        );
+
+        assert_eq!(
+            analysis.calls,
+            BTreeMap::from([(0x200, BTreeSet::from([0x210])), (0x210, BTreeSet::new())])
+        );
+        assert_eq!(analysis.data_refs, BTreeSet::from([0x214]));
+    }
+
+    #[test]
+    fn graphs_show_calls_and_edge_kinds() {
+        let analysis = analyze(&ROM, PROG_POS_START);
+        let cfg = analysis.cfg_dot();
+        assert!(cfg.starts_with("digraph cfg {"));
+        assert!(cfg.contains("    b0200 [label=\"0200: CALL 0x210\\l0202: SE V0, 0x00\\l\"];\n"));
+        assert!(cfg.contains("    b0200 -> b0204;\n"));
+        assert!(cfg.contains("    b0200 -> b0206 [label=\"skip\"];\n"));
+        assert!(cfg.contains("    b0204 -> b0208 [label=\"jump\"];\n"));
+        assert!(cfg.contains("    b0208 -> b021A [label=\"+V0\" style=dashed];\n"));
+        assert!(cfg.contains("    b0200 -> b0210 [label=\"call\" style=dotted];\n"));
+
+        let calls = analysis.call_graph_dot();
+        assert!(calls.contains("    f0200 [label=\"entry\"];\n"));
+        assert!(calls.contains("    f0210 [label=\"sub_0210\"];\n"));
+        assert!(calls.contains("    f0200 -> f0210;\n"));
+    }
+
+    #[test]
+    fn ld_i_targets_are_listed_as_data() {
+        let options = Options {
+            columns: false,
+            ..Options::default()
+        };
+        let listing = analyze(&ROM, PROG_POS_START).listing(&ROM, &options);
+        assert!(listing.contains("JP V0, 0x21A\nDW 0xFFFF\n"));
+        assert!(listing.contains(
+            "RET\n; likely sprite/data, loaded into I\nDB 0xF0, 0x90, 0x90, 0x90, 0xF0, 0x00\n"
+        ));
+    }
+}
@@ -0,0 +1,64 @@
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChipError {
+    // Opcode at addr does not decode to a known instruction
+    IllegalInstruction { addr: u16, opcode: u16 },
+    // Call with all 16 stack slots in use
+    StackOverflow { addr: u16 },
+    // Ret with an empty stack
+    StackUnderflow { addr: u16 },
+    // Instruction at pc accessed memory outside of the address space
+    MemoryFault { pc: u16, addr: usize },
+    // pc points outside of the address space
+    PcOutOfRange { pc: u16 },
+    // Register index outside of V0-VF
+    InvalidRegister { reg: u8 },
+    // Program does not fit into memory after PROG_POS_START
+    ProgramTooLarge { size: usize, max: usize },
+}
+
+impl fmt::Display for ChipError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ChipError::IllegalInstruction { addr, opcode } => {
+                write!(f, "illegal instruction {opcode:04X} at {addr:03X}")
+            }
+            ChipError::StackOverflow { addr } => write!(f, "stack overflow at {addr:03X}"),
+            ChipError::StackUnderflow { addr } => write!(f, "stack underflow at {addr:03X}"),
+            ChipError::MemoryFault { pc, addr } => {
+                write!(
+                    f,
+                    "memory fault at {pc:03X}: address {addr:X} is out of bounds"
+                )
+            }
+            ChipError::PcOutOfRange { pc } => write!(f, "program counter out of range: {pc:X}"),
+            ChipError::InvalidRegister { reg } => write!(f, "invalid register v{reg:x}"),
+            ChipError::ProgramTooLarge { size, max } => {
+                write!(f, "program too large: {size} bytes, max {max} bytes")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ChipError {}
+
+// Error in assembler source, line 0 means the whole file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AsmError {
+    pub file: String,
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.line == 0 {
+            write!(f, "{}: {}", self.file, self.message)
+        } else {
+            write!(f, "{}:{}: {}", self.file, self.line, self.message)
+        }
+    }
+}
+
+impl std::error::Error for AsmError {}
@@ -1,4 +1,6 @@
-#[derive(Debug)]
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Inst {
     // Does nothing, for syscall
     Empty,
@@ -47,6 +49,7 @@ pub enum Inst {
     // Moves rnd value (0-255) & byte into vx
     Rnd { vx: u8, byte: u8 },
     // Display n-byte sprite starting at memory location I at (vx, vy), set VF = collision
+    // n = 0 displays a 16x16 sprite (SUPER-CHIP)
     Disp { vx: u8, vy: u8, n: u8 },
     // Skip next instruction if key with the value of vx is pressed
     SKp { vx: u8 },
@@ -70,13 +73,62 @@ pub enum Inst {
     LdIR { vx: u8 },
     // Read registers V0 through Vx from memory starting at location I
     LdRI { vx: u8 },
+
+    // --- SUPER-CHIP 1.1 ---
+
+    // Scroll the display down by n pixels
+    ScrollDown { n: u8 },
+    // Scroll the display right by 4 pixels
+    ScrollRight,
+    // Scroll the display left by 4 pixels
+    ScrollLeft,
+    // Exit the interpreter
+    Exit,
+    // Switch to 64x32 low resolution
+    Low,
+    // Switch to 128x64 high resolution
+    High,
+    // Set I = location of big (8x10) font char for val of vx
+    LdIBigF { vx: u8 },
+    // Store registers v0 through vx in the RPL user flags
+    LdRplR { vx: u8 },
+    // Read registers v0 through vx from the RPL user flags
+    LdRRpl { vx: u8 },
+
+    // --- XO-CHIP ---
+
+    // Scroll the display up by n pixels
+    ScrollUp { n: u8 },
+    // Store registers vx through vy in memory starting at location I, I is unchanged
+    SaveR { vx: u8, vy: u8 },
+    // Read registers vx through vy from memory starting at location I, I is unchanged
+    LoadR { vx: u8, vy: u8 },
+    // Load the 16 bit word following the instruction into I
+    LdIL,
+    // Select the bitplanes n (0-3) for drawing, clearing and scrolling
+    Plane { n: u8 },
+    // Load 16 bytes starting at I into the audio pattern buffer
+    Audio,
+    // Set the audio pitch = vx
+    Pitch { vx: u8 },
 }
 
-pub fn hex_to_inst(val: u16) -> Inst {
-    match val & 0xF000 {
+pub fn hex_to_inst(val: u16) -> Option<Inst> {
+    let inst = match val & 0xF000 {
         0x0000 => match val {
+            0x00C0..=0x00CF => Inst::ScrollDown {
+                n: (val & 0x000F) as u8,
+            },
+            0x00D0..=0x00DF => Inst::ScrollUp {
+                n: (val & 0x000F) as u8,
+            },
             0x00E0 => Inst::Cls,
             0x00EE => Inst::Ret,
+            0x00FB => Inst::ScrollRight,
+            0x00FC => Inst::ScrollLeft,
+            0x00FD => Inst::Exit,
+            0x00FE => Inst::Low,
+            0x00FF => Inst::High,
             _ => Inst::Empty,
         },
         0x1000 => Inst::Jmp { addr: val & 0x0FFF },
@@ -89,9 +141,20 @@ pub fn hex_to_inst(val: u16) -> Inst {
             vx: ((val & 0x0F00) >> 8) as u8,
             byte: (val & 0x00FF) as u8,
         },
-        0x5000 => Inst::SR {
-            vx: ((val & 0x0F00) >> 8) as u8,
-            vy: ((val & 0x00F0) >> 4) as u8,
+        0x5000 => match val & 0xF00F {
+            0x5000 => Inst::SR {
+                vx: ((val & 0x0F00) >> 8) as u8,
+                vy: ((val & 0x00F0) >> 4) as u8,
+            },
+            0x5002 => Inst::SaveR {
+                vx: ((val & 0x0F00) >> 8) as u8,
+                vy: ((val & 0x00F0) >> 4) as u8,
+            },
+            0x5003 => Inst::LoadR {
+                vx: ((val & 0x0F00) >> 8) as u8,
+                vy: ((val & 0x00F0) >> 4) as u8,
+            },
+            _ => return None,
         },
         0x6000 => Inst::LdV {
             vx: ((val & 0x0F00) >> 8) as u8,
@@ -139,7 +202,7 @@ pub fn hex_to_inst(val: u16) -> Inst {
                 vx: ((val & 0x0F00) >> 8) as u8,
                 vy: ((val & 0x00F0) >> 4) as u8,
             },
-            _ => panic!("Illegal instruction {val}"),
+            _ => return None,
         },
         0x9000 => Inst::SnR {
             vx: ((val & 0x0F00) >> 8) as u8,
@@ -163,9 +226,14 @@ pub fn hex_to_inst(val: u16) -> Inst {
             0x00A1 => Inst::SKnp {
                 vx: ((val & 0x0F00) >> 8) as u8,
             },
-            _ => panic!("Illegal instruction {val}"),
+            _ => return None,
         },
         0xF000 => match val & 0x00FF {
+            0x0000 if val == 0xF000 => Inst::LdIL,
+            0x0001 => Inst::Plane {
+                n: ((val & 0x0F00) >> 8) as u8,
+            },
+            0x0002 if val == 0xF002 => Inst::Audio,
             0x0007 => Inst::LdRDt {
                 vx: ((val & 0x0F00) >> 8) as u8,
             },
@@ -184,6 +252,12 @@ pub fn hex_to_inst(val: u16) -> Inst {
             0x0029 => Inst::LdIF {
                 vx: ((val & 0x0F00) >> 8) as u8,
             },
+            0x0030 => Inst::LdIBigF {
+                vx: ((val & 0x0F00) >> 8) as u8,
+            },
+            0x003A => Inst::Pitch {
+                vx: ((val & 0x0F00) >> 8) as u8,
+            },
             0x0033 => Inst::LdBCDR {
                 vx: ((val & 0x0F00) >> 8) as u8,
             },
@@ -193,8 +267,136 @@ pub fn hex_to_inst(val: u16) -> Inst {
             0x0065 => Inst::LdRI {
                 vx: ((val & 0x0F00) >> 8) as u8,
             },
-            _ => panic!("Illegal instruction {val}"),
+            0x0075 => Inst::LdRplR {
+                vx: ((val & 0x0F00) >> 8) as u8,
+            },
+            0x0085 => Inst::LdRRpl {
+                vx: ((val & 0x0F00) >> 8) as u8,
+            },
+            _ => return None,
         },
-        _ => panic!("Illegal instruction {val}"),
+        _ => return None,
+    };
+    Some(inst)
+}
+
+// Inverse of hex_to_inst, LdIL is only the first word, Empty encodes as 0000
+pub fn inst_to_hex(inst: Inst) -> u16 {
+    let x = |vx: u8| (vx as u16 & 0xF) << 8;
+    let xy = |vx: u8, vy: u8| x(vx) | (vy as u16 & 0xF) << 4;
+    let xnn = |vx: u8, byte: u8| x(vx) | byte as u16;
+    match inst {
+        Inst::Empty => 0x0000,
+        Inst::Cls => 0x00E0,
+        Inst::Ret => 0x00EE,
+        Inst::Jmp { addr } => 0x1000 | addr & 0x0FFF,
+        Inst::Call { addr } => 0x2000 | addr & 0x0FFF,
+        Inst::SV { vx, byte } => 0x3000 | xnn(vx, byte),
+        Inst::SnV { vx, byte } => 0x4000 | xnn(vx, byte),
+        Inst::SR { vx, vy } => 0x5000 | xy(vx, vy),
+        Inst::LdV { vx, byte } => 0x6000 | xnn(vx, byte),
+        Inst::AddV { vx, byte } => 0x7000 | xnn(vx, byte),
+        Inst::LdR { vx, vy } => 0x8000 | xy(vx, vy),
+        Inst::OrR { vx, vy } => 0x8001 | xy(vx, vy),
+        Inst::AndR { vx, vy } => 0x8002 | xy(vx, vy),
+        Inst::XorR { vx, vy } => 0x8003 | xy(vx, vy),
+        Inst::AddR { vx, vy } => 0x8004 | xy(vx, vy),
+        Inst::SubR { vx, vy } => 0x8005 | xy(vx, vy),
+        Inst::Shr { vx, vy } => 0x8006 | xy(vx, vy),
+        Inst::SubnR { vx, vy } => 0x8007 | xy(vx, vy),
+        Inst::Shl { vx, vy } => 0x800E | xy(vx, vy),
+        Inst::SnR { vx, vy } => 0x9000 | xy(vx, vy),
+        Inst::LdI { addr } => 0xA000 | addr & 0x0FFF,
+        Inst::JpV0 { addr } => 0xB000 | addr & 0x0FFF,
+        Inst::Rnd { vx, byte } => 0xC000 | xnn(vx, byte),
+        Inst::Disp { vx, vy, n } => 0xD000 | xy(vx, vy) | (n as u16 & 0xF),
+        Inst::SKp { vx } => 0xE09E | x(vx),
+        Inst::SKnp { vx } => 0xE0A1 | x(vx),
+        Inst::LdRDt { vx } => 0xF007 | x(vx),
+        Inst::LdRKp { vx } => 0xF00A | x(vx),
+        Inst::LdDtR { vx } => 0xF015 | x(vx),
+        Inst::LdStR { vx } => 0xF018 | x(vx),
+        Inst::AddRI { vx } => 0xF01E | x(vx),
+        Inst::LdIF { vx } => 0xF029 | x(vx),
+        Inst::LdBCDR { vx } => 0xF033 | x(vx),
+        Inst::LdIR { vx } => 0xF055 | x(vx),
+        Inst::LdRI { vx } => 0xF065 | x(vx),
+        Inst::ScrollDown { n } => 0x00C0 | (n as u16 & 0xF),
+        Inst::ScrollRight => 0x00FB,
+        Inst::ScrollLeft => 0x00FC,
+        Inst::Exit => 0x00FD,
+        Inst::Low => 0x00FE,
+        Inst::High => 0x00FF,
+        Inst::LdIBigF { vx } => 0xF030 | x(vx),
+        Inst::LdRplR { vx } => 0xF075 | x(vx),
+        Inst::LdRRpl { vx } => 0xF085 | x(vx),
+        Inst::ScrollUp { n } => 0x00D0 | (n as u16 & 0xF),
+        Inst::SaveR { vx, vy } => 0x5002 | xy(vx, vy),
+        Inst::LoadR { vx, vy } => 0x5003 | xy(vx, vy),
+        Inst::LdIL => 0xF000,
+        Inst::Plane { n } => 0xF001 | x(n),
+        Inst::Audio => 0xF002,
+        Inst::Pitch { vx } => 0xF03A | x(vx),
+    }
+}
+
+// Conventional mnemonics (Cowgod's reference, extended for SUPER-CHIP and XO-CHIP)
+impl fmt::Display for Inst {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            // hex_to_inst does not keep the address of 0NNN
+            Inst::Empty => write!(f, "SYS"),
+            Inst::Cls => write!(f, "CLS"),
+            Inst::Ret => write!(f, "RET"),
+            Inst::Jmp { addr } => write!(f, "JP 0x{addr:03X}"),
+            Inst::Call { addr } => write!(f, "CALL 0x{addr:03X}"),
+            Inst::SV { vx, byte } => write!(f, "SE V{vx:X}, 0x{byte:02X}"),
+            Inst::SnV { vx, byte } => write!(f, "SNE V{vx:X}, 0x{byte:02X}"),
+            Inst::SR { vx, vy } => write!(f, "SE V{vx:X}, V{vy:X}"),
+            Inst::LdV { vx, byte } => write!(f, "LD V{vx:X}, 0x{byte:02X}"),
+            Inst::AddV { vx, byte } => write!(f, "ADD V{vx:X}, 0x{byte:02X}"),
+            Inst::LdR { vx, vy } => write!(f, "LD V{vx:X}, V{vy:X}"),
+            Inst::OrR { vx, vy } => write!(f, "OR V{vx:X}, V{vy:X}"),
+            Inst::AndR { vx, vy } => write!(f, "AND V{vx:X}, V{vy:X}"),
+            Inst::XorR { vx, vy } => write!(f, "XOR V{vx:X}, V{vy:X}"),
+            Inst::AddR { vx, vy } => write!(f, "ADD V{vx:X}, V{vy:X}"),
+            Inst::SubR { vx, vy } => write!(f, "SUB V{vx:X}, V{vy:X}"),
+            Inst::Shr { vx, vy } => write!(f, "SHR V{vx:X}, V{vy:X}"),
+            Inst::SubnR { vx, vy } => write!(f, "SUBN V{vx:X}, V{vy:X}"),
+            Inst::Shl { vx, vy } => write!(f, "SHL V{vx:X}, V{vy:X}"),
+            Inst::SnR { vx, vy } => write!(f, "SNE V{vx:X}, V{vy:X}"),
+            Inst::LdI { addr } => write!(f, "LD I, 0x{addr:03X}"),
+            Inst::JpV0 { addr } => write!(f, "JP V0, 0x{addr:03X}"),
+            Inst::Rnd { vx, byte } => write!(f, "RND V{vx:X}, 0x{byte:02X}"),
+            Inst::Disp { vx, vy, n } => write!(f, "DRW V{vx:X}, V{vy:X}, {n}"),
+            Inst::SKp { vx } => write!(f, "SKP V{vx:X}"),
+            Inst::SKnp { vx } => write!(f, "SKNP V{vx:X}"),
+            Inst::LdRDt { vx } => write!(f, "LD V{vx:X}, DT"),
+            Inst::LdRKp { vx } => write!(f, "LD V{vx:X}, K"),
+            Inst::LdDtR { vx } => write!(f, "LD DT, V{vx:X}"),
+            Inst::LdStR { vx } => write!(f, "LD ST, V{vx:X}"),
+            Inst::AddRI { vx } => write!(f, "ADD I, V{vx:X}"),
+            Inst::LdIF { vx } => write!(f, "LD F, V{vx:X}"),
+            Inst::LdBCDR { vx } => write!(f, "LD B, V{vx:X}"),
+            Inst::LdIR { vx } => write!(f, "LD [I], V{vx:X}"),
+            Inst::LdRI { vx } => write!(f, "LD V{vx:X}, [I]"),
+            Inst::ScrollDown { n } => write!(f, "SCD {n}"),
+            Inst::ScrollRight => write!(f, "SCR"),
+            Inst::ScrollLeft => write!(f, "SCL"),
+            Inst::Exit => write!(f, "EXIT"),
+            Inst::Low => write!(f, "LOW"),
+            Inst::High => write!(f, "HIGH"),
+            Inst::LdIBigF { vx } => write!(f, "LD HF, V{vx:X}"),
+            Inst::LdRplR { vx } => write!(f, "LD R, V{vx:X}"),
+            Inst::LdRRpl { vx } => write!(f, "LD V{vx:X}, R"),
+            Inst::ScrollUp { n } => write!(f, "SCU {n}"),
+            Inst::SaveR { vx, vy } => write!(f, "SAVE V{vx:X}, V{vy:X}"),
+            Inst::LoadR { vx, vy } => write!(f, "LOAD V{vx:X}, V{vy:X}"),
+            // The address is the word following the instruction
+            Inst::LdIL => write!(f, "LD I, LONG"),
+            Inst::Plane { n } => write!(f, "PLANE {n}"),
+            Inst::Audio => write!(f, "AUDIO"),
+            Inst::Pitch { vx } => write!(f, "PITCH V{vx:X}"),
+        }
     }
 }
@@ -0,0 +1,10 @@
+pub mod asm;
+pub mod chip;
+pub mod config;
+pub mod disasm;
+pub mod error;
+pub mod inst;
+pub mod octo;
+pub mod quirks;
+pub mod rng;
+pub mod screen;
@@ -1,19 +1,24 @@
 #![allow(unused_variables)]
 
-mod chip;
-mod config;
-mod inst;
-mod screen;
+mod cli;
 
 use std::{
-    env,
+    env, fmt,
     fs::{self, File},
     io::Read,
+    net::TcpListener,
+    path::{Path, PathBuf},
+    process,
 };
 
-use chip::Chip;
-use config::*;
-use screen::{tui::Tui, Interface};
+use chip_8::{
+    chip::{Chip, Debugger, GdbStub, Movie, RewindBuffer, ScheduledScreenshot},
+    config::*,
+    octo,
+    rng::{self, RngKind, VIP_INTERPRETER_SIZE},
+    screen::{graphics::Graphics, Interface},
+};
+use cli::{parse_args, USAGE};
 
 fn get_file_in_bytes(filename: &String) -> Vec<u8> {
     let mut file = File::open(filename).expect("file not found: {filename}");
@@ -25,22 +30,145 @@ fn get_file_in_bytes(filename: &String) -> Vec<u8> {
     buffer
 }
 
+fn exit_with_error(err: impl fmt::Display) -> ! {
+    eprintln!("{err}");
+    process::exit(1);
+}
+
 fn main() {
     //Debug
     //env::set_var("RUST_BACKTRACE", "1");
 
     let args: Vec<String> = env::args().collect();
-    if args.len() <= 1 {
-        panic!("missing filepath argument");
+    let args = match parse_args(&args) {
+        Ok(args) => args,
+        Err(err) => {
+            eprintln!("{err}\n\n{USAGE}");
+            process::exit(2);
+        }
     };
 
-    let interface = Tui::new();
+    let mut interface = Graphics::new();
+    interface.tui_mut().set_output_mode(args.render);
+    interface.set_scale(args.graphics_scale);
+    // Queried before the alternate screen is entered, without a protocol characters are drawn
+    if let Some(mode) = args.graphics {
+        interface.set_protocol(mode.protocol());
+    }
+    // Started before init so the alternate screen setup is part of the recording
+    if let Some(path) = &args.cast {
+        if let Err(err) = interface.tui_mut().record_cast(path) {
+            exit_with_error(format!("{}: {err}", path.display()));
+        }
+    }
+    // Octo source is compiled in-process, its :breakpoint directives go to the debugger
+    let (rom, breakpoints) = if args.rom.ends_with(".8o") {
+        let program =
+            octo::compile_file(Path::new(&args.rom)).unwrap_or_else(|err| exit_with_error(err));
+        (program.rom, program.breakpoints)
+    } else {
+        (get_file_in_bytes(&args.rom), Vec::new())
+    };
+
+    let mut chip: Chip<Graphics> = Chip::new(PROG_POS_START, interface, args.quirks);
+    chip.rom_path = Some(PathBuf::from(&args.rom));
+    if let Some(seed) = args.seed {
+        chip.seed = seed;
+    }
+    chip.set_rng_kind(args.rng);
+    if let Some(path) = &args.vip_interpreter {
+        let page = fs::read(path)
+            .map_err(|err| format!("{}: {err}", path.display()))
+            .and_then(|dump| {
+                rng::vip_page(&dump).ok_or_else(|| {
+                    format!(
+                        "{}: not a {VIP_INTERPRETER_SIZE} byte interpreter dump",
+                        path.display()
+                    )
+                })
+            })
+            .unwrap_or_else(|err| exit_with_error(err));
+        chip.set_vip_page(page);
+    }
+    chip.screenshot_scale = args.screenshot_scale;
+    if let Some(frame) = args.screenshot_at {
+        let path = args
+            .screenshot
+            .clone()
+            .or_else(|| chip.frame_path(frame, "png"));
+        chip.scheduled_screenshot = path.map(|path| ScheduledScreenshot::new(frame, path));
+    }
+    chip.clip_scale = args.record_scale;
+    if let Some(path) = &args.record {
+        chip.start_clip(path.clone());
+    }
+
+    if let Some(path) = &args.movie_play {
+        let movie = Movie::load(path).unwrap_or_else(|err| exit_with_error(err));
+        if let Err(err) = movie.check_rom(&rom) {
+            exit_with_error(err);
+        }
+        if movie.rng_kind == RngKind::CosmacVip && args.vip_interpreter.is_none() {
+            exit_with_error("the movie uses the cosmac-vip rng, it needs --vip-interpreter");
+        }
+        chip.start_playback(movie);
+    }
 
-    let mut chip: Chip<Tui> = Chip::new(PROG_POS_START, interface);
+    if let Err(err) = chip.load_prog(rom.clone()) {
+        exit_with_error(err);
+    }
 
-    chip.load_prog(get_file_in_bytes(&args[1]));
+    if args.movie_record.is_some() {
+        chip.start_recording(&rom);
+    }
+
+    if args.debug {
+        chip.debugger = Some(Debugger::new());
+    }
+    chip.debug_view = args.debug_view;
+    if args.rewind {
+        chip.rewind = Some(RewindBuffer::new(REWIND_BUFFER_SIZE));
+    }
+    if let Some(port) = args.gdb_port {
+        let listener =
+            TcpListener::bind(("127.0.0.1", port)).unwrap_or_else(|err| exit_with_error(err));
+        eprintln!("waiting for gdb on 127.0.0.1:{port}");
+        chip.gdb = Some(GdbStub::accept(&listener).unwrap_or_else(|err| exit_with_error(err)));
+        chip.debugger = Some(Debugger::new());
+    }
+    chip.add_breakpoints(&breakpoints);
 
     chip.init_interface();
-    chip.run();
+    let result = chip.run();
     chip.stop_interface();
+
+    if let (Some(path), Err(err)) = (&args.cast, chip.interface.tui_mut().finish_cast()) {
+        exit_with_error(format!("{}: {err}", path.display()));
+    }
+
+    if let (Some(path), Some(movie)) = (&args.movie_record, chip.stop_movie()) {
+        if let Err(err) = movie.save(path) {
+            exit_with_error(err);
+        }
+    }
+
+    // A clip started by --record or the hotkey is still running
+    if let Some(clip) = chip.stop_clip() {
+        if let Err(err) = clip.save(chip.clip_scale) {
+            exit_with_error(format!("{}: {err}", clip.path.display()));
+        }
+    }
+
+    if let Some(ScheduledScreenshot {
+        path,
+        error: Some(err),
+        ..
+    }) = &chip.scheduled_screenshot
+    {
+        exit_with_error(format!("{}: {err}", path.display()));
+    }
+
+    if let Err(err) = result {
+        exit_with_error(err);
+    }
 }
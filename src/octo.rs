@@ -0,0 +1,949 @@
+use std::{collections::HashMap, fs, path::Path};
+
+use crate::{
+    config::PROG_POS_START,
+    error::AsmError,
+    inst::{inst_to_hex, Inst},
+};
+
+// Macro expansions nested deeper than this are most likely recursive
+const MAX_MACRO_DEPTH: usize = 64;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Token {
+    text: String,
+    line: usize,
+    // Macro expansion depth the token came from
+    depth: usize,
+}
+
+// Compiled program with the :breakpoint directives by name
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Program {
+    pub rom: Vec<u8>,
+    pub breakpoints: Vec<(String, u16)>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FixupKind {
+    // Low 12 bits of the instruction at addr
+    Addr12,
+    // The word at addr, for i := long
+    Word,
+}
+
+struct Fixup {
+    addr: u16,
+    label: String,
+    kind: FixupKind,
+    line: usize,
+}
+
+struct Macro {
+    params: Vec<String>,
+    body: Vec<Token>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CondKind {
+    Eq,
+    Ne,
+    Key,
+    NotKey,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Rhs {
+    Reg(u8),
+    Byte(u8),
+}
+
+// Condition after its setup instructions were emitted
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Cond {
+    vx: u8,
+    kind: CondKind,
+    rhs: Rhs,
+}
+
+struct Loop {
+    start: u16,
+    // Jumps emitted by while, they leave the loop
+    breaks: Vec<u16>,
+}
+
+struct Compiler {
+    // Remaining tokens in reverse order, macro expansions are pushed back on top
+    tokens: Vec<Token>,
+    line: usize,
+    rom: Vec<u8>,
+    here: u32,
+    labels: HashMap<String, u16>,
+    constants: HashMap<String, i64>,
+    aliases: HashMap<String, u8>,
+    macros: HashMap<String, Macro>,
+    fixups: Vec<Fixup>,
+    loops: Vec<Loop>,
+    // Jumps of if ... begin and else waiting for their target
+    branches: Vec<u16>,
+    breakpoints: Vec<(String, u16)>,
+}
+
+fn tokenize(source: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    for (index, line) in source.lines().enumerate() {
+        let code = line.split_once('#').map(|(code, _)| code).unwrap_or(line);
+        tokens.extend(code.split_whitespace().map(|text| Token {
+            text: text.to_string(),
+            line: index + 1,
+            depth: 0,
+        }));
+    }
+    tokens.reverse();
+    tokens
+}
+
+// Decimal, 0x hex or 0b binary, optionally negative
+fn parse_number(text: &str) -> Option<i64> {
+    let (negative, digits) = match text.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, text),
+    };
+    let value = if let Some(hex) = digits.strip_prefix("0x") {
+        i64::from_str_radix(hex, 16).ok()?
+    } else if let Some(bin) = digits.strip_prefix("0b") {
+        i64::from_str_radix(bin, 2).ok()?
+    } else {
+        digits.parse().ok()?
+    };
+    Some(if negative { -value } else { value })
+}
+
+impl Compiler {
+    fn new(source: &str) -> Self {
+        Compiler {
+            tokens: tokenize(source),
+            line: 0,
+            rom: Vec::new(),
+            here: PROG_POS_START as u32,
+            labels: HashMap::new(),
+            constants: HashMap::new(),
+            aliases: HashMap::new(),
+            macros: HashMap::new(),
+            fixups: Vec::new(),
+            loops: Vec::new(),
+            branches: Vec::new(),
+            breakpoints: Vec::new(),
+        }
+    }
+
+    fn next_token(&mut self) -> Result<Token, String> {
+        let token = self.tokens.pop().ok_or("unexpected end of source")?;
+        self.line = token.line;
+        Ok(token)
+    }
+
+    fn next(&mut self) -> Result<String, String> {
+        Ok(self.next_token()?.text)
+    }
+
+    fn peek(&self) -> Option<&str> {
+        self.tokens.last().map(|token| token.text.as_str())
+    }
+
+    fn expect(&mut self, text: &str) -> Result<(), String> {
+        match self.next()? {
+            token if token == text => Ok(()),
+            token => Err(format!("expected '{text}', found '{token}'")),
+        }
+    }
+
+    fn emit(&mut self, byte: u8) -> Result<(), String> {
+        if self.here > u16::MAX as u32 {
+            return Err(String::from("program does not fit below 0x10000"));
+        }
+        let offset = (self.here - PROG_POS_START as u32) as usize;
+        if self.rom.len() <= offset {
+            self.rom.resize(offset + 1, 0);
+        }
+        self.rom[offset] = byte;
+        self.here += 1;
+        Ok(())
+    }
+
+    fn emit_word(&mut self, word: u16) -> Result<(), String> {
+        let [high, low] = word.to_be_bytes();
+        self.emit(high)?;
+        self.emit(low)
+    }
+
+    fn emit_inst(&mut self, inst: Inst) -> Result<(), String> {
+        self.emit_word(inst_to_hex(inst))
+    }
+
+    fn here(&self) -> u16 {
+        self.here as u16
+    }
+
+    fn patch(&mut self, addr: u16, value: u16, kind: FixupKind) -> Result<(), String> {
+        let offset = (addr - PROG_POS_START) as usize;
+        let old = u16::from_be_bytes([self.rom[offset], self.rom[offset + 1]]);
+        let word = match kind {
+            FixupKind::Addr12 if value > 0xFFF => {
+                return Err(format!("address 0x{value:X} does not fit in 12 bits"));
+            }
+            FixupKind::Addr12 => old & 0xF000 | value,
+            FixupKind::Word => value,
+        };
+        self.rom[offset..offset + 2].copy_from_slice(&word.to_be_bytes());
+        Ok(())
+    }
+
+    // Emits a jump whose target is patched later
+    fn emit_jump_placeholder(&mut self) -> Result<u16, String> {
+        let addr = self.here();
+        self.emit_inst(Inst::Jmp { addr: 0 })?;
+        Ok(addr)
+    }
+
+    fn define(&mut self, name: &str) -> Result<(), String> {
+        if parse_number(name).is_some() || self.register(name).is_ok() {
+            return Err(format!("invalid name: {name}"));
+        }
+        if self.labels.contains_key(name) || self.constants.contains_key(name) {
+            return Err(format!("name defined twice: {name}"));
+        }
+        Ok(())
+    }
+
+    fn register(&self, text: &str) -> Result<u8, String> {
+        let lower = text.to_ascii_lowercase();
+        match lower.strip_prefix('v').map(|x| u8::from_str_radix(x, 16)) {
+            Some(Ok(x)) if lower.len() == 2 => Ok(x),
+            _ => self
+                .aliases
+                .get(text)
+                .copied()
+                .ok_or_else(|| format!("expected a register, found '{text}'")),
+        }
+    }
+
+    // Numbers and names known at this point
+    fn value(&self, text: &str) -> Result<i64, String> {
+        parse_number(text)
+            .or_else(|| self.constants.get(text).copied())
+            .or_else(|| self.labels.get(text).map(|addr| *addr as i64))
+            .ok_or_else(|| format!("undefined name: {text}"))
+    }
+
+    fn ranged(&self, text: &str, min: i64, max: i64) -> Result<i64, String> {
+        let value = self.value(text)?;
+        if value < min || value > max {
+            return Err(format!("{text} = {value} out of range {min}..={max}"));
+        }
+        Ok(value)
+    }
+
+    // Negative bytes are two's complement
+    fn byte(&self, text: &str) -> Result<u8, String> {
+        Ok(self.ranged(text, -128, 0xFF)? as u8)
+    }
+
+    fn nibble(&self, text: &str) -> Result<u8, String> {
+        Ok(self.ranged(text, 0, 0xF)? as u8)
+    }
+
+    fn next_register(&mut self) -> Result<u8, String> {
+        let text = self.next()?;
+        self.register(&text)
+    }
+
+    fn next_value(&mut self) -> Result<i64, String> {
+        let text = self.next()?;
+        self.value(&text)
+    }
+
+    fn next_byte(&mut self) -> Result<u8, String> {
+        let text = self.next()?;
+        self.byte(&text)
+    }
+
+    fn next_nibble(&mut self) -> Result<u8, String> {
+        let text = self.next()?;
+        self.nibble(&text)
+    }
+
+    // Address operand of the instruction about to be emitted, labels may be defined later
+    fn addr_operand(&mut self, text: &str, kind: FixupKind) -> Result<u16, String> {
+        let max = match kind {
+            FixupKind::Addr12 => 0xFFF,
+            FixupKind::Word => 0xFFFF,
+        };
+        if self.value(text).is_ok() {
+            return self.ranged(text, 0, max).map(|value| value as u16);
+        }
+        let offset = match kind {
+            FixupKind::Addr12 => 0,
+            FixupKind::Word => 2,
+        };
+        self.fixups.push(Fixup {
+            addr: self.here() + offset,
+            label: text.to_string(),
+            kind,
+            line: self.line,
+        });
+        Ok(0)
+    }
+
+    // Tokens up to the matching }, the { has been read
+    fn block(&mut self) -> Result<Vec<Token>, String> {
+        let mut depth = 1;
+        let mut body = Vec::new();
+        loop {
+            let token = self.next_token()?;
+            match token.text.as_str() {
+                "{" => depth += 1,
+                "}" => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return Ok(body);
+                    }
+                }
+                _ => {}
+            }
+            body.push(token);
+        }
+    }
+
+    // :calc expression, operators are evaluated right to left without precedence
+    fn calc(&self, tokens: &[Token], pos: &mut usize) -> Result<i64, String> {
+        let lhs = self.calc_term(tokens, pos)?;
+        let op = match tokens.get(*pos) {
+            Some(token) if token.text != ")" => token.text.as_str(),
+            _ => return Ok(lhs),
+        };
+        *pos += 1;
+        let rhs = self.calc(tokens, pos)?;
+        let value = match op {
+            "+" => lhs.wrapping_add(rhs),
+            "-" => lhs.wrapping_sub(rhs),
+            "*" => lhs.wrapping_mul(rhs),
+            "/" => lhs.checked_div(rhs).ok_or("division by zero")?,
+            "%" => lhs.checked_rem(rhs).ok_or("division by zero")?,
+            "&" => lhs & rhs,
+            "|" => lhs | rhs,
+            "^" => lhs ^ rhs,
+            "<<" => lhs.checked_shl(rhs as u32).unwrap_or(0),
+            ">>" => lhs.checked_shr(rhs as u32).unwrap_or(0),
+            "<" => (lhs < rhs) as i64,
+            ">" => (lhs > rhs) as i64,
+            "<=" => (lhs <= rhs) as i64,
+            ">=" => (lhs >= rhs) as i64,
+            "==" => (lhs == rhs) as i64,
+            "!=" => (lhs != rhs) as i64,
+            "min" => lhs.min(rhs),
+            "max" => lhs.max(rhs),
+            _ => return Err(format!("unknown operator in :calc: {op}")),
+        };
+        Ok(value)
+    }
+
+    fn calc_term(&self, tokens: &[Token], pos: &mut usize) -> Result<i64, String> {
+        let token = tokens.get(*pos).ok_or("unexpected end of :calc")?;
+        *pos += 1;
+        match token.text.as_str() {
+            "(" => {
+                let value = self.calc(tokens, pos)?;
+                match tokens.get(*pos) {
+                    Some(token) if token.text == ")" => {
+                        *pos += 1;
+                        Ok(value)
+                    }
+                    _ => Err(String::from("missing ) in :calc")),
+                }
+            }
+            "-" => Ok(self.calc_term(tokens, pos)?.wrapping_neg()),
+            "~" => Ok(!self.calc_term(tokens, pos)?),
+            "!" => Ok((self.calc_term(tokens, pos)? == 0) as i64),
+            "HERE" => Ok(self.here as i64),
+            text => self.value(text),
+        }
+    }
+
+    fn calc_block(&mut self) -> Result<i64, String> {
+        self.expect("{")?;
+        let body = self.block()?;
+        let mut pos = 0;
+        let value = self.calc(&body, &mut pos)?;
+        match body.get(pos) {
+            None => Ok(value),
+            Some(token) => Err(format!("unexpected '{}' in :calc", token.text)),
+        }
+    }
+
+    // Reads "vx <op> <rhs>" and emits the setup for comparisons other than == and !=
+    fn condition(&mut self) -> Result<Cond, String> {
+        let vx = self.next_register()?;
+        let op = self.next()?;
+        let kind = match op.as_str() {
+            "key" => CondKind::Key,
+            "-key" => CondKind::NotKey,
+            _ => CondKind::Eq,
+        };
+        if kind != CondKind::Eq {
+            return Ok(Cond {
+                vx,
+                kind,
+                rhs: Rhs::Byte(0),
+            });
+        }
+        let text = self.next()?;
+        let rhs = match self.register(&text) {
+            Ok(vy) => Rhs::Reg(vy),
+            Err(_) => Rhs::Byte(self.byte(&text)?),
+        };
+        let (setup, flag) = match op.as_str() {
+            "==" => return Ok(Cond { vx, kind, rhs }),
+            "!=" => {
+                return Ok(Cond {
+                    vx,
+                    kind: CondKind::Ne,
+                    rhs,
+                })
+            }
+            // vf := rhs, vf =- vx sets vf to 1 if vx >= rhs
+            "<" => (Inst::SubnR { vx: 0xF, vy: vx }, 0),
+            ">=" => (Inst::SubnR { vx: 0xF, vy: vx }, 1),
+            // vf := rhs, vf -= vx sets vf to 1 if rhs >= vx
+            ">" => (Inst::SubR { vx: 0xF, vy: vx }, 0),
+            "<=" => (Inst::SubR { vx: 0xF, vy: vx }, 1),
+            _ => return Err(format!("unknown comparison: {op}")),
+        };
+        match rhs {
+            Rhs::Reg(vy) => self.emit_inst(Inst::LdR { vx: 0xF, vy })?,
+            Rhs::Byte(byte) => self.emit_inst(Inst::LdV { vx: 0xF, byte })?,
+        }
+        self.emit_inst(setup)?;
+        Ok(Cond {
+            vx: 0xF,
+            kind: CondKind::Eq,
+            rhs: Rhs::Byte(flag),
+        })
+    }
+
+    // Emits a skip over the next instruction, skipping when the condition is true
+    // if skip_when is set and when it is false otherwise
+    fn emit_skip(&mut self, cond: Cond, skip_when: bool) -> Result<(), String> {
+        let Cond { vx, kind, rhs } = cond;
+        let skip_if_equal = match kind {
+            CondKind::Eq => skip_when,
+            CondKind::Ne => !skip_when,
+            CondKind::Key => {
+                return self.emit_inst(if skip_when {
+                    Inst::SKp { vx }
+                } else {
+                    Inst::SKnp { vx }
+                })
+            }
+            CondKind::NotKey => {
+                return self.emit_inst(if skip_when {
+                    Inst::SKnp { vx }
+                } else {
+                    Inst::SKp { vx }
+                })
+            }
+        };
+        let inst = match (rhs, skip_if_equal) {
+            (Rhs::Reg(vy), true) => Inst::SR { vx, vy },
+            (Rhs::Reg(vy), false) => Inst::SnR { vx, vy },
+            (Rhs::Byte(byte), true) => Inst::SV { vx, byte },
+            (Rhs::Byte(byte), false) => Inst::SnV { vx, byte },
+        };
+        self.emit_inst(inst)
+    }
+
+    // vx := ..., vx += ... and the other register assignments
+    fn assign(&mut self, vx: u8) -> Result<(), String> {
+        let op = self.next()?;
+        let text = self.next()?;
+        let reg = self.register(&text).ok();
+        let inst = match (op.as_str(), text.as_str(), reg) {
+            (":=", "key", _) => Inst::LdRKp { vx },
+            (":=", "delay", _) => Inst::LdRDt { vx },
+            (":=", "random", _) => {
+                let byte = self.next_byte()?;
+                Inst::Rnd { vx, byte }
+            }
+            (":=", _, Some(vy)) => Inst::LdR { vx, vy },
+            (":=", _, None) => Inst::LdV {
+                vx,
+                byte: self.byte(&text)?,
+            },
+            ("+=", _, Some(vy)) => Inst::AddR { vx, vy },
+            ("+=", _, None) => Inst::AddV {
+                vx,
+                byte: self.byte(&text)?,
+            },
+            ("-=", _, Some(vy)) => Inst::SubR { vx, vy },
+            ("-=", _, None) => Inst::AddV {
+                vx,
+                byte: self.byte(&text)?.wrapping_neg(),
+            },
+            ("=-", _, Some(vy)) => Inst::SubnR { vx, vy },
+            ("|=", _, Some(vy)) => Inst::OrR { vx, vy },
+            ("&=", _, Some(vy)) => Inst::AndR { vx, vy },
+            ("^=", _, Some(vy)) => Inst::XorR { vx, vy },
+            (">>=", _, Some(vy)) => Inst::Shr { vx, vy },
+            ("<<=", _, Some(vy)) => Inst::Shl { vx, vy },
+            _ => return Err(format!("invalid assignment: v{vx:x} {op} {text}")),
+        };
+        self.emit_inst(inst)
+    }
+
+    // i := ... and i += vx
+    fn assign_i(&mut self) -> Result<(), String> {
+        let op = self.next()?;
+        let text = self.next()?;
+        let inst = match (op.as_str(), text.as_str()) {
+            (":=", "hex") => Inst::LdIF {
+                vx: self.next_register()?,
+            },
+            (":=", "bighex") => Inst::LdIBigF {
+                vx: self.next_register()?,
+            },
+            (":=", "long") => {
+                let target = self.next()?;
+                let addr = self.addr_operand(&target, FixupKind::Word)?;
+                self.emit_inst(Inst::LdIL)?;
+                return self.emit_word(addr);
+            }
+            (":=", _) => Inst::LdI {
+                addr: self.addr_operand(&text, FixupKind::Addr12)?,
+            },
+            ("+=", _) => Inst::AddRI {
+                vx: self.register(&text)?,
+            },
+            _ => return Err(format!("invalid assignment: i {op} {text}")),
+        };
+        self.emit_inst(inst)
+    }
+
+    // save/load vx or save/load vx - vy
+    fn save_load(&mut self, save: bool) -> Result<(), String> {
+        let vx = self.next_register()?;
+        let inst = if self.peek() == Some("-") {
+            self.next()?;
+            let vy = self.next_register()?;
+            if save {
+                Inst::SaveR { vx, vy }
+            } else {
+                Inst::LoadR { vx, vy }
+            }
+        } else if save {
+            Inst::LdIR { vx }
+        } else {
+            Inst::LdRI { vx }
+        };
+        self.emit_inst(inst)
+    }
+
+    fn directive(&mut self, word: &str) -> Result<(), String> {
+        match word {
+            ":" => {
+                let name = self.next()?;
+                self.define(&name)?;
+                self.labels.insert(name, self.here());
+            }
+            ":alias" => {
+                let name = self.next()?;
+                let reg = self.next_register()?;
+                self.aliases.insert(name, reg);
+            }
+            ":const" => {
+                let name = self.next()?;
+                self.define(&name)?;
+                let value = self.next_value()?;
+                self.constants.insert(name, value);
+            }
+            ":calc" => {
+                let name = self.next()?;
+                self.define(&name)?;
+                let value = self.calc_block()?;
+                self.constants.insert(name, value);
+            }
+            ":macro" => {
+                let name = self.next()?;
+                let mut params = Vec::new();
+                loop {
+                    match self.next()? {
+                        brace if brace == "{" => break,
+                        param => params.push(param),
+                    }
+                }
+                let body = self.block()?;
+                self.macros.insert(name, Macro { params, body });
+            }
+            ":byte" => {
+                let byte = if self.peek() == Some("{") {
+                    let value = self.calc_block()?;
+                    if !(-128..=0xFF).contains(&value) {
+                        return Err(format!("{value} out of range for :byte"));
+                    }
+                    value as u8
+                } else {
+                    self.next_byte()?
+                };
+                self.emit(byte)?;
+            }
+            ":org" => {
+                let text = self.next()?;
+                let addr = self.ranged(&text, PROG_POS_START as i64, 0xFFFF)?;
+                self.here = addr as u32;
+            }
+            ":call" => {
+                let target = self.next()?;
+                let addr = self.addr_operand(&target, FixupKind::Addr12)?;
+                self.emit_inst(Inst::Call { addr })?;
+            }
+            ":breakpoint" => {
+                let name = self.next()?;
+                self.breakpoints.push((name, self.here()));
+            }
+            _ => return Err(format!("unsupported directive: {word}")),
+        }
+        Ok(())
+    }
+
+    fn statement(&mut self) -> Result<(), String> {
+        let token = self.next_token()?;
+        let word = token.text.as_str();
+        if word.starts_with(':') {
+            return self.directive(word);
+        }
+        match word {
+            ";" | "return" => self.emit_inst(Inst::Ret)?,
+            "clear" => self.emit_inst(Inst::Cls)?,
+            "hires" => self.emit_inst(Inst::High)?,
+            "lores" => self.emit_inst(Inst::Low)?,
+            "exit" => self.emit_inst(Inst::Exit)?,
+            "scroll-left" => self.emit_inst(Inst::ScrollLeft)?,
+            "scroll-right" => self.emit_inst(Inst::ScrollRight)?,
+            "audio" => self.emit_inst(Inst::Audio)?,
+            "scroll-down" => {
+                let n = self.next_nibble()?;
+                self.emit_inst(Inst::ScrollDown { n })?;
+            }
+            "scroll-up" => {
+                let n = self.next_nibble()?;
+                self.emit_inst(Inst::ScrollUp { n })?;
+            }
+            "plane" => {
+                let n = self.next_nibble()?;
+                self.emit_inst(Inst::Plane { n })?;
+            }
+            "bcd" => {
+                let vx = self.next_register()?;
+                self.emit_inst(Inst::LdBCDR { vx })?;
+            }
+            "saveflags" => {
+                let vx = self.next_register()?;
+                self.emit_inst(Inst::LdRplR { vx })?;
+            }
+            "loadflags" => {
+                let vx = self.next_register()?;
+                self.emit_inst(Inst::LdRRpl { vx })?;
+            }
+            "save" => self.save_load(true)?,
+            "load" => self.save_load(false)?,
+            "sprite" => {
+                let vx = self.next_register()?;
+                let vy = self.next_register()?;
+                let n = self.next_nibble()?;
+                self.emit_inst(Inst::Disp { vx, vy, n })?;
+            }
+            "jump" => {
+                let target = self.next()?;
+                let addr = self.addr_operand(&target, FixupKind::Addr12)?;
+                self.emit_inst(Inst::Jmp { addr })?;
+            }
+            "jump0" => {
+                let target = self.next()?;
+                let addr = self.addr_operand(&target, FixupKind::Addr12)?;
+                self.emit_inst(Inst::JpV0 { addr })?;
+            }
+            "delay" | "buzzer" | "pitch" => {
+                self.expect(":=")?;
+                let vx = self.next_register()?;
+                self.emit_inst(match word {
+                    "delay" => Inst::LdDtR { vx },
+                    "buzzer" => Inst::LdStR { vx },
+                    _ => Inst::Pitch { vx },
+                })?;
+            }
+            "i" => self.assign_i()?,
+            "if" => {
+                let cond = self.condition()?;
+                match self.next()?.as_str() {
+                    "then" => self.emit_skip(cond, false)?,
+                    "begin" => {
+                        self.emit_skip(cond, true)?;
+                        let jump = self.emit_jump_placeholder()?;
+                        self.branches.push(jump);
+                    }
+                    other => return Err(format!("expected then or begin, found '{other}'")),
+                }
+            }
+            "else" => {
+                let jump = self.branches.pop().ok_or("else without if ... begin")?;
+                let end = self.emit_jump_placeholder()?;
+                self.patch(jump, self.here(), FixupKind::Addr12)?;
+                self.branches.push(end);
+            }
+            "end" => {
+                let jump = self.branches.pop().ok_or("end without if ... begin")?;
+                self.patch(jump, self.here(), FixupKind::Addr12)?;
+            }
+            "loop" => self.loops.push(Loop {
+                start: self.here(),
+                breaks: Vec::new(),
+            }),
+            "while" => {
+                let cond = self.condition()?;
+                self.emit_skip(cond, true)?;
+                let jump = self.emit_jump_placeholder()?;
+                self.loops
+                    .last_mut()
+                    .ok_or("while outside of loop ... again")?
+                    .breaks
+                    .push(jump);
+            }
+            "again" => {
+                let frame = self.loops.pop().ok_or("again without loop")?;
+                self.emit_inst(Inst::Jmp { addr: frame.start })?;
+                for jump in frame.breaks {
+                    self.patch(jump, self.here(), FixupKind::Addr12)?;
+                }
+            }
+            _ if parse_number(word).is_some() => {
+                let byte = self.byte(word)?;
+                self.emit(byte)?;
+            }
+            _ if self.register(word).is_ok() => self.assign(self.register(word)?)?,
+            _ if self.macros.contains_key(word) => {
+                if token.depth >= MAX_MACRO_DEPTH {
+                    return Err(format!("macro {word} expands recursively"));
+                }
+                let count = self.macros[word].params.len();
+                let args = (0..count)
+                    .map(|_| self.next())
+                    .collect::<Result<Vec<_>, _>>()?;
+                let mac = &self.macros[word];
+                let expanded: Vec<Token> = mac
+                    .body
+                    .iter()
+                    .map(|body| {
+                        let text = match mac.params.iter().position(|p| *p == body.text) {
+                            Some(index) => args[index].clone(),
+                            None => body.text.clone(),
+                        };
+                        Token {
+                            text,
+                            line: token.line,
+                            depth: token.depth + 1,
+                        }
+                    })
+                    .collect();
+                self.tokens.extend(expanded.into_iter().rev());
+            }
+            // Any other name calls the label
+            _ => {
+                let addr = self.addr_operand(word, FixupKind::Addr12)?;
+                self.emit_inst(Inst::Call { addr })?;
+            }
+        }
+        Ok(())
+    }
+
+    fn compile(mut self, file: &str) -> Result<Program, AsmError> {
+        let err = |line: usize, message: String| AsmError {
+            file: file.to_string(),
+            line,
+            message,
+        };
+
+        // Execution starts at 0x200, programs that do not start with main jump there
+        let starts_with_main = self
+            .tokens
+            .iter()
+            .rev()
+            .take(2)
+            .map(|t| t.text.as_str())
+            .eq([":", "main"]);
+        if !starts_with_main {
+            self.emit_inst(Inst::Jmp { addr: 0 })
+                .map_err(|message| err(0, message))?;
+            self.fixups.push(Fixup {
+                addr: PROG_POS_START,
+                label: String::from("main"),
+                kind: FixupKind::Addr12,
+                line: 1,
+            });
+        }
+
+        while !self.tokens.is_empty() {
+            self.statement()
+                .map_err(|message| err(self.line, message))?;
+        }
+        if !self.branches.is_empty() {
+            return Err(err(self.line, String::from("if ... begin without end")));
+        }
+        if !self.loops.is_empty() {
+            return Err(err(self.line, String::from("loop without again")));
+        }
+
+        for fixup in std::mem::take(&mut self.fixups) {
+            let addr = *self
+                .labels
+                .get(&fixup.label)
+                .ok_or_else(|| err(fixup.line, format!("undefined name: {}", fixup.label)))?;
+            self.patch(fixup.addr, addr, fixup.kind)
+                .map_err(|message| err(fixup.line, message))?;
+        }
+        Ok(Program {
+            rom: self.rom,
+            breakpoints: self.breakpoints,
+        })
+    }
+}
+
+// Compiles Octo source, the program is loaded at PROG_POS_START
+pub fn compile(source: &str) -> Result<Program, AsmError> {
+    Compiler::new(source).compile("<source>")
+}
+
+pub fn compile_file(path: &Path) -> Result<Program, AsmError> {
+    let source = fs::read_to_string(path).map_err(|e| AsmError {
+        file: path.display().to_string(),
+        line: 0,
+        message: e.to_string(),
+    })?;
+    Compiler::new(&source).compile(&path.display().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compiles_control_flow_macros_and_breakpoints() {
+        let source = "
+            :alias x v1
+            :const SPEED 2
+            :calc START { SPEED * 2 + 1 }  # right to left, 2 * 3
+            :macro step reg { reg += SPEED }
+
+            : draw
+                i := glyph
+                sprite v0 x 5
+            ;
+
+            : main
+                x := START
+                loop
+                    step x
+                    while x != 0x10
+                    if x > 4 begin
+                        draw
+                    else
+                        v0 := 1
+                    end
+                again
+                :breakpoint done
+                if v2 key then jump main
+            : glyph
+                0xF0 0x90 :byte { 0xF0 - 1 }
+        ";
+        let program = compile(source).unwrap();
+        assert_eq!(
+            program.rom,
+            [
+                0x12, 0x08, // jump main
+                0xA2, 0x24, // draw: i := glyph
+                0xD0, 0x15, // sprite v0 x 5
+                0x00, 0xEE, // ;
+                0x61, 0x06, // main: x := START
+                0x71, 0x02, // loop: step x
+                0x41, 0x10, // while x != 0x10
+                0x12, 0x20, // leaves the loop
+                0x6F, 0x04, // if x > 4 begin: vf := 4
+                0x8F, 0x15, // vf -= x
+                0x3F, 0x00, // skips the jump to else if x > 4
+                0x12, 0x1C, // jump else
+                0x22, 0x02, // draw
+                0x12, 0x1E, // else: jump end
+                0x60, 0x01, // v0 := 1
+                0x12, 0x0A, // end: again
+                0xE2, 0xA1, // done: if v2 key then
+                0x12, 0x08, // jump main
+                0xF0, 0x90, 0xEF, // glyph
+            ]
+        );
+        assert_eq!(program.breakpoints, [(String::from("done"), 0x220)]);
+    }
+
+    fn error(source: &str) -> (usize, String) {
+        let err = compile(source).unwrap_err();
+        (err.line, err.message)
+    }
+
+    #[test]
+    fn reports_errors_with_their_line() {
+        let cases = [
+            (": main\n jump nowhere", 2, "undefined name: nowhere"),
+            (": main\n else", 2, "else without if ... begin"),
+            (": main\n end", 2, "end without if ... begin"),
+            (
+                ": main\n if v0 == 1 begin\n v1 := 2",
+                3,
+                "if ... begin without end",
+            ),
+            (": main\n loop\n v0 += 1", 3, "loop without again"),
+            (": main\n again", 2, "again without loop"),
+            (
+                ":macro boom { boom }\n: main\n boom",
+                3,
+                "macro boom expands recursively",
+            ),
+            (
+                ": main\n jump far\n:org 0x1000\n: far",
+                2,
+                "address 0x1000 does not fit in 12 bits",
+            ),
+        ];
+        for (source, line, message) in cases {
+            assert_eq!(error(source), (line, String::from(message)), "{source}");
+        }
+    }
+
+    #[test]
+    fn breakpoints_reach_the_debugger() {
+        use crate::{
+            chip::{Chip, Debugger},
+            quirks::Quirks,
+            screen::{headless::Headless, Interface},
+        };
+
+        let program = compile(": main\n v0 := 1\n :breakpoint here\n v0 := 2").unwrap();
+        let mut chip = Chip::new(PROG_POS_START, Headless::new(), Quirks::default());
+        chip.add_breakpoints(&program.breakpoints);
+        let debugger = chip.debugger.as_mut().unwrap();
+        assert_eq!(debugger.breakpoints().collect::<Vec<_>>(), [0x202]);
+        assert!(!debugger.should_break(0x200, 0));
+        assert!(debugger.should_break(0x202, 0));
+
+        // An existing debugger is kept
+        chip.debugger = Some(Debugger::new());
+        chip.add_breakpoints(&program.breakpoints);
+        assert!(chip.debugger.as_mut().unwrap().should_break(0x200, 0));
+    }
+}
@@ -0,0 +1,162 @@
+use std::str::FromStr;
+
+// Behaviour of opcodes that differ between CHIP-8 implementations
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Quirks {
+    // Shr/Shl shift vy and store the result in vx, otherwise vx is shifted in place
+    pub shift_vy: bool,
+    // How far LdIR/LdRI move I
+    pub load_store: LoadStore,
+    // OrR/AndR/XorR reset VF to 0
+    pub vf_reset: bool,
+    // JpV0 (BXNN) jumps to XNN + vx instead of NNN + v0
+    pub jump_vx: bool,
+    // Sprites are clipped at the screen edges instead of wrapping around
+    pub clip: bool,
+    // Disp waits for the next frame, limiting drawing to one sprite per frame
+    pub display_wait: bool,
+    // XO-CHIP machine: 64K memory, long I loads, register ranges, bitplanes and audio
+    pub xo_chip: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LoadStore {
+    // I is left unchanged (SUPER-CHIP)
+    KeepI,
+    // I += x, one short of the last register (CHIP-48)
+    IncrementX,
+    // I += x + 1, pointing past the last register (COSMAC VIP, XO-CHIP)
+    IncrementXPlusOne,
+}
+
+impl LoadStore {
+    // Amount added to I after registers v0-vx were stored or loaded
+    pub fn increment(self, vx: u8) -> u16 {
+        match self {
+            LoadStore::KeepI => 0,
+            LoadStore::IncrementX => vx as u16,
+            LoadStore::IncrementXPlusOne => vx as u16 + 1,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Preset {
+    CosmacVip,
+    Chip48,
+    SuperChip,
+    XoChip,
+}
+
+impl Quirks {
+    pub fn from_preset(preset: Preset) -> Self {
+        match preset {
+            Preset::CosmacVip => Quirks {
+                shift_vy: true,
+                load_store: LoadStore::IncrementXPlusOne,
+                vf_reset: true,
+                jump_vx: false,
+                clip: true,
+                display_wait: true,
+                xo_chip: false,
+            },
+            Preset::Chip48 => Quirks {
+                shift_vy: false,
+                load_store: LoadStore::IncrementX,
+                vf_reset: false,
+                jump_vx: true,
+                clip: true,
+                display_wait: false,
+                xo_chip: false,
+            },
+            Preset::SuperChip => Quirks {
+                shift_vy: false,
+                load_store: LoadStore::KeepI,
+                vf_reset: false,
+                jump_vx: true,
+                clip: true,
+                display_wait: false,
+                xo_chip: false,
+            },
+            Preset::XoChip => Quirks {
+                shift_vy: true,
+                load_store: LoadStore::IncrementXPlusOne,
+                vf_reset: false,
+                jump_vx: false,
+                clip: false,
+                display_wait: false,
+                xo_chip: true,
+            },
+        }
+    }
+}
+
+impl Quirks {
+    // One byte per quirk, used by save states and movies. Flags are 0 or 1, load_store is
+    // 0 (keep I), 1 (x + 1, as when it was a flag) or 2 (x)
+    pub fn to_bytes(self) -> [u8; 7] {
+        let load_store = match self.load_store {
+            LoadStore::KeepI => 0,
+            LoadStore::IncrementXPlusOne => 1,
+            LoadStore::IncrementX => 2,
+        };
+        [
+            self.shift_vy as u8,
+            load_store,
+            self.vf_reset as u8,
+            self.jump_vx as u8,
+            self.clip as u8,
+            self.display_wait as u8,
+            self.xo_chip as u8,
+        ]
+    }
+
+    // None if data is not 7 bytes or holds a value out of range
+    pub fn from_bytes(data: &[u8]) -> Option<Self> {
+        if data.len() != 7 {
+            return None;
+        }
+        let flag = |byte: u8| match byte {
+            0 => Some(false),
+            1 => Some(true),
+            _ => None,
+        };
+        let load_store = match data[1] {
+            0 => LoadStore::KeepI,
+            1 => LoadStore::IncrementXPlusOne,
+            2 => LoadStore::IncrementX,
+            _ => return None,
+        };
+        Some(Quirks {
+            shift_vy: flag(data[0])?,
+            load_store,
+            vf_reset: flag(data[2])?,
+            jump_vx: flag(data[3])?,
+            clip: flag(data[4])?,
+            display_wait: flag(data[5])?,
+            xo_chip: flag(data[6])?,
+        })
+    }
+}
+
+impl Default for Quirks {
+    fn default() -> Self {
+        Quirks::from_preset(Preset::CosmacVip)
+    }
+}
+
+impl FromStr for Preset {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "cosmac-vip" | "vip" | "chip-8" => Ok(Preset::CosmacVip),
+            "chip-48" => Ok(Preset::Chip48),
+            "super-chip" | "schip" => Ok(Preset::SuperChip),
+            "xo-chip" => Ok(Preset::XoChip),
+            _ => Err(format!(
+                "unknown quirks preset: {s} (expected cosmac-vip, chip-48, super-chip or xo-chip)"
+            )),
+        }
+    }
+}
@@ -0,0 +1,176 @@
+use std::str::FromStr;
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+// Source of the random bytes used by Rnd
+pub trait RandomSource {
+    fn next_byte(&mut self) -> u8;
+    // Called once per frame, from the 60 Hz timer interrupt
+    fn tick(&mut self) {}
+    fn reseed(&mut self, seed: u64);
+}
+
+// Size of a COSMAC VIP CHIP-8 interpreter dump, 0x0000-0x01FF
+pub const VIP_INTERPRETER_SIZE: usize = 512;
+
+// Page 0x0100-0x01FF of a VIP interpreter dump, the bytes CosmacVipRng adds to its seed
+pub fn vip_page(interpreter: &[u8]) -> Option<[u8; 256]> {
+    if interpreter.len() != VIP_INTERPRETER_SIZE {
+        return None;
+    }
+    interpreter[0x100..].try_into().ok()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RngKind {
+    Std,
+    CosmacVip,
+}
+
+impl RngKind {
+    // vip_page is only used by CosmacVip, see vip_page
+    pub fn create(self, seed: u64, vip_page: &[u8; 256]) -> Box<dyn RandomSource> {
+        match self {
+            RngKind::Std => Box::new(SeededRng::new(seed)),
+            RngKind::CosmacVip => Box::new(CosmacVipRng::new(seed, *vip_page)),
+        }
+    }
+
+    pub fn to_byte(self) -> u8 {
+        match self {
+            RngKind::Std => 0,
+            RngKind::CosmacVip => 1,
+        }
+    }
+
+    pub fn from_byte(byte: u8) -> Option<RngKind> {
+        match byte {
+            0 => Some(RngKind::Std),
+            1 => Some(RngKind::CosmacVip),
+            _ => None,
+        }
+    }
+}
+
+impl FromStr for RngKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "std" => Ok(RngKind::Std),
+            "cosmac-vip" | "vip" => Ok(RngKind::CosmacVip),
+            _ => Err(format!("unknown rng: {s} (expected std or cosmac-vip)")),
+        }
+    }
+}
+
+pub struct SeededRng {
+    rng: StdRng,
+}
+
+impl SeededRng {
+    pub fn new(seed: u64) -> Self {
+        SeededRng {
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+}
+
+impl RandomSource for SeededRng {
+    fn next_byte(&mut self) -> u8 {
+        self.rng.gen()
+    }
+
+    fn reseed(&mut self, seed: u64) {
+        self.rng = StdRng::seed_from_u64(seed);
+    }
+}
+
+// Modelled on the COSMAC VIP interpreter, which keeps a 16 bit seed in R9 that the timer
+// interrupt increments. CXNN increments it again and adds the byte at 0x0100 + R9.0, in the
+// interpreter's own code page, to R9.1. The sum shifted right with the carry on top is added
+// to it once more, that is the random byte and the new R9.1.
+// The interpreter is RCA's and not bundled, the page has to come from a dump, see vip_page.
+pub struct CosmacVipRng {
+    r9: u16,
+    page: [u8; 256],
+}
+
+impl CosmacVipRng {
+    pub fn new(seed: u64, page: [u8; 256]) -> Self {
+        CosmacVipRng {
+            r9: seed as u16,
+            page,
+        }
+    }
+}
+
+impl RandomSource for CosmacVipRng {
+    fn next_byte(&mut self) -> u8 {
+        self.r9 = self.r9.wrapping_add(1);
+        let [high, low] = self.r9.to_be_bytes();
+        let (sum, carry) = high.overflowing_add(self.page[low as usize]);
+        // SHRC shifts the carry of the add into the top bit
+        let shifted = sum >> 1 | (carry as u8) << 7;
+        let val = sum.wrapping_add(shifted);
+        self.r9 = u16::from_be_bytes([val, low]);
+        val
+    }
+
+    fn tick(&mut self) {
+        self.r9 = self.r9.wrapping_add(1);
+    }
+
+    fn reseed(&mut self, seed: u64) {
+        self.r9 = seed as u16;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bytes(rng: &mut dyn RandomSource, count: usize) -> Vec<u8> {
+        (0..count).map(|_| rng.next_byte()).collect()
+    }
+
+    #[test]
+    fn seeded_rng_is_deterministic() {
+        let mut rng = SeededRng::new(1234);
+        let first = bytes(&mut rng, 8);
+        assert_eq!(first, [0x99, 0x91, 0xEE, 0x15, 0x23, 0x7B, 0x1B, 0x58]);
+        rng.reseed(1234);
+        assert_eq!(bytes(&mut rng, 8), first);
+        assert_ne!(bytes(&mut SeededRng::new(1235), 8), first);
+    }
+
+    #[test]
+    fn cosmac_vip_rng_follows_the_interpreter() {
+        // R9 = 0x1235: 0x12 + 0x00 = 0x12, plus 0x12 >> 1 gives 0x1B, and so on
+        let mut rng = CosmacVipRng::new(0x1234, [0; 256]);
+        assert_eq!(bytes(&mut rng, 4), [0x1B, 0x28, 0x3C, 0x5A]);
+        // The timer interrupt moves R9.0 on by one
+        rng.tick();
+        assert_eq!(bytes(&mut rng, 1), [0x87]);
+
+        // 0x1B + 0xF0 carries, the carry becomes the top bit of the shifted sum
+        let mut page = [0; 256];
+        page[0x36] = 0xF0;
+        let mut rng = CosmacVipRng::new(0x1234, page);
+        assert_eq!(bytes(&mut rng, 2), [0x1B, 0x90]);
+        rng.reseed(0x1234);
+        assert_eq!(bytes(&mut rng, 2), [0x1B, 0x90]);
+    }
+
+    #[test]
+    fn vip_page_is_the_second_page_of_the_interpreter() {
+        let mut interpreter = [0; VIP_INTERPRETER_SIZE];
+        interpreter[0x0FF] = 1;
+        interpreter[0x100] = 2;
+        interpreter[0x1FF] = 3;
+        let page = vip_page(&interpreter).unwrap();
+        assert_eq!((page[0], page[255]), (2, 3));
+        assert_eq!(vip_page(&interpreter[..VIP_INTERPRETER_SIZE - 1]), None);
+        assert_eq!(vip_page(&[0; 4096]), None);
+    }
+}
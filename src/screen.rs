@@ -1,15 +1,79 @@
+use std::io::{self, Write};
+
 use crate::config::*;
 
+pub mod animation;
+pub mod cast;
+pub mod framebuffer;
+pub mod graphics;
+pub mod headless;
+pub mod image;
 pub mod tui;
 
+use framebuffer::Framebuffer;
+
+// Emulator functions bound to keys outside of the keypad
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Hotkey {
+    SaveState(u8),
+    LoadState(u8),
+    // Reported every frame while the key is held
+    Rewind,
+    // Breaks into the debugger
+    Pause,
+    Screenshot,
+    // Starts or stops a gameplay clip
+    Record,
+}
+
+// Titled block of text shown next to the game screen, the highlighted line is drawn inverted
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SidePanel {
+    pub title: String,
+    pub lines: Vec<String>,
+    pub highlight: Option<usize>,
+}
+
+// Prints output and a debugger prompt to stdout and reads a line from stdin, None once stdin is closed
+pub fn prompt_stdin(output: &str) -> Option<String> {
+    if !output.is_empty() {
+        println!("{output}");
+    }
+    print!("(chip8) ");
+    io::stdout().flush().ok()?;
+    let mut line = String::new();
+    match io::stdin().read_line(&mut line) {
+        Ok(0) | Err(_) => None,
+        Ok(_) => Some(line),
+    }
+}
+
 pub trait Interface {
     fn new() -> Self;
-    fn draw_sprite(&mut self, x: u8, y: u8, sprite: Vec<u8>) -> bool;
+    fn framebuffer(&self) -> &Framebuffer;
+    fn framebuffer_mut(&mut self) -> &mut Framebuffer;
+    fn draw_sprite(&mut self, x: u8, y: u8, sprite: Vec<u8>, width: u8, clip: bool) -> bool;
     fn update_screen(&mut self);
     fn clear_screen(&mut self);
     fn get_key(&self, key: u8) -> bool;
     fn get_keys_pressed(&self) -> Vec<u8>;
     fn get_close_window(&self) -> bool;
+    // Returns the hotkeys pressed since the last call
+    fn get_hotkeys(&mut self) -> Vec<Hotkey>;
     fn init(&self);
     fn stop(&self);
+    // Whether run sleeps to keep the frame rate, headless interfaces run as fast as possible
+    fn paced(&self) -> bool {
+        true
+    }
+    // Panels drawn next to the game screen, interfaces without room for them ignore them
+    fn set_side_panels(&mut self, _panels: Vec<SidePanel>) {}
+    // Reads a debugger command while the game stays on screen, output is shown above the prompt
+    fn read_command(&mut self, output: &str) -> Option<String> {
+        prompt_stdin(output)
+    }
+    // Colours the pixel values 0-3 are drawn in, recorded clips use the same ones
+    fn palette(&self) -> [(u8, u8, u8); 4] {
+        PALETTE
+    }
 }
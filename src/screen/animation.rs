@@ -0,0 +1,337 @@
+use std::{collections::HashMap, fs, io, path::Path};
+
+use super::{
+    framebuffer::Framebuffer,
+    image::{png_chunk, png_data, png_start, scale_pixels},
+};
+use crate::config::SCREEN_REFRESH_RATE;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AnimationFormat {
+    Gif,
+    Apng,
+}
+
+impl AnimationFormat {
+    // Format from the file extension, .png files are written as APNG
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let ext = path.extension()?.to_str()?.to_ascii_lowercase();
+        match ext.as_str() {
+            "gif" => Some(AnimationFormat::Gif),
+            "png" | "apng" => Some(AnimationFormat::Apng),
+            _ => None,
+        }
+    }
+}
+
+// Screen shown for a number of emulator frames
+struct Frame {
+    width: usize,
+    height: usize,
+    pixels: Vec<u8>,
+    frames: u32,
+}
+
+// Frames captured from the framebuffer, unchanged frames extend the previous one
+pub struct Animation {
+    frames: Vec<Frame>,
+    palette: [(u8, u8, u8); 4],
+}
+
+#[allow(dead_code)]
+impl Animation {
+    // palette holds the colours of the pixel values 0-3
+    pub fn new(palette: [(u8, u8, u8); 4]) -> Self {
+        Animation {
+            frames: Vec::new(),
+            palette,
+        }
+    }
+
+    pub fn push(&mut self, fb: &Framebuffer) {
+        let (width, height) = (fb.width() as usize, fb.height() as usize);
+        if let Some(last) = self.frames.last_mut() {
+            if last.width == width && last.pixels == fb.pixels() {
+                last.frames += 1;
+                return;
+            }
+        }
+        self.frames.push(Frame {
+            width,
+            height,
+            pixels: fb.pixels().to_vec(),
+            frames: 1,
+        });
+    }
+
+    // Distinct frames captured
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    // Emulator frames covered
+    pub fn duration(&self) -> u32 {
+        self.frames.iter().map(|frame| frame.frames).sum()
+    }
+
+    // Every frame scaled to the largest resolution in the recording times scale
+    fn scaled_frames(&self, scale: usize) -> (usize, usize, Vec<Vec<Vec<u8>>>) {
+        let width = self
+            .frames
+            .iter()
+            .map(|frame| frame.width)
+            .max()
+            .unwrap_or(0);
+        let height = self
+            .frames
+            .iter()
+            .map(|frame| frame.height)
+            .max()
+            .unwrap_or(0);
+        let frames = self
+            .frames
+            .iter()
+            .map(|frame| scale_pixels(frame.width, &frame.pixels, scale * width / frame.width))
+            .collect();
+        (width * scale, height * scale, frames)
+    }
+
+    pub fn gif(&self, scale: usize) -> Vec<u8> {
+        let (width, height, frames) = self.scaled_frames(scale.max(1));
+        let mut out = b"GIF89a".to_vec();
+        out.extend_from_slice(&(width as u16).to_le_bytes());
+        out.extend_from_slice(&(height as u16).to_le_bytes());
+        // Global colour table of 4 entries, background 0, square pixels
+        out.extend_from_slice(&[0xF1, 0, 0]);
+        for (r, g, b) in self.palette {
+            out.extend_from_slice(&[r, g, b]);
+        }
+        // Loop forever
+        out.extend_from_slice(&[0x21, 0xFF, 0x0B]);
+        out.extend_from_slice(b"NETSCAPE2.0");
+        out.extend_from_slice(&[0x03, 0x01, 0x00, 0x00, 0x00]);
+
+        for (rows, delay) in frames.iter().zip(gif_delays(&self.frames)) {
+            let Some(delay) = delay else {
+                continue;
+            };
+            // Graphic control extension with the delay in 1/100 s
+            out.extend_from_slice(&[0x21, 0xF9, 0x04, 0x00]);
+            out.extend_from_slice(&delay.to_le_bytes());
+            out.extend_from_slice(&[0x00, 0x00]);
+            // Image descriptor covering the whole screen, no local colour table
+            out.push(0x2C);
+            out.extend_from_slice(&[0, 0, 0, 0]);
+            out.extend_from_slice(&(width as u16).to_le_bytes());
+            out.extend_from_slice(&(height as u16).to_le_bytes());
+            out.push(0x00);
+
+            let pixels: Vec<u8> = rows.concat();
+            out.push(GIF_MIN_CODE_SIZE);
+            for block in lzw(&pixels).chunks(255) {
+                out.push(block.len() as u8);
+                out.extend_from_slice(block);
+            }
+            out.push(0x00);
+        }
+        out.push(0x3B);
+        out
+    }
+
+    pub fn apng(&self, scale: usize) -> Vec<u8> {
+        let (width, height, frames) = self.scaled_frames(scale.max(1));
+        let mut out = png_start(width, height, &self.palette);
+        let mut control = Vec::new();
+        control.extend_from_slice(&(frames.len() as u32).to_be_bytes());
+        // Loop forever
+        control.extend_from_slice(&0u32.to_be_bytes());
+        png_chunk(&mut out, b"acTL", &control);
+
+        // fcTL and fdAT chunks share one sequence
+        let mut sequence = 0u32;
+        for (index, (rows, frame)) in frames.iter().zip(self.frames.iter()).enumerate() {
+            let mut control = Vec::new();
+            control.extend_from_slice(&sequence.to_be_bytes());
+            control.extend_from_slice(&(width as u32).to_be_bytes());
+            control.extend_from_slice(&(height as u32).to_be_bytes());
+            control.extend_from_slice(&[0; 8]);
+            // Delay as a fraction of a second, no disposal, no blending
+            control.extend_from_slice(&(frame.frames.min(u16::MAX as u32) as u16).to_be_bytes());
+            control.extend_from_slice(&(SCREEN_REFRESH_RATE as u16).to_be_bytes());
+            control.extend_from_slice(&[0, 0]);
+            png_chunk(&mut out, b"fcTL", &control);
+            sequence += 1;
+
+            let data = png_data(rows);
+            if index == 0 {
+                png_chunk(&mut out, b"IDAT", &data);
+            } else {
+                let mut frame_data = sequence.to_be_bytes().to_vec();
+                frame_data.extend_from_slice(&data);
+                png_chunk(&mut out, b"fdAT", &frame_data);
+                sequence += 1;
+            }
+        }
+        png_chunk(&mut out, b"IEND", &[]);
+        out
+    }
+
+    // Writes the animation to path, the format follows the extension
+    pub fn save(&self, path: &Path, scale: usize) -> io::Result<()> {
+        let format = AnimationFormat::from_path(path).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "{}: unknown animation format, use .gif or .png",
+                    path.display()
+                ),
+            )
+        })?;
+        if self.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "no frames recorded",
+            ));
+        }
+        let data = match format {
+            AnimationFormat::Gif => self.gif(scale),
+            AnimationFormat::Apng => self.apng(scale),
+        };
+        fs::write(path, data)
+    }
+}
+
+// Shortest delay in 1/100 s, viewers slow down anything below it
+const GIF_MIN_DELAY: u32 = 2;
+// Colour indices use 2 bits
+const GIF_MIN_CODE_SIZE: u8 = 2;
+
+// Delay of every frame in 1/100 s, None for frames too short for GIF that are skipped
+fn gif_delays(frames: &[Frame]) -> Vec<Option<u16>> {
+    let centis = |frame: u32| (frame as u64 * 100 / SCREEN_REFRESH_RATE as u64) as u32;
+    let mut delays = vec![None; frames.len()];
+    // Index and start time of the frame on screen
+    let mut shown: Option<(usize, u32)> = None;
+    let mut start = 0;
+    for (index, frame) in frames.iter().enumerate() {
+        let at = centis(start);
+        shown = match shown {
+            // The frame before has not been up long enough, this one takes its place
+            Some((prev, prev_at)) if at - prev_at < GIF_MIN_DELAY => {
+                delays[prev] = None;
+                Some((index, prev_at))
+            }
+            _ => Some((index, at)),
+        };
+        start += frame.frames;
+        let shown_at = shown.map_or(at, |(_, at)| at);
+        let delay = (centis(start) - shown_at).max(GIF_MIN_DELAY);
+        delays[index] = Some(delay.min(u16::MAX as u32) as u16);
+    }
+    delays
+}
+
+// Variable width LZW as used by GIF, with 2 bit colour indices
+fn lzw(pixels: &[u8]) -> Vec<u8> {
+    let clear = 1u16 << GIF_MIN_CODE_SIZE;
+    let end = clear + 1;
+    let mut out = Vec::new();
+    let (mut acc, mut bits) = (0u32, 0u32);
+    let mut emit = |code: u16, size: u32| {
+        acc |= (code as u32) << bits;
+        bits += size;
+        while bits >= 8 {
+            out.push(acc as u8);
+            acc >>= 8;
+            bits -= 8;
+        }
+    };
+
+    let mut table: HashMap<(u16, u8), u16> = HashMap::new();
+    let mut next = end + 1;
+    let mut size = GIF_MIN_CODE_SIZE as u32 + 1;
+    emit(clear, size);
+    let mut pixels = pixels.iter();
+    if let Some(&first) = pixels.next() {
+        let mut prefix = first as u16;
+        for &pixel in pixels {
+            if let Some(&code) = table.get(&(prefix, pixel)) {
+                prefix = code;
+                continue;
+            }
+            emit(prefix, size);
+            if next < 4096 {
+                table.insert((prefix, pixel), next);
+                next += 1;
+                // The decoder adds its entry one code later
+                if next > 1 << size && size < 12 {
+                    size += 1;
+                }
+            } else {
+                emit(clear, size);
+                table.clear();
+                next = end + 1;
+                size = GIF_MIN_CODE_SIZE as u32 + 1;
+            }
+            prefix = pixel as u16;
+        }
+        emit(prefix, size);
+    }
+    emit(end, size);
+    if bits > 0 {
+        out.push(acc as u8);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::PALETTE;
+
+    #[test]
+    fn unchanged_frames_merge_into_longer_delays() {
+        let mut animation = Animation::new(PALETTE);
+        let mut fb = Framebuffer::new();
+        for frames in [1, 1, 1, 30] {
+            fb.draw_sprite(0, 0, &[0x80], 8, false);
+            for _ in 0..frames {
+                animation.push(&fb);
+            }
+        }
+        assert_eq!(animation.len(), 4);
+        assert_eq!(animation.duration(), 33);
+        // 60 Hz frames start at 0, 1, 3 and 5 centiseconds, the first one is too short for GIF
+        assert_eq!(
+            gif_delays(&animation.frames),
+            [None, Some(3), Some(2), Some(50)]
+        );
+    }
+
+    #[test]
+    fn clips_use_the_given_palette() {
+        let palette = [(1, 2, 3), (4, 5, 6), (7, 8, 9), (10, 11, 12)];
+        let mut animation = Animation::new(palette);
+        animation.push(&Framebuffer::new());
+        // Global colour table after the header and screen descriptor
+        assert_eq!(
+            animation.gif(1)[13..25],
+            [1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12]
+        );
+        // PLTE chunk data after the signature, IHDR and the PLTE length and type
+        assert_eq!(
+            animation.apng(1)[41..53],
+            [1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12]
+        );
+    }
+
+    #[test]
+    fn lzw_packs_codes_lsb_first() {
+        // Clear (4), pixel 0 and end (5) as 3 bit codes
+        assert_eq!(lzw(&[0]), [0b01_000_100, 0b1]);
+    }
+}
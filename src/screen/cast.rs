@@ -0,0 +1,101 @@
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+    time::{Instant, SystemTime, UNIX_EPOCH},
+};
+
+// Asciicast v2 recording: a JSON header line followed by one [time, "o", data] line per output
+pub struct CastWriter<W: Write> {
+    out: W,
+    start: Instant,
+    // First write error, later output is dropped
+    error: Option<io::Error>,
+}
+
+impl CastWriter<BufWriter<File>> {
+    pub fn create(path: &Path, width: u16, height: u16) -> io::Result<Self> {
+        CastWriter::new(BufWriter::new(File::create(path)?), width, height)
+    }
+}
+
+impl<W: Write> CastWriter<W> {
+    pub fn new(mut out: W, width: u16, height: u16) -> io::Result<Self> {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |time| time.as_secs());
+        writeln!(
+            out,
+            "{{\"version\": 2, \"width\": {width}, \"height\": {height}, \"timestamp\": {timestamp}}}"
+        )?;
+        Ok(CastWriter {
+            out,
+            start: Instant::now(),
+            error: None,
+        })
+    }
+
+    // Records bytes written to the terminal, timed from the creation of the writer
+    pub fn output(&mut self, data: &[u8]) {
+        if data.is_empty() || self.error.is_some() {
+            return;
+        }
+        let time = self.start.elapsed().as_secs_f64();
+        let text = json_string(&String::from_utf8_lossy(data));
+        if let Err(err) = writeln!(self.out, "[{time:.6}, \"o\", {text}]") {
+            self.error = Some(err);
+        }
+    }
+
+    // Flushes the recording and reports the first error
+    pub fn finish(&mut self) -> io::Result<()> {
+        if let Some(err) = self.error.take() {
+            return Err(err);
+        }
+        self.out.flush()
+    }
+
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+}
+
+fn json_string(text: &str) -> String {
+    let mut out = String::with_capacity(text.len() + 2);
+    out.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c < ' ' || c == '\x7f' => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn output_is_escaped_json() {
+        let mut cast = CastWriter::new(Vec::new(), 80, 24).unwrap();
+        cast.output(b"\x1b[?1049h");
+        cast.output(b"");
+        cast.output("\"\\█\r\n".as_bytes());
+        cast.finish().unwrap();
+        let text = String::from_utf8(cast.into_inner()).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with("{\"version\": 2, \"width\": 80, \"height\": 24, "));
+        assert!(lines[1].starts_with('['));
+        assert!(lines[1].ends_with(", \"o\", \"\\u001b[?1049h\"]"));
+        assert!(lines[2].ends_with(", \"o\", \"\\\"\\\\█\\r\\n\"]"));
+    }
+}
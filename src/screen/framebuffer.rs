@@ -0,0 +1,173 @@
+use crate::config::*;
+
+// Each pixel holds one bit per bitplane, bit 0 is plane 1 and bit 1 is plane 2 (XO-CHIP)
+pub struct Framebuffer {
+    width: u8,
+    height: u8,
+    planes: u8,
+    pixel_bitmap: Vec<u8>,
+}
+
+#[allow(dead_code)]
+impl Framebuffer {
+    pub fn new() -> Self {
+        Framebuffer {
+            width: SCREEN_WIDTH,
+            height: SCREEN_HEIGHT,
+            planes: 0b01,
+            pixel_bitmap: vec![0; SCREEN_WIDTH as usize * SCREEN_HEIGHT as usize],
+        }
+    }
+
+    pub fn width(&self) -> u8 {
+        self.width
+    }
+
+    pub fn height(&self) -> u8 {
+        self.height
+    }
+
+    pub fn hires(&self) -> bool {
+        self.width == HIRES_SCREEN_WIDTH
+    }
+
+    // Switches between 64x32 and 128x64, clears all planes
+    pub fn set_hires(&mut self, hires: bool) {
+        (self.width, self.height) = if hires {
+            (HIRES_SCREEN_WIDTH, HIRES_SCREEN_HEIGHT)
+        } else {
+            (SCREEN_WIDTH, SCREEN_HEIGHT)
+        };
+        self.pixel_bitmap = vec![0; self.width as usize * self.height as usize];
+    }
+
+    pub fn planes(&self) -> u8 {
+        self.planes
+    }
+
+    // Selects the bitplanes affected by drawing, clearing and scrolling
+    pub fn select_planes(&mut self, planes: u8) {
+        self.planes = planes & 0b11;
+    }
+
+    // Returns the colour index (0-3) of the pixel
+    pub fn get_pixel(&self, x: u8, y: u8) -> u8 {
+        if x >= self.width || y >= self.height {
+            return 0;
+        }
+        self.pixel_bitmap[x as usize + y as usize * self.width as usize]
+    }
+
+    pub fn clear(&mut self) {
+        for i in self.pixel_bitmap.iter_mut() {
+            *i &= !self.planes;
+        }
+    }
+
+    // Xors the pixel in plane with val, returns true if the pixel was erased
+    fn set_pixel(&mut self, x: u8, y: u8, plane: u8, val: bool) -> bool {
+        // Clip out of bounds
+        if x >= self.width || y >= self.height || !val {
+            return false;
+        }
+        let pixel = &mut self.pixel_bitmap[x as usize + y as usize * self.width as usize];
+        let before = *pixel & plane != 0;
+        *pixel ^= plane;
+        before
+    }
+
+    // Number of sprite bytes needed to draw len bytes into every selected plane
+    pub fn sprite_len(&self, len: usize) -> usize {
+        len * self.planes.count_ones() as usize
+    }
+
+    // Draws a sprite of 8 or 16 pixel wide rows, 16 pixel rows take two bytes.
+    // With several planes selected the sprite holds the data for each plane in turn
+    pub fn draw_sprite(&mut self, x: u8, y: u8, sprite: &[u8], width: u8, clip: bool) -> bool {
+        let mut pixel_erased: bool = false;
+        let x = x % self.width;
+        let y = y % self.height;
+        let bytes_per_row = width as usize / 8;
+        let selected: Vec<u8> = [0b01, 0b10]
+            .into_iter()
+            .filter(|plane| self.planes & plane != 0)
+            .collect();
+        if selected.is_empty() {
+            return false;
+        }
+        let plane_len = sprite.len() / selected.len();
+
+        for (plane, data) in selected.into_iter().zip(sprite.chunks(plane_len.max(1))) {
+            for (iteration, row) in data.chunks(bytes_per_row).enumerate() {
+                for bit in 0..width {
+                    let byte = row.get(bit as usize / 8).copied().unwrap_or(0);
+                    let val = (byte & (0b10000000 >> (bit % 8))) != 0;
+                    let (mut px, mut py) = (x as usize + bit as usize, y as usize + iteration);
+                    // Wrap around instead of clipping out of bounds
+                    if !clip {
+                        px %= self.width as usize;
+                        py %= self.height as usize;
+                    }
+                    if px < self.width as usize
+                        && py < self.height as usize
+                        && self.set_pixel(px as u8, py as u8, plane, val)
+                    {
+                        pixel_erased = true;
+                    }
+                }
+            }
+        }
+        pixel_erased
+    }
+
+    // Moves the selected planes by (dx, dy), pixels moved in from outside are cleared
+    fn shift(&mut self, dx: isize, dy: isize) {
+        let (width, height) = (self.width as isize, self.height as isize);
+        let old = self.pixel_bitmap.clone();
+        for y in 0..height {
+            for x in 0..width {
+                let (sx, sy) = (x - dx, y - dy);
+                let moved = if (0..width).contains(&sx) && (0..height).contains(&sy) {
+                    old[(sx + sy * width) as usize] & self.planes
+                } else {
+                    0
+                };
+                let pixel = &mut self.pixel_bitmap[(x + y * width) as usize];
+                *pixel = (*pixel & !self.planes) | moved;
+            }
+        }
+    }
+
+    pub fn scroll_down(&mut self, n: u8) {
+        self.shift(0, n as isize);
+    }
+
+    pub fn scroll_up(&mut self, n: u8) {
+        self.shift(0, -(n as isize));
+    }
+
+    pub fn scroll_right(&mut self, n: u8) {
+        self.shift(n as isize, 0);
+    }
+
+    pub fn scroll_left(&mut self, n: u8) {
+        self.shift(-(n as isize), 0);
+    }
+
+    pub fn pixels(&self) -> &[u8] {
+        &self.pixel_bitmap
+    }
+
+    // Restores pixels saved with pixels(), ignored if the size does not match the resolution
+    pub fn load_pixels(&mut self, pixels: &[u8]) {
+        if pixels.len() == self.pixel_bitmap.len() {
+            self.pixel_bitmap.copy_from_slice(pixels);
+        }
+    }
+}
+
+impl Default for Framebuffer {
+    fn default() -> Self {
+        Framebuffer::new()
+    }
+}
@@ -0,0 +1,451 @@
+use std::{
+    fmt::Write as _,
+    io::{self, IsTerminal, Write},
+    str::FromStr,
+    time::{Duration, Instant},
+};
+
+use crossterm::{
+    cursor,
+    terminal::{self, Clear, ClearType},
+    QueueableCommand,
+};
+
+use super::{framebuffer::Framebuffer, image, tui::Tui, Hotkey, Interface, SidePanel, PALETTE};
+
+// Sent before the primary device attributes query, only kitty compatible terminals answer it
+const KITTY_QUERY: &str = "\x1b_Gi=31,s=1,v=1,a=q,t=d,f=24;AAAA\x1b\\";
+const KITTY_REPLY: &str = "\x1b_Gi=31;OK";
+const DEVICE_ATTRIBUTES_QUERY: &str = "\x1b[c";
+// Terminals that do not answer at all get this long
+const DETECT_TIMEOUT: Duration = Duration::from_millis(500);
+// Base64 bytes per kitty escape sequence
+const KITTY_CHUNK_SIZE: usize = 4096;
+// Cell size assumed when the terminal does not report its size in pixels
+const DEFAULT_CELL_SIZE: (usize, usize) = (10, 20);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GraphicsProtocol {
+    Sixel,
+    Kitty,
+}
+
+// Protocol asked for on the command line, Auto queries the terminal
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GraphicsMode {
+    Auto,
+    Sixel,
+    Kitty,
+}
+
+impl GraphicsMode {
+    pub fn protocol(self) -> Option<GraphicsProtocol> {
+        match self {
+            GraphicsMode::Auto => detect(),
+            GraphicsMode::Sixel => Some(GraphicsProtocol::Sixel),
+            GraphicsMode::Kitty => Some(GraphicsProtocol::Kitty),
+        }
+    }
+}
+
+impl FromStr for GraphicsMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "auto" => Ok(GraphicsMode::Auto),
+            "sixel" => Ok(GraphicsMode::Sixel),
+            "kitty" => Ok(GraphicsMode::Kitty),
+            _ => Err(format!(
+                "unknown graphics protocol: {s} (expected auto, sixel or kitty)"
+            )),
+        }
+    }
+}
+
+// Asks the terminal which graphics protocol it speaks, None if it speaks neither or does not answer
+pub fn detect() -> Option<GraphicsProtocol> {
+    if !io::stdin().is_terminal() || !io::stdout().is_terminal() {
+        return None;
+    }
+    terminal::enable_raw_mode().ok()?;
+    let mut stdout = io::stdout();
+    let sent =
+        write!(stdout, "{KITTY_QUERY}{DEVICE_ATTRIBUTES_QUERY}").and_then(|_| stdout.flush());
+    let reply = match sent {
+        Ok(()) => read_reply(Instant::now() + DETECT_TIMEOUT),
+        Err(_) => Vec::new(),
+    };
+    let _ = terminal::disable_raw_mode();
+    parse_reply(&reply)
+}
+
+// Reads the reply from the tty without blocking past the deadline, so nothing is left
+// waiting on the tty to swallow the first key press once it is over
+#[cfg(unix)]
+fn read_reply(deadline: Instant) -> Vec<u8> {
+    use std::{
+        fs::OpenOptions,
+        io::Read,
+        os::unix::{fs::OpenOptionsExt, io::AsRawFd},
+    };
+
+    let mut reply = Vec::new();
+    let Ok(mut tty) = OpenOptions::new()
+        .read(true)
+        .custom_flags(libc::O_NONBLOCK)
+        .open("/dev/tty")
+    else {
+        return reply;
+    };
+    let mut buf = [0u8; 64];
+    while !reply_complete(&reply) {
+        let timeout = deadline.saturating_duration_since(Instant::now());
+        let mut fd = libc::pollfd {
+            fd: tty.as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        // Safe as fd is a single valid pollfd for the length of the call
+        let ready = unsafe { libc::poll(&mut fd, 1, timeout.as_millis() as libc::c_int) };
+        if ready <= 0 {
+            break;
+        }
+        match tty.read(&mut buf) {
+            Ok(0) => break,
+            Ok(len) => reply.extend_from_slice(&buf[..len]),
+            Err(err) if err.kind() == io::ErrorKind::WouldBlock => continue,
+            Err(_) => break,
+        }
+    }
+    reply
+}
+
+#[cfg(not(unix))]
+fn read_reply(_deadline: Instant) -> Vec<u8> {
+    Vec::new()
+}
+
+// The device attributes reply ends with c and comes after the kitty reply
+fn reply_complete(reply: &[u8]) -> bool {
+    let text = String::from_utf8_lossy(reply);
+    text.find("\x1b[?")
+        .is_some_and(|start| text[start..].ends_with('c'))
+}
+
+fn parse_reply(reply: &[u8]) -> Option<GraphicsProtocol> {
+    let text = String::from_utf8_lossy(reply);
+    if text.contains(KITTY_REPLY) {
+        return Some(GraphicsProtocol::Kitty);
+    }
+    // Attribute 4 in the device attributes means sixel graphics
+    let start = text.find("\x1b[?")? + 3;
+    let end = start + text[start..].find('c')?;
+    text[start..end]
+        .split(';')
+        .any(|attribute| attribute == "4")
+        .then_some(GraphicsProtocol::Sixel)
+}
+
+// Sixel image of the framebuffer, every pixel is scale x scale dots
+pub fn sixel(fb: &Framebuffer, scale: usize) -> String {
+    let rows = image::scale_pixels(fb.width() as usize, fb.pixels(), scale);
+    let (width, height) = (rows[0].len(), rows.len());
+    let mut out = format!("\x1bPq\"1;1;{width};{height}");
+    for (index, (r, g, b)) in PALETTE.iter().enumerate() {
+        let percent = |value: &u8| *value as u32 * 100 / 255;
+        let _ = write!(
+            out,
+            "#{index};2;{};{};{}",
+            percent(r),
+            percent(g),
+            percent(b)
+        );
+    }
+
+    for (band, band_rows) in rows.chunks(6).enumerate() {
+        if band > 0 {
+            out.push('-');
+        }
+        for colour in 0..PALETTE.len() as u8 {
+            let mut sixels: Vec<u8> = (0..width)
+                .map(|x| {
+                    band_rows
+                        .iter()
+                        .enumerate()
+                        .filter(|(_, row)| row[x] == colour)
+                        .fold(0, |bits, (dy, _)| bits | 1 << dy)
+                })
+                .collect();
+            while sixels.last() == Some(&0) {
+                sixels.pop();
+            }
+            if sixels.is_empty() {
+                continue;
+            }
+            let _ = write!(out, "#{colour}");
+            for run in sixels.chunk_by(|a, b| a == b) {
+                let char = (b'?' + run[0]) as char;
+                if run.len() > 3 {
+                    let _ = write!(out, "!{}{char}", run.len());
+                } else {
+                    out.extend(std::iter::repeat_n(char, run.len()));
+                }
+            }
+            out.push('$');
+        }
+    }
+    out.push_str("\x1b\\");
+    out
+}
+
+// Kitty graphics escape sequences sending the framebuffer as a PNG, it replaces the image of the previous frame
+pub fn kitty(fb: &Framebuffer, scale: usize) -> String {
+    let data = base64(&image::png(fb, scale));
+    let chunks: Vec<&[u8]> = data.as_bytes().chunks(KITTY_CHUNK_SIZE).collect();
+    let mut out = String::with_capacity(data.len() + 32 * chunks.len());
+    for (index, chunk) in chunks.iter().enumerate() {
+        let more = (index + 1 < chunks.len()) as u8;
+        // Only the first chunk carries the image keys, q=2 stops the terminal replying, C=1 keeps the cursor
+        if index == 0 {
+            out.push_str("\x1b_Ga=T,f=100,i=1,q=2,C=1,");
+        } else {
+            out.push_str("\x1b_G");
+        }
+        let _ = write!(out, "m={more};");
+        out.push_str(std::str::from_utf8(chunk).unwrap_or_default());
+        out.push_str("\x1b\\");
+    }
+    out
+}
+
+fn base64(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let bytes = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let group = (bytes[0] as u32) << 16 | (bytes[1] as u32) << 8 | bytes[2] as u32;
+        for index in 0..4 {
+            if index <= chunk.len() {
+                out.push(ALPHABET[(group >> (18 - 6 * index) & 0x3F) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+// Tui that draws the game screen as a bitmap with a terminal graphics protocol,
+// without a protocol it draws characters like Tui
+pub struct Graphics {
+    tui: Tui,
+    protocol: Option<GraphicsProtocol>,
+    scale: usize,
+}
+
+#[allow(dead_code)]
+impl Graphics {
+    pub fn set_protocol(&mut self, protocol: Option<GraphicsProtocol>) {
+        self.protocol = protocol;
+    }
+
+    pub fn protocol(&self) -> Option<GraphicsProtocol> {
+        self.protocol
+    }
+
+    pub fn set_scale(&mut self, scale: usize) {
+        self.scale = scale.max(1);
+    }
+
+    // Character output, cast recording and key handling are done by the wrapped Tui
+    pub fn tui_mut(&mut self) -> &mut Tui {
+        &mut self.tui
+    }
+
+    // Terminal cells covered by the image
+    fn image_cells(&self) -> (usize, usize) {
+        let fb = self.tui.framebuffer();
+        let (width, height) = (
+            fb.width() as usize * self.scale,
+            fb.height() as usize * self.scale,
+        );
+        let (cell_width, cell_height) = terminal::window_size()
+            .ok()
+            .filter(|size| size.width > 0 && size.height > 0 && size.columns > 0 && size.rows > 0)
+            .map_or(DEFAULT_CELL_SIZE, |size| {
+                (
+                    (size.width / size.columns) as usize,
+                    (size.height / size.rows) as usize,
+                )
+            });
+        (
+            width.div_ceil(cell_width.max(1)),
+            height.div_ceil(cell_height.max(1)),
+        )
+    }
+
+    // Rows used by the image and the side panels
+    fn rows(&self) -> usize {
+        self.image_cells().1.max(self.tui.side_lines().len())
+    }
+
+    // Escape sequences drawing one frame, side panels go to the right of the image
+    fn frame(&self, protocol: GraphicsProtocol, columns: usize) -> Vec<u8> {
+        let mut out = Vec::new();
+        // Reset cursor to the top-left corner
+        let _ = out.queue(Clear(ClearType::All));
+        let _ = out.queue(cursor::MoveTo(0, 0));
+        let fb = self.tui.framebuffer();
+        match protocol {
+            GraphicsProtocol::Sixel => out.extend_from_slice(sixel(fb, self.scale).as_bytes()),
+            GraphicsProtocol::Kitty => out.extend_from_slice(kitty(fb, self.scale).as_bytes()),
+        }
+        for (row, line) in self.tui.side_lines().iter().enumerate() {
+            let _ = out.queue(cursor::MoveTo(columns as u16 + 2, row as u16));
+            out.extend_from_slice(line.as_bytes());
+        }
+        out
+    }
+}
+
+impl Interface for Graphics {
+    fn new() -> Self {
+        Graphics {
+            tui: Tui::new(),
+            protocol: None,
+            scale: 1,
+        }
+    }
+
+    fn framebuffer(&self) -> &Framebuffer {
+        self.tui.framebuffer()
+    }
+
+    fn framebuffer_mut(&mut self) -> &mut Framebuffer {
+        self.tui.framebuffer_mut()
+    }
+
+    fn draw_sprite(&mut self, x: u8, y: u8, sprite: Vec<u8>, width: u8, clip: bool) -> bool {
+        self.tui.draw_sprite(x, y, sprite, width, clip)
+    }
+
+    fn update_screen(&mut self) {
+        let Some(protocol) = self.protocol else {
+            self.tui.update_screen();
+            return;
+        };
+        self.tui.poll_events();
+        let frame = self.frame(protocol, self.image_cells().0);
+        let mut stdout = self.tui.term();
+        stdout.write_all(&frame).unwrap();
+        stdout.flush().unwrap();
+    }
+
+    fn clear_screen(&mut self) {
+        self.tui.framebuffer_mut().clear();
+        self.update_screen();
+    }
+
+    fn get_key(&self, key: u8) -> bool {
+        self.tui.get_key(key)
+    }
+
+    fn get_keys_pressed(&self) -> Vec<u8> {
+        self.tui.get_keys_pressed()
+    }
+
+    fn get_close_window(&self) -> bool {
+        self.tui.get_close_window()
+    }
+
+    fn get_hotkeys(&mut self) -> Vec<Hotkey> {
+        self.tui.get_hotkeys()
+    }
+
+    fn init(&self) {
+        self.tui.init();
+    }
+
+    fn stop(&self) {
+        self.tui.stop();
+    }
+
+    fn set_side_panels(&mut self, panels: Vec<SidePanel>) {
+        self.tui.set_side_panels(panels);
+    }
+
+    fn read_command(&mut self, output: &str) -> Option<String> {
+        match self.protocol {
+            Some(_) => {
+                let rows = self.rows();
+                self.tui.read_command_at(rows, output)
+            }
+            None => self.tui.read_command(output),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sixel_bands_are_run_length_encoded() {
+        let mut fb = Framebuffer::new();
+        fb.draw_sprite(0, 0, &[0x80], 8, false);
+        let out = sixel(&fb, 1);
+
+        assert!(out.starts_with("\x1bPq\"1;1;64;32#0;2;0;0;0#1;2;100;100;100#2;2;100;40;0"));
+        assert!(out.ends_with("\x1b\\"));
+        // 32 rows make 5 full bands and one of 2 rows, the first holds the lit pixel
+        assert_eq!(out.matches('-').count(), 5);
+        assert!(out.contains("#0}!63~$#1@$-#0!64~$-"));
+        assert!(out.ends_with("-#0!64B$\x1b\\"));
+    }
+
+    #[test]
+    fn kitty_sends_png_in_chunks() {
+        let fb = Framebuffer::new();
+        let out = kitty(&fb, 1);
+        assert!(out.starts_with("\x1b_Ga=T,f=100,i=1,q=2,C=1,m=0;iVBORw0KGgo"));
+        assert!(out.ends_with("\x1b\\"));
+
+        // A large screen needs several escape sequences, all but the last marked m=1
+        let out = kitty(&fb, 16);
+        let sequences: Vec<&str> = out.split_terminator("\x1b\\").collect();
+        assert!(sequences.len() > 1);
+        assert!(sequences[0].starts_with("\x1b_Ga=T,f=100,i=1,q=2,C=1,m=1;"));
+        assert!(sequences[1..sequences.len() - 1]
+            .iter()
+            .all(|sequence| sequence.starts_with("\x1b_Gm=1;")));
+        assert!(sequences.last().unwrap().starts_with("\x1b_Gm=0;"));
+    }
+
+    #[test]
+    fn base64_pads_the_last_group() {
+        assert_eq!(base64(b"PNG"), "UE5H");
+        assert_eq!(base64(b"PN"), "UE4=");
+        assert_eq!(base64(b"P"), "UA==");
+    }
+
+    #[test]
+    fn replies_pick_the_protocol() {
+        assert_eq!(
+            parse_reply(b"\x1b_Gi=31;OK\x1b\\\x1b[?62;22c"),
+            Some(GraphicsProtocol::Kitty)
+        );
+        assert_eq!(
+            parse_reply(b"\x1b[?62;4;6;22c"),
+            Some(GraphicsProtocol::Sixel)
+        );
+        assert_eq!(parse_reply(b"\x1b[?62;22c"), None);
+        assert_eq!(parse_reply(b""), None);
+        assert!(reply_complete(b"\x1b_Gi=31;OK\x1b\\\x1b[?62;4c"));
+        assert!(!reply_complete(b"\x1b_Gi=31;OK\x1b\\\x1b[?62;4"));
+    }
+}
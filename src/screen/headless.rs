@@ -0,0 +1,164 @@
+use std::collections::VecDeque;
+
+use super::{framebuffer::Framebuffer, Hotkey, Interface};
+
+// Characters for the colour indices 0-3 in screen_text
+const PIXEL_CHARS: [char; 4] = ['.', '#', '+', '@'];
+
+// Scripted input, applied before the frame it is queued for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Input {
+    Press(u8),
+    Release(u8),
+    Hotkey(Hotkey),
+    Close,
+}
+
+// Interface without a terminal, keeps the framebuffer in memory and runs as fast as possible
+pub struct Headless {
+    framebuffer: Framebuffer,
+    // Bit n is set while key n is held
+    keys: u16,
+    script: VecDeque<(u64, Input)>,
+    hotkeys: Vec<Hotkey>,
+    // Frames shown with update_screen
+    frame: u64,
+    frame_limit: Option<u64>,
+    closed: bool,
+}
+
+#[allow(dead_code)]
+impl Headless {
+    // Queues input for the frame, frames are counted by update_screen starting at 0
+    pub fn queue(&mut self, frame: u64, input: Input) {
+        let index = self.script.partition_point(|(at, _)| *at <= frame);
+        self.script.insert(index, (frame, input));
+        self.apply_script();
+    }
+
+    // Presses the key for frames frames starting at frame
+    pub fn tap(&mut self, frame: u64, key: u8, frames: u64) {
+        self.queue(frame, Input::Press(key));
+        self.queue(frame + frames, Input::Release(key));
+    }
+
+    // Closes the window once frames frames have been shown
+    pub fn close_after(&mut self, frames: u64) {
+        self.frame_limit = Some(frames);
+    }
+
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+
+    // The screen as one line of PIXEL_CHARS per row
+    pub fn screen_text(&self) -> String {
+        let fb = &self.framebuffer;
+        let mut text = String::new();
+        for y in 0..fb.height() {
+            text.extend((0..fb.width()).map(|x| PIXEL_CHARS[fb.get_pixel(x, y) as usize & 0b11]));
+            text.push('\n');
+        }
+        text
+    }
+
+    fn apply_script(&mut self) {
+        while let Some((_, input)) = self.script.front().filter(|(at, _)| *at <= self.frame) {
+            match *input {
+                Input::Press(key) if key < 16 => self.keys |= 1 << key,
+                Input::Release(key) if key < 16 => self.keys &= !(1 << key),
+                Input::Press(_) | Input::Release(_) => {}
+                Input::Hotkey(hotkey) => self.hotkeys.push(hotkey),
+                Input::Close => self.closed = true,
+            }
+            self.script.pop_front();
+        }
+    }
+}
+
+impl Interface for Headless {
+    fn new() -> Self {
+        Headless {
+            framebuffer: Framebuffer::new(),
+            keys: 0,
+            script: VecDeque::new(),
+            hotkeys: Vec::new(),
+            frame: 0,
+            frame_limit: None,
+            closed: false,
+        }
+    }
+
+    fn framebuffer(&self) -> &Framebuffer {
+        &self.framebuffer
+    }
+
+    fn framebuffer_mut(&mut self) -> &mut Framebuffer {
+        &mut self.framebuffer
+    }
+
+    fn draw_sprite(&mut self, x: u8, y: u8, sprite: Vec<u8>, width: u8, clip: bool) -> bool {
+        self.framebuffer.draw_sprite(x, y, &sprite, width, clip)
+    }
+
+    fn update_screen(&mut self) {
+        self.frame += 1;
+        self.apply_script();
+    }
+
+    fn clear_screen(&mut self) {
+        self.framebuffer.clear();
+    }
+
+    fn get_key(&self, key: u8) -> bool {
+        key < 16 && self.keys & (1 << key) != 0
+    }
+
+    fn get_keys_pressed(&self) -> Vec<u8> {
+        (0..16).filter(|key| self.get_key(*key)).collect()
+    }
+
+    fn get_close_window(&self) -> bool {
+        self.closed || self.frame_limit.is_some_and(|limit| self.frame >= limit)
+    }
+
+    fn get_hotkeys(&mut self) -> Vec<Hotkey> {
+        std::mem::take(&mut self.hotkeys)
+    }
+
+    fn init(&self) {}
+
+    fn stop(&self) {}
+
+    fn paced(&self) -> bool {
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{chip::Chip, config::PROG_POS_START, quirks::Quirks};
+
+    #[test]
+    fn scripted_key_draws_its_font_glyph() {
+        let mut interface = Headless::new();
+        interface.tap(2, 0xA, 3);
+        interface.close_after(10);
+        let mut chip = Chip::new(PROG_POS_START, interface, Quirks::default());
+        // Wait for a key, draw its glyph at 0, 0 and loop forever
+        chip.load_prog(vec![
+            0xF0, 0x0A, 0xF0, 0x29, 0x61, 0x00, 0x62, 0x00, 0xD1, 0x25, 0x12, 0x0A,
+        ])
+        .unwrap();
+        chip.run().unwrap();
+
+        assert_eq!(chip.interface.frame(), 10);
+        let screen = chip.interface.screen_text();
+        let glyph: Vec<&str> = screen.lines().take(6).map(|row| &row[..5]).collect();
+        assert_eq!(
+            glyph,
+            ["####.", "#..#.", "####.", "#..#.", "#..#.", "....."]
+        );
+    }
+}
@@ -0,0 +1,402 @@
+use std::{fs, io, path::Path};
+
+use super::framebuffer::Framebuffer;
+use crate::config::PALETTE;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageFormat {
+    // Plain (ASCII) portable bitmap, lit pixels of any plane are black
+    Pbm,
+    // Indexed colour PNG using PALETTE
+    Png,
+}
+
+impl ImageFormat {
+    // Format from the file extension
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let ext = path.extension()?.to_str()?.to_ascii_lowercase();
+        match ext.as_str() {
+            "pbm" => Some(ImageFormat::Pbm),
+            "png" => Some(ImageFormat::Png),
+            _ => None,
+        }
+    }
+}
+
+// Colour index of every pixel, each one repeated scale times in both directions
+pub(super) fn scale_pixels(width: usize, pixels: &[u8], scale: usize) -> Vec<Vec<u8>> {
+    let scale = scale.max(1);
+    let mut rows = Vec::new();
+    for row in pixels.chunks(width) {
+        let row: Vec<u8> = row
+            .iter()
+            .flat_map(|pixel| std::iter::repeat_n(*pixel, scale))
+            .collect();
+        rows.extend(std::iter::repeat_n(row, scale));
+    }
+    rows
+}
+
+fn scaled_rows(fb: &Framebuffer, scale: usize) -> Vec<Vec<u8>> {
+    scale_pixels(fb.width() as usize, fb.pixels(), scale)
+}
+
+pub fn pbm(fb: &Framebuffer, scale: usize) -> Vec<u8> {
+    let rows = scaled_rows(fb, scale);
+    let mut out = format!("P1\n{} {}\n", rows[0].len(), rows.len());
+    for row in rows {
+        out.extend(row.iter().map(|pixel| if *pixel == 0 { '0' } else { '1' }));
+        out.push('\n');
+    }
+    out.into_bytes()
+}
+
+// Signature, header and palette of an indexed colour PNG
+pub(super) fn png_start(width: usize, height: usize, palette: &[(u8, u8, u8); 4]) -> Vec<u8> {
+    let mut out = PNG_SIGNATURE.to_vec();
+    let mut header = Vec::new();
+    header.extend_from_slice(&(width as u32).to_be_bytes());
+    header.extend_from_slice(&(height as u32).to_be_bytes());
+    // 8 bit indexed colour, deflate, no filtering, no interlacing
+    header.extend_from_slice(&[8, 3, 0, 0, 0]);
+    png_chunk(&mut out, b"IHDR", &header);
+
+    let palette: Vec<u8> = palette.iter().flat_map(|&(r, g, b)| [r, g, b]).collect();
+    png_chunk(&mut out, b"PLTE", &palette);
+    out
+}
+
+// Compressed image data, every row starts with filter type 0
+pub(super) fn png_data(rows: &[Vec<u8>]) -> Vec<u8> {
+    let mut raw = Vec::new();
+    for row in rows {
+        raw.push(0);
+        raw.extend_from_slice(row);
+    }
+    zlib(&raw)
+}
+
+pub fn png(fb: &Framebuffer, scale: usize) -> Vec<u8> {
+    let rows = scaled_rows(fb, scale);
+    let mut out = png_start(rows[0].len(), rows.len(), &PALETTE);
+    png_chunk(&mut out, b"IDAT", &png_data(&rows));
+    png_chunk(&mut out, b"IEND", &[]);
+    out
+}
+
+pub fn encode(fb: &Framebuffer, format: ImageFormat, scale: usize) -> Vec<u8> {
+    match format {
+        ImageFormat::Pbm => pbm(fb, scale),
+        ImageFormat::Png => png(fb, scale),
+    }
+}
+
+// Writes the framebuffer to path, the format follows the extension
+pub fn save(fb: &Framebuffer, path: &Path, scale: usize) -> io::Result<()> {
+    let format = ImageFormat::from_path(path).ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{}: unknown image format, use .png or .pbm", path.display()),
+        )
+    })?;
+    fs::write(path, encode(fb, format, scale))
+}
+
+const PNG_SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1A, b'\n'];
+
+// Appends a chunk with its length and CRC
+pub(super) fn png_chunk(out: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    out.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    let start = out.len();
+    out.extend_from_slice(kind);
+    out.extend_from_slice(data);
+    let crc = crc32(&out[start..]);
+    out.extend_from_slice(&crc.to_be_bytes());
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xFFFF_FFFFu32;
+    for byte in data {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for byte in data {
+        a = (a + *byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    (b << 16) | a
+}
+
+// Deflate length and distance codes: (base, extra bits)
+const LENGTH_CODES: [(u16, u8); 29] = [
+    (3, 0),
+    (4, 0),
+    (5, 0),
+    (6, 0),
+    (7, 0),
+    (8, 0),
+    (9, 0),
+    (10, 0),
+    (11, 1),
+    (13, 1),
+    (15, 1),
+    (17, 1),
+    (19, 2),
+    (23, 2),
+    (27, 2),
+    (31, 2),
+    (35, 3),
+    (43, 3),
+    (51, 3),
+    (59, 3),
+    (67, 4),
+    (83, 4),
+    (99, 4),
+    (115, 4),
+    (131, 5),
+    (163, 5),
+    (195, 5),
+    (227, 5),
+    (258, 0),
+];
+const DISTANCE_CODES: [(u16, u8); 30] = [
+    (1, 0),
+    (2, 0),
+    (3, 0),
+    (4, 0),
+    (5, 1),
+    (7, 1),
+    (9, 2),
+    (13, 2),
+    (17, 3),
+    (25, 3),
+    (33, 4),
+    (49, 4),
+    (65, 5),
+    (97, 5),
+    (129, 6),
+    (193, 6),
+    (257, 7),
+    (385, 7),
+    (513, 8),
+    (769, 8),
+    (1025, 9),
+    (1537, 9),
+    (2049, 10),
+    (3073, 10),
+    (4097, 11),
+    (6145, 11),
+    (8193, 12),
+    (12289, 12),
+    (16385, 13),
+    (24577, 13),
+];
+const WINDOW: usize = 32768;
+const MAX_MATCH: usize = 258;
+// Positions tried per match search
+const MAX_CHAIN: usize = 32;
+
+// Writes bits least significant first, as deflate expects
+struct BitWriter {
+    out: Vec<u8>,
+    acc: u32,
+    bits: u32,
+}
+
+impl BitWriter {
+    fn write(&mut self, value: u32, bits: u32) {
+        self.acc |= value << self.bits;
+        self.bits += bits;
+        while self.bits >= 8 {
+            self.out.push(self.acc as u8);
+            self.acc >>= 8;
+            self.bits -= 8;
+        }
+    }
+
+    // Huffman codes are stored most significant bit first
+    fn write_code(&mut self, code: u32, bits: u32) {
+        self.write(code.reverse_bits() >> (32 - bits), bits);
+    }
+
+    // Literal/length symbol with the fixed Huffman code
+    fn write_symbol(&mut self, symbol: u16) {
+        let symbol = symbol as u32;
+        match symbol {
+            0..=143 => self.write_code(0x30 + symbol, 8),
+            144..=255 => self.write_code(0x190 + symbol - 144, 9),
+            256..=279 => self.write_code(symbol - 256, 7),
+            _ => self.write_code(0xC0 + symbol - 280, 8),
+        }
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.bits > 0 {
+            self.out.push(self.acc as u8);
+        }
+        self.out
+    }
+}
+
+// Index of the last table entry whose base is at most value
+fn code_index(table: &[(u16, u8)], value: usize) -> usize {
+    table.partition_point(|(base, _)| *base as usize <= value) - 1
+}
+
+// Hash chains over 3 byte sequences for finding earlier matches
+struct Matcher<'a> {
+    data: &'a [u8],
+    head: Vec<usize>,
+    prev: Vec<usize>,
+}
+
+impl Matcher<'_> {
+    fn hash(&self, pos: usize) -> usize {
+        let bytes = &self.data[pos..pos + 3];
+        let value = (bytes[0] as usize) << 16 | (bytes[1] as usize) << 8 | bytes[2] as usize;
+        value.wrapping_mul(2654435761) >> 17 & 0x7FFF
+    }
+
+    fn insert(&mut self, pos: usize) {
+        if pos + 3 <= self.data.len() {
+            let hash = self.hash(pos);
+            self.prev[pos] = self.head[hash];
+            self.head[hash] = pos;
+        }
+    }
+
+    // Longest earlier match as (length, distance)
+    fn find(&self, pos: usize) -> (usize, usize) {
+        let (mut best_len, mut best_dist) = (0, 0);
+        if pos + 3 > self.data.len() {
+            return (best_len, best_dist);
+        }
+        let max_len = MAX_MATCH.min(self.data.len() - pos);
+        let mut candidate = self.head[self.hash(pos)];
+        let mut chain = 0;
+        while candidate != usize::MAX && pos - candidate <= WINDOW && chain < MAX_CHAIN {
+            let len = (0..max_len)
+                .take_while(|i| self.data[candidate + i] == self.data[pos + i])
+                .count();
+            if len > best_len {
+                (best_len, best_dist) = (len, pos - candidate);
+            }
+            candidate = self.prev[candidate];
+            chain += 1;
+        }
+        (best_len, best_dist)
+    }
+}
+
+// Single fixed Huffman block with greedy LZ77 matching, screens are mostly long runs
+fn deflate(data: &[u8]) -> Vec<u8> {
+    let mut writer = BitWriter {
+        out: Vec::new(),
+        acc: 0,
+        bits: 0,
+    };
+    // Final block, fixed Huffman codes
+    writer.write(1, 1);
+    writer.write(1, 2);
+
+    let mut matcher = Matcher {
+        data,
+        head: vec![usize::MAX; 0x8000],
+        prev: vec![usize::MAX; data.len()],
+    };
+    let mut pos = 0;
+    while pos < data.len() {
+        let (len, dist) = matcher.find(pos);
+        if len >= 3 {
+            let index = code_index(&LENGTH_CODES, len);
+            let (base, extra) = LENGTH_CODES[index];
+            writer.write_symbol(257 + index as u16);
+            writer.write((len - base as usize) as u32, extra as u32);
+            let index = code_index(&DISTANCE_CODES, dist);
+            let (base, extra) = DISTANCE_CODES[index];
+            writer.write_code(index as u32, 5);
+            writer.write((dist - base as usize) as u32, extra as u32);
+        } else {
+            writer.write_symbol(data[pos] as u16);
+        }
+        let next = pos + len.max(1);
+        for p in pos..next {
+            matcher.insert(p);
+        }
+        pos = next;
+    }
+    writer.write_symbol(256);
+    writer.finish()
+}
+
+fn zlib(data: &[u8]) -> Vec<u8> {
+    let mut out = vec![0x78, 0x01];
+    out.extend_from_slice(&deflate(data));
+    out.extend_from_slice(&adler32(data).to_be_bytes());
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dot() -> Framebuffer {
+        let mut fb = Framebuffer::new();
+        fb.draw_sprite(1, 0, &[0x80], 8, true);
+        fb
+    }
+
+    #[test]
+    fn pbm_is_scaled() {
+        let pbm = String::from_utf8(pbm(&dot(), 2)).unwrap();
+        let lines: Vec<&str> = pbm.lines().collect();
+        assert_eq!(lines[..2], ["P1", "128 64"]);
+        assert_eq!(&lines[2][..6], "001100");
+        assert_eq!(&lines[3][..6], "001100");
+        assert_eq!(&lines[4][..6], "000000");
+        assert_eq!(lines.len(), 2 + 64);
+    }
+
+    #[test]
+    fn png_chunks_are_valid() {
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+        assert_eq!(adler32(b"Wikipedia"), 0x11E6_0398);
+
+        let png = png(&dot(), 3);
+        assert_eq!(png[..8], PNG_SIGNATURE);
+        // Walks the chunks and checks their CRCs
+        let mut kinds = Vec::new();
+        let mut pos = 8;
+        while pos < png.len() {
+            let len = u32::from_be_bytes(png[pos..pos + 4].try_into().unwrap()) as usize;
+            let body = &png[pos + 4..pos + 8 + len];
+            let crc = u32::from_be_bytes(png[pos + 8 + len..pos + 12 + len].try_into().unwrap());
+            assert_eq!(crc32(body), crc);
+            kinds.push(String::from_utf8_lossy(&body[..4]).into_owned());
+            if &body[..4] == b"IHDR" {
+                assert_eq!(body[4..12], [0, 0, 0, 192, 0, 0, 0, 96]);
+            }
+            pos += 12 + len;
+        }
+        assert_eq!(kinds, ["IHDR", "PLTE", "IDAT", "IEND"]);
+    }
+
+    #[test]
+    fn zlib_uses_fixed_huffman_codes() {
+        // Empty final fixed block
+        assert_eq!(zlib(&[]), [0x78, 0x01, 0x03, 0x00, 0x00, 0x00, 0x00, 0x01]);
+        // A literal followed by a match of length 9 at distance 1
+        assert_eq!(deflate(&[0; 10]), [0x63, 0x80, 0x03, 0x00]);
+    }
+}
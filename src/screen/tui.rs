@@ -1,16 +1,21 @@
 use std::{
-    io::{self, Write},
-    time::Duration,
+    cell::{RefCell, RefMut},
+    fs::File,
+    io::{self, BufRead, BufWriter, StdoutLock, Write},
+    path::Path,
+    time::{Duration, Instant},
 };
 
 use crossterm::{
     cursor,
-    event::{self, Event, KeyCode, KeyEvent},
+    event::{self, Event, KeyCode, KeyEvent, KeyEventKind},
     terminal::{self, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen},
     ExecutableCommand, QueueableCommand,
 };
 
-use super::{Interface, SCREEN_HEIGHT, SCREEN_WIDTH};
+use super::{
+    cast::CastWriter, framebuffer::Framebuffer, Hotkey, Interface, SidePanel, TuiOutputMode,
+};
 
 // Keys for querty keyboard
 const KEY_1: KeyCode = KeyCode::Char(super::KEY_1);
@@ -30,24 +35,92 @@ const KEY_D: KeyCode = KeyCode::Char(super::KEY_D);
 const KEY_E: KeyCode = KeyCode::Char(super::KEY_E);
 const KEY_F: KeyCode = KeyCode::Char(super::KEY_F);
 
+// Braille characters start at U+2800 with one bit per dot
+const BRAILLE_BLANK: u32 = 0x2800;
+// Offset of the pixel behind each braille dot bit within its 2x4 block
+const BRAILLE_DOTS: [(usize, usize); 8] = [
+    (0, 0),
+    (0, 1),
+    (0, 2),
+    (1, 0),
+    (1, 1),
+    (1, 2),
+    (0, 3),
+    (1, 3),
+];
+
 pub struct Tui {
-    pixel_bitmap: [bool; SCREEN_WIDTH as usize * SCREEN_HEIGHT as usize],
+    pixel_bitmap: Framebuffer,
     close_window: bool,
+    key_pressed_at: [Option<Instant>; 16],
+    rewind_pressed_at: Option<Instant>,
+    hotkeys: Vec<Hotkey>,
+    side_panels: Vec<SidePanel>,
+    output_mode: TuiOutputMode,
+    // Terminal output is copied here when recording an asciicast
+    cast: RefCell<Option<CastWriter<BufWriter<File>>>>,
+}
+
+// Stdout that copies everything written to the cast recording, one event per flush
+pub(super) struct TermWriter<'a> {
+    stdout: StdoutLock<'static>,
+    cast: RefMut<'a, Option<CastWriter<BufWriter<File>>>>,
+    pending: Vec<u8>,
+}
+
+impl Write for TermWriter<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.stdout.write(buf)?;
+        if self.cast.is_some() {
+            self.pending.extend_from_slice(&buf[..written]);
+        }
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if let Some(cast) = self.cast.as_mut() {
+            cast.output(&self.pending);
+            self.pending.clear();
+        }
+        self.stdout.flush()
+    }
+}
+
+impl Drop for TermWriter<'_> {
+    fn drop(&mut self) {
+        let _ = self.flush();
+    }
 }
 
 impl Tui {
-    fn set_pixel(&mut self, x: u8, y: u8, val: bool) -> bool {
-        // Clip out of bounds
-        if x >= SCREEN_WIDTH || y >= SCREEN_HEIGHT {
-            return false;
+    // Records all terminal output from now on as an asciicast v2 file
+    pub fn record_cast(&mut self, path: &Path) -> io::Result<()> {
+        let (width, height) = terminal::size()
+            .ok()
+            .filter(|&(width, height)| width > 0 && height > 0)
+            .unwrap_or((80, 24));
+        *self.cast.get_mut() = Some(CastWriter::create(path, width, height)?);
+        Ok(())
+    }
+
+    // Ends the cast recording, reports the first write error
+    pub fn finish_cast(&mut self) -> io::Result<()> {
+        match self.cast.get_mut().take() {
+            Some(mut cast) => cast.finish(),
+            None => Ok(()),
         }
-        let pixel = &mut self.pixel_bitmap[x as usize + y as usize * SCREEN_WIDTH as usize];
-        let before = *pixel;
-        *pixel ^= val;
-        if !*pixel && before {
-            return true;
+    }
+
+    pub(super) fn term(&self) -> TermWriter<'_> {
+        TermWriter {
+            stdout: io::stdout().lock(),
+            cast: self.cast.borrow_mut(),
+            pending: Vec::new(),
         }
-        false
+    }
+
+    pub fn set_output_mode(&mut self, mode: TuiOutputMode) {
+        self.output_mode = mode;
     }
 
     pub fn key_to_u8(&self, key: Option<KeyCode>) -> Option<u8> {
@@ -75,57 +148,250 @@ impl Tui {
         }
     }
 
-    fn is_key_pressed(target_key: KeyCode) -> bool {
-        if event::poll(Duration::from_millis(0)).unwrap() {
+    // Drains all pending terminal events into the keypad state and hotkeys
+    pub(super) fn poll_events(&mut self) {
+        while event::poll(Duration::from_millis(0)).unwrap() {
             if let Event::Key(KeyEvent {
                 code,
                 modifiers: _,
-                kind: _,
+                kind,
                 state: _,
             }) = event::read().unwrap()
             {
-                return code == target_key;
+                if kind == KeyEventKind::Release {
+                    if let Some(key) = self.key_to_u8(Some(code)) {
+                        self.key_pressed_at[key as usize] = None;
+                    } else if code == KeyCode::Backspace {
+                        self.rewind_pressed_at = None;
+                    }
+                    continue;
+                }
+                match code {
+                    KeyCode::Esc => self.close_window = true,
+                    KeyCode::F(n @ 1..=4) => self.hotkeys.push(Hotkey::SaveState(n)),
+                    KeyCode::F(n @ 5..=8) => self.hotkeys.push(Hotkey::LoadState(n - 4)),
+                    KeyCode::F(9) => self.hotkeys.push(Hotkey::Pause),
+                    KeyCode::F(11) => self.hotkeys.push(Hotkey::Record),
+                    KeyCode::F(12) => self.hotkeys.push(Hotkey::Screenshot),
+                    KeyCode::Backspace => self.rewind_pressed_at = Some(Instant::now()),
+                    _ => {
+                        if let Some(key) = self.key_to_u8(Some(code)) {
+                            self.key_pressed_at[key as usize] = Some(Instant::now());
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    // Escape sequence setting the foreground for a pixel colour, plane 1 keeps the terminal colour
+    fn colour_escape(colour: u8) -> String {
+        match colour {
+            0 | 1 => String::from("\x1b[39m"),
+            _ => {
+                let (r, g, b) = super::PALETTE[colour as usize & 0b11];
+                format!("\x1b[38;2;{r};{g};{b}m")
+            }
+        }
+    }
+
+    // Escape sequence setting the background for a pixel colour, 0 keeps the terminal colour
+    fn background_escape(colour: u8) -> String {
+        match colour {
+            0 => String::from("\x1b[49m"),
+            _ => {
+                let (r, g, b) = super::PALETTE[colour as usize & 0b11];
+                format!("\x1b[48;2;{r};{g};{b}m")
+            }
+        }
+    }
+
+    // Character, foreground and background of the cell showing two pixels stacked vertically
+    fn half_block(top: u8, bottom: u8) -> (char, u8, u8) {
+        match (top, bottom) {
+            (0, 0) => (' ', 0, 0),
+            (top, 0) => (super::PIXEL_UPPER_HALF, top, 0),
+            (0, bottom) => (super::PIXEL_LOWER_HALF, bottom, 0),
+            (top, bottom) if top == bottom => (super::PIXEL_FULL, top, 0),
+            // Plane 1 has no background escape, it is always drawn as the foreground
+            (top, 1) => (super::PIXEL_LOWER_HALF, 1, top),
+            (top, bottom) => (super::PIXEL_UPPER_HALF, top, bottom),
+        }
+    }
+
+    // Character and colours of the cell showing 2x4 pixels, dots are given in braille bit order
+    fn braille(dots: [u8; 8]) -> (char, u8, u8) {
+        let bits = dots
+            .iter()
+            .enumerate()
+            .filter(|(_, &pixel)| pixel != 0)
+            .fold(0u32, |bits, (bit, _)| bits | 1 << bit);
+        if bits == 0 {
+            return (' ', 0, 0);
+        }
+        // Dots share one colour, the plane with the most lit pixels
+        let colour = (1..=3)
+            .max_by_key(|&colour| {
+                (
+                    dots.iter().filter(|&&pixel| pixel == colour).count(),
+                    3 - colour,
+                )
+            })
+            .unwrap_or(1);
+        let char = char::from_u32(BRAILLE_BLANK + bits).unwrap_or(' ');
+        (char, colour, 0)
+    }
+
+    // Prompts at row, below everything drawn, raw mode is left while the line is read
+    pub(super) fn read_command_at(&mut self, row: usize, output: &str) -> Option<String> {
+        let mut stdout = self.term();
+        stdout.queue(cursor::MoveTo(0, row as u16)).ok()?;
+        stdout.queue(Clear(ClearType::FromCursorDown)).ok()?;
+        for line in output.lines() {
+            write!(stdout, "{line}\r\n").ok()?;
+        }
+        write!(stdout, "(chip8) ").ok()?;
+        stdout.queue(cursor::Show).ok()?;
+        stdout.flush().ok()?;
+        drop(stdout);
+
+        terminal::disable_raw_mode().ok()?;
+        let mut line = String::new();
+        let read = io::stdin().lock().read_line(&mut line);
+        terminal::enable_raw_mode().ok()?;
+        self.term().execute(cursor::Hide).ok()?;
+        match read {
+            Ok(0) | Err(_) => None,
+            Ok(_) => Some(line),
+        }
+    }
+
+    // Side panels as text lines, highlighted lines are wrapped in inverse video escapes
+    pub(super) fn side_lines(&self) -> Vec<String> {
+        let mut lines = Vec::new();
+        for panel in self.side_panels.iter() {
+            lines.push(format!("\x1b[1m{}\x1b[22m", panel.title));
+            for (index, line) in panel.lines.iter().enumerate() {
+                if panel.highlight == Some(index) {
+                    lines.push(format!("\x1b[7m{line}\x1b[27m"));
+                } else {
+                    lines.push(line.clone());
+                }
             }
         }
-        false
+        lines
+    }
+
+    // Rows used by the game screen and the side panels
+    fn rows(&self) -> usize {
+        let side_rows: usize = self
+            .side_panels
+            .iter()
+            .map(|panel| panel.lines.len() + 1)
+            .sum();
+        side_rows.max(self.screen_rows())
+    }
+
+    // Terminal rows used by the game screen
+    fn screen_rows(&self) -> usize {
+        let height = self.pixel_bitmap.height() as usize;
+        match self.output_mode {
+            TuiOutputMode::HalfBlock => height.div_ceil(2),
+            TuiOutputMode::Braille => height.div_ceil(4),
+            _ => height,
+        }
     }
 
-    fn print_to_term(buffer: [char; 2 * (SCREEN_WIDTH as usize * SCREEN_HEIGHT as usize)]) {
-        let mut stdout = io::stdout().lock();
+    // Prints width x height cells with their colours next to the side panels
+    fn print_to_term(
+        &self,
+        buffer: Vec<char>,
+        colours: Vec<(u8, u8)>,
+        side: Vec<String>,
+        width: usize,
+        height: usize,
+    ) {
+        let mut stdout = self.term();
+        let mut current_colour = (0, 0);
+        let rows = height.max(side.len());
+        let reset = format!("{}{}", Tui::colour_escape(0), Tui::background_escape(0));
 
         // Reset cursor to the top-left corner
         stdout.queue(Clear(ClearType::All)).unwrap();
         stdout.queue(cursor::MoveTo(0, 0)).unwrap();
 
-        match super::TUI_OUTPUT_MODE {
-            super::TuiOutputMode::VecU8 => {
-                let mut output =
-                    Vec::<u8>::with_capacity(buffer.len() + 2 * SCREEN_HEIGHT as usize);
+        match self.output_mode {
+            TuiOutputMode::VecU8 => {
+                let mut output = Vec::<u8>::with_capacity(buffer.len() + 2 * rows);
 
-                for y in 0..SCREEN_HEIGHT as usize {
-                    for x in 0..(2 * SCREEN_WIDTH) as usize {
-                        let index = y * (2 * SCREEN_WIDTH as usize) + x;
-                        output.push(buffer[index] as u8)
+                for y in 0..rows {
+                    if y >= height {
+                        // Pad rows below the game screen so the side panels line up
+                        output.resize(output.len() + width, b' ');
+                    } else {
+                        for x in 0..width {
+                            let index = y * width + x;
+                            let (fg, bg) = colours[index];
+                            if fg != current_colour.0 {
+                                output.extend_from_slice(Tui::colour_escape(fg).as_bytes());
+                            }
+                            if bg != current_colour.1 {
+                                output.extend_from_slice(Tui::background_escape(bg).as_bytes());
+                            }
+                            current_colour = (fg, bg);
+                            output.push(buffer[index] as u8)
+                        }
                     }
-                    if y < SCREEN_HEIGHT as usize - 1 {
+                    if let Some(line) = side.get(y) {
+                        if current_colour != (0, 0) {
+                            output.extend_from_slice(reset.as_bytes());
+                            current_colour = (0, 0);
+                        }
+                        output.extend_from_slice(b"  ");
+                        output.extend_from_slice(line.as_bytes());
+                    }
+                    if y < rows - 1 {
                         output.push(b'\r');
                         output.push(b'\n');
                     }
                 }
+                output.extend_from_slice(reset.as_bytes());
                 stdout.write_all(&output).unwrap();
             }
-            super::TuiOutputMode::String => {
-                let mut output = String::with_capacity(buffer.len() + 2 * SCREEN_HEIGHT as usize);
+            TuiOutputMode::String | TuiOutputMode::HalfBlock | TuiOutputMode::Braille => {
+                let mut output = String::with_capacity(buffer.len() + 2 * rows);
 
-                for y in 0..SCREEN_HEIGHT as usize {
-                    for x in 0..(2 * SCREEN_WIDTH) as usize {
-                        let index = y * (2 * SCREEN_WIDTH as usize) + x;
-                        output.push(buffer[index])
+                for y in 0..rows {
+                    if y >= height {
+                        // Pad rows below the game screen so the side panels line up
+                        output.push_str(&" ".repeat(width));
+                    } else {
+                        for x in 0..width {
+                            let index = y * width + x;
+                            let (fg, bg) = colours[index];
+                            if fg != current_colour.0 {
+                                output.push_str(&Tui::colour_escape(fg));
+                            }
+                            if bg != current_colour.1 {
+                                output.push_str(&Tui::background_escape(bg));
+                            }
+                            current_colour = (fg, bg);
+                            output.push(buffer[index])
+                        }
+                    }
+                    if let Some(line) = side.get(y) {
+                        if current_colour != (0, 0) {
+                            output.push_str(&reset);
+                            current_colour = (0, 0);
+                        }
+                        output.push_str("  ");
+                        output.push_str(line);
                     }
-                    if y < SCREEN_HEIGHT as usize - 1 {
+                    if y < rows - 1 {
                         output.push_str("\r\n");
                     }
                 }
+                output.push_str(&reset);
                 stdout.write_all(output.as_bytes()).unwrap();
             }
         }
@@ -137,96 +403,119 @@ impl Tui {
 impl Interface for Tui {
     fn new() -> Self {
         Tui {
-            pixel_bitmap: [false; SCREEN_WIDTH as usize * SCREEN_HEIGHT as usize],
+            pixel_bitmap: Framebuffer::new(),
             close_window: false,
+            key_pressed_at: [None; 16],
+            rewind_pressed_at: None,
+            hotkeys: Vec::new(),
+            side_panels: Vec::new(),
+            output_mode: super::TUI_OUTPUT_MODE,
+            cast: RefCell::new(None),
         }
     }
 
-    fn draw_sprite(&mut self, x: u8, y: u8, sprite: Vec<u8>) -> bool {
-        let mut pixel_erased: bool = false;
-        let x = x % SCREEN_WIDTH;
-        let y = y % SCREEN_HEIGHT;
-        for (iteration, line) in sprite.iter().enumerate() {
-            for bit in 0..8u8 {
-                if self.set_pixel(
-                    x + bit,
-                    y + iteration as u8,
-                    (line & (0b10000000 >> bit)) != 0,
-                ) {
-                    pixel_erased = true;
-                }
-            }
-        }
-        pixel_erased
+    fn framebuffer(&self) -> &Framebuffer {
+        &self.pixel_bitmap
     }
 
-    fn update_screen(&mut self) {
-        if Tui::is_key_pressed(KeyCode::Esc) {
-            self.close_window = true;
-        }
-
-        let mut output_buffer = [' '; 2 * (SCREEN_WIDTH as usize * SCREEN_HEIGHT as usize)];
+    fn framebuffer_mut(&mut self) -> &mut Framebuffer {
+        &mut self.pixel_bitmap
+    }
 
-        let pixel_on = match super::TUI_OUTPUT_MODE {
-            super::TuiOutputMode::VecU8 => super::PIXEL_ON_VEC_U8,
-            super::TuiOutputMode::String => super::PIXEL_ON_STRING,
-        };
-        let pixel_off = match super::TUI_OUTPUT_MODE {
-            super::TuiOutputMode::VecU8 => super::PIXEL_OFF_VEC_U8,
-            super::TuiOutputMode::String => super::PIXEL_OFF_STRING,
-        };
+    fn draw_sprite(&mut self, x: u8, y: u8, sprite: Vec<u8>, width: u8, clip: bool) -> bool {
+        self.pixel_bitmap.draw_sprite(x, y, &sprite, width, clip)
+    }
 
-        for (index, &pixel) in self.pixel_bitmap.iter().enumerate() {
-            let y = index / SCREEN_WIDTH as usize;
-            let x = index % SCREEN_WIDTH as usize;
+    fn update_screen(&mut self) {
+        self.poll_events();
 
-            let output_index = y * (2 * SCREEN_WIDTH as usize) + (2 * x);
+        let width = self.pixel_bitmap.width() as usize;
+        let height = self.pixel_bitmap.height() as usize;
+        let pixels = self.pixel_bitmap.pixels();
 
-            let char = if pixel {
-                pixel_on
-            } else {
-                pixel_off
-            };
-            output_buffer[output_index] = char;
-            output_buffer[output_index + 1] = char;
-        }
+        let (output_buffer, colour_buffer, columns) = match self.output_mode {
+            TuiOutputMode::VecU8 | TuiOutputMode::String => {
+                let (pixel_on, pixel_off) = match self.output_mode {
+                    TuiOutputMode::VecU8 => (super::PIXEL_ON_VEC_U8, super::PIXEL_OFF_VEC_U8),
+                    _ => (super::PIXEL_ON_STRING, super::PIXEL_OFF_STRING),
+                };
+                // Two characters per pixel to keep pixels roughly square
+                let mut output_buffer = Vec::with_capacity(2 * pixels.len());
+                let mut colour_buffer = Vec::with_capacity(2 * pixels.len());
+                for &pixel in pixels {
+                    let char = if pixel != 0 { pixel_on } else { pixel_off };
+                    output_buffer.extend([char, char]);
+                    colour_buffer.extend([(pixel, 0), (pixel, 0)]);
+                }
+                (output_buffer, colour_buffer, 2 * width)
+            }
+            TuiOutputMode::HalfBlock => {
+                let mut output_buffer = Vec::with_capacity(pixels.len() / 2);
+                let mut colour_buffer = Vec::with_capacity(pixels.len() / 2);
+                for y in (0..height).step_by(2) {
+                    for x in 0..width {
+                        let top = pixels[y * width + x];
+                        let bottom = if y + 1 < height {
+                            pixels[(y + 1) * width + x]
+                        } else {
+                            0
+                        };
+                        let (char, fg, bg) = Tui::half_block(top, bottom);
+                        output_buffer.push(char);
+                        colour_buffer.push((fg, bg));
+                    }
+                }
+                (output_buffer, colour_buffer, width)
+            }
+            TuiOutputMode::Braille => {
+                let mut output_buffer = Vec::with_capacity(pixels.len() / 8);
+                let mut colour_buffer = Vec::with_capacity(pixels.len() / 8);
+                for y in (0..height).step_by(4) {
+                    for x in (0..width).step_by(2) {
+                        let mut dots = [0; 8];
+                        for (dot, &(dx, dy)) in dots.iter_mut().zip(BRAILLE_DOTS.iter()) {
+                            if x + dx < width && y + dy < height {
+                                *dot = pixels[(y + dy) * width + x + dx];
+                            }
+                        }
+                        let (char, fg, bg) = Tui::braille(dots);
+                        output_buffer.push(char);
+                        colour_buffer.push((fg, bg));
+                    }
+                }
+                (output_buffer, colour_buffer, width.div_ceil(2))
+            }
+        };
 
-        Tui::print_to_term(output_buffer);
+        self.print_to_term(
+            output_buffer,
+            colour_buffer,
+            self.side_lines(),
+            columns,
+            self.screen_rows(),
+        );
     }
 
     fn clear_screen(&mut self) {
-        for i in self.pixel_bitmap.iter_mut() {
-            *i = false;
-        }
+        self.pixel_bitmap.clear();
         self.update_screen();
     }
 
     fn get_key(&self, key: u8) -> bool {
-        match key {
-            0x0 => Tui::is_key_pressed(KEY_0),
-            0x1 => Tui::is_key_pressed(KEY_1),
-            0x2 => Tui::is_key_pressed(KEY_2),
-            0x3 => Tui::is_key_pressed(KEY_3),
-            0x4 => Tui::is_key_pressed(KEY_4),
-            0x5 => Tui::is_key_pressed(KEY_5),
-            0x6 => Tui::is_key_pressed(KEY_6),
-            0x7 => Tui::is_key_pressed(KEY_7),
-            0x8 => Tui::is_key_pressed(KEY_8),
-            0x9 => Tui::is_key_pressed(KEY_9),
-            0xA => Tui::is_key_pressed(KEY_A),
-            0xB => Tui::is_key_pressed(KEY_B),
-            0xC => Tui::is_key_pressed(KEY_C),
-            0xD => Tui::is_key_pressed(KEY_D),
-            0xE => Tui::is_key_pressed(KEY_E),
-            0xF => Tui::is_key_pressed(KEY_F),
+        match self.key_pressed_at.get(key as usize) {
+            Some(Some(pressed_at)) => {
+                pressed_at.elapsed() < Duration::from_millis(super::KEY_HOLD_TIME_MS)
+            }
             _ => false,
         }
     }
 
     fn get_keys_pressed(&self) -> Vec<u8> {
         let mut out = Vec::<u8>::new();
-        for key in 0..0xF {
-            if self.get_key(key) {out.push(key);}
+        for key in 0..=0xF {
+            if self.get_key(key) {
+                out.push(key);
+            }
         }
         out
     }
@@ -235,18 +524,64 @@ impl Interface for Tui {
         self.close_window
     }
 
+    fn get_hotkeys(&mut self) -> Vec<Hotkey> {
+        let mut hotkeys = std::mem::take(&mut self.hotkeys);
+        if let Some(pressed_at) = self.rewind_pressed_at {
+            if pressed_at.elapsed() < Duration::from_millis(super::KEY_HOLD_TIME_MS) {
+                hotkeys.push(Hotkey::Rewind);
+            }
+        }
+        hotkeys
+    }
+
     fn init(&self) {
-        io::stdout()
+        self.term()
             .execute(EnterAlternateScreen)
             .expect("Could not Enter alternate Screen");
-        io::stdout().execute(cursor::Hide).unwrap();
+        self.term().execute(cursor::Hide).unwrap();
         terminal::enable_raw_mode().expect("Could not enable raw mode");
     }
 
     fn stop(&self) {
         terminal::disable_raw_mode().expect("Could not disable raw mode");
-        io::stdout()
+        self.term()
             .execute(LeaveAlternateScreen)
             .expect("Could not leave Alternate Screen");
     }
+
+    fn set_side_panels(&mut self, panels: Vec<SidePanel>) {
+        self.side_panels = panels;
+    }
+
+    // Prompts below the game screen, raw mode is left while the line is read
+    fn read_command(&mut self, output: &str) -> Option<String> {
+        self.read_command_at(self.rows(), output)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn half_blocks_pack_two_rows() {
+        assert_eq!(Tui::half_block(0, 0), (' ', 0, 0));
+        assert_eq!(Tui::half_block(1, 0), ('▀', 1, 0));
+        assert_eq!(Tui::half_block(0, 2), ('▄', 2, 0));
+        assert_eq!(Tui::half_block(3, 3), ('█', 3, 0));
+        assert_eq!(Tui::half_block(1, 2), ('▀', 1, 2));
+        // Plane 1 stays in the foreground
+        assert_eq!(Tui::half_block(2, 1), ('▄', 1, 2));
+    }
+
+    #[test]
+    fn braille_dots_follow_bit_order() {
+        assert_eq!(Tui::braille([0; 8]), (' ', 0, 0));
+        assert_eq!(Tui::braille([1, 0, 0, 0, 0, 0, 0, 0]), ('⠁', 1, 0));
+        assert_eq!(Tui::braille([0, 0, 0, 0, 0, 0, 0, 1]), ('⢀', 1, 0));
+        assert_eq!(Tui::braille([1; 8]), ('⣿', 1, 0));
+        // The most common plane colours the cell, ties go to the lower plane
+        assert_eq!(Tui::braille([2, 2, 1, 0, 0, 0, 0, 0]), ('⠇', 2, 0));
+        assert_eq!(Tui::braille([2, 3, 0, 0, 0, 0, 0, 0]), ('⠃', 2, 0));
+    }
 }
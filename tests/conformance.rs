@@ -0,0 +1,184 @@
+// Runs test roms headless and compares the final screen with tests/golden/<name>.pbm.
+// Set UPDATE_GOLDEN=1 to write the golden files instead of comparing.
+
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+use chip_8::{
+    asm::assemble_file,
+    chip::Chip,
+    config::PROG_POS_START,
+    quirks::Quirks,
+    screen::{headless::Headless, image, Interface},
+};
+
+fn tests_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests")
+}
+
+// Runs the rom for frames frames, script queues input on the interface first
+fn run(rom: Vec<u8>, frames: u64, script: impl FnOnce(&mut Headless)) -> String {
+    let mut interface = Headless::new();
+    script(&mut interface);
+    interface.close_after(frames);
+    let mut chip = Chip::new(PROG_POS_START, interface, Quirks::default());
+    chip.load_prog(rom).unwrap();
+    chip.run().unwrap();
+    String::from_utf8(image::pbm(chip.interface.framebuffer(), 1)).unwrap()
+}
+
+fn assert_golden(name: &str, screen: &str) {
+    let path = tests_dir().join("golden").join(format!("{name}.pbm"));
+    if env::var_os("UPDATE_GOLDEN").is_some() {
+        fs::write(&path, screen).unwrap();
+        return;
+    }
+    let golden = fs::read_to_string(&path).unwrap_or_else(|err| {
+        panic!(
+            "{}: {err}, run with UPDATE_GOLDEN=1 to create it",
+            path.display()
+        )
+    });
+    assert_eq!(screen, golden, "{name} differs from {}", path.display());
+}
+
+// The pass and fail sprites of common.asm
+const TICK: [&str; 5] = ["00000010", "00000100", "10001000", "01010000", "00100000"];
+const CROSS: [&str; 5] = ["10001000", "01010000", "00100000", "01010000", "10001000"];
+
+// Marks on the screen in the order mark draws them, true for a tick, up to the first empty cell
+fn marks(screen: &str) -> Vec<bool> {
+    let rows: Vec<&str> = screen.lines().skip(2).collect();
+    let mut marks = Vec::new();
+    for y in (0..rows.len() - TICK.len()).step_by(7) {
+        for x in (0..64).step_by(8) {
+            let cell: Vec<&str> = rows[y..y + TICK.len()]
+                .iter()
+                .map(|row| &row[x..x + 8])
+                .collect();
+            if cell == TICK {
+                marks.push(true);
+            } else if cell == CROSS {
+                marks.push(false);
+            } else {
+                return marks;
+            }
+        }
+    }
+    marks
+}
+
+// Assembles tests/roms/<name>.asm, every check draws a tick or a cross.
+// The screen has to show checks ticks before it is compared with the golden file.
+fn check_asm(name: &str, frames: u64, checks: usize, script: impl FnOnce(&mut Headless)) {
+    let path = tests_dir().join("roms").join(format!("{name}.asm"));
+    let rom = assemble_file(&path, PROG_POS_START).unwrap_or_else(|err| panic!("{err}"));
+    let screen = run(rom, frames, script);
+    assert_eq!(
+        marks(&screen),
+        vec![true; checks],
+        "{name}: a check failed\n{screen}"
+    );
+    assert_golden(name, &screen);
+}
+
+// Third party roms are not vendored, see tests/roms/README.md for where to get them
+fn check_rom(file: &str, frames: u64, script: impl FnOnce(&mut Headless)) {
+    let path = tests_dir().join("roms").join(file);
+    let rom = fs::read(&path).unwrap_or_else(|err| panic!("{}: {err}", path.display()));
+    let name = Path::new(file).file_stem().unwrap().to_string_lossy();
+    assert_golden(&name, &run(rom, frames, script));
+}
+
+#[test]
+fn flow() {
+    check_asm("flow", 60, 6, |_| {});
+}
+
+#[test]
+fn skips() {
+    check_asm("skips", 60, 8, |_| {});
+}
+
+#[test]
+fn load_add() {
+    check_asm("load_add", 60, 5, |_| {});
+}
+
+#[test]
+fn alu() {
+    check_asm("alu", 60, 21, |_| {});
+}
+
+#[test]
+fn index() {
+    check_asm("index", 60, 12, |_| {});
+}
+
+#[test]
+fn random() {
+    check_asm("random", 60, 3, |_| {});
+}
+
+#[test]
+fn draw() {
+    check_asm("draw", 60, 5, |_| {});
+}
+
+#[test]
+fn timers() {
+    check_asm("timers", 60, 4, |_| {});
+}
+
+#[test]
+fn keypad() {
+    check_asm("keypad", 60, 5, |interface| {
+        interface.tap(2, 5, 2);
+        interface.tap(10, 9, 30);
+    });
+}
+
+// Timendus chip8-test-suite, https://github.com/Timendus/chip8-test-suite
+
+#[test]
+#[ignore = "needs 2-ibm-logo.ch8 in tests/roms"]
+fn ibm_logo() {
+    check_rom("2-ibm-logo.ch8", 60, |_| {});
+}
+
+#[test]
+#[ignore = "needs 3-corax+.ch8 in tests/roms"]
+fn corax_plus() {
+    check_rom("3-corax+.ch8", 60, |_| {});
+}
+
+#[test]
+#[ignore = "needs 4-flags.ch8 in tests/roms"]
+fn flags() {
+    check_rom("4-flags.ch8", 60, |_| {});
+}
+
+#[test]
+#[ignore = "needs 5-quirks.ch8 in tests/roms"]
+fn quirks() {
+    // Picks CHIP-8 from the menu
+    check_rom("5-quirks.ch8", 600, |interface| interface.tap(10, 1, 5));
+}
+
+#[test]
+#[ignore = "needs 6-keypad.ch8 in tests/roms"]
+fn keypad_rom() {
+    // Picks the FX0A test from the menu, then presses and releases a key
+    check_rom("6-keypad.ch8", 120, |interface| {
+        interface.tap(10, 3, 5);
+        interface.tap(40, 0xA, 5);
+    });
+}
+
+#[test]
+#[ignore = "needs BC_test.ch8 in tests/roms"]
+fn bc_test() {
+    check_rom("BC_test.ch8", 60, |_| {});
+}
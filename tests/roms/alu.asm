@@ -0,0 +1,150 @@
+; 8XY1 OR, 8XY2 AND, 8XY3 XOR, 8XY4 ADD, 8XY5 SUB, 8XY6 SHR, 8XY7 SUBN, 8XYE SHL
+; with the COSMAC VIP quirks: logic ops reset VF, shifts read VY.
+; The flag is copied to V2 right away, then the result in V0 and the flag are checked.
+
+        LD V0, 0x0F
+        LD V1, 0xF0
+        LD VF, 5
+        OR V0, V1
+        LD V2, VF
+        LD I, pass
+        SE V0, 0xFF
+        LD I, fail
+        CALL mark
+        LD I, pass
+        SE V2, 0
+        LD I, fail
+        CALL mark
+
+        LD V0, 0x3C
+        LD V1, 0x0F
+        LD VF, 5
+        AND V0, V1
+        LD V2, VF
+        LD I, pass
+        SE V0, 0x0C
+        LD I, fail
+        CALL mark
+        LD I, pass
+        SE V2, 0
+        LD I, fail
+        CALL mark
+
+        LD V0, 0x3C
+        LD V1, 0x0F
+        LD VF, 5
+        XOR V0, V1
+        LD V2, VF
+        LD I, pass
+        SE V0, 0x33
+        LD I, fail
+        CALL mark
+        LD I, pass
+        SE V2, 0
+        LD I, fail
+        CALL mark
+
+        LD V0, 0x10
+        LD V1, 0x20
+        ADD V0, V1
+        LD V2, VF
+        LD I, pass
+        SE V0, 0x30
+        LD I, fail
+        CALL mark
+        LD I, pass
+        SE V2, 0
+        LD I, fail
+        CALL mark
+
+        LD V0, 0xF0
+        LD V1, 0x20
+        ADD V0, V1
+        LD V2, VF
+        LD I, pass
+        SE V0, 0x10
+        LD I, fail
+        CALL mark
+        LD I, pass
+        SE V2, 1
+        LD I, fail
+        CALL mark
+
+        LD V0, 0x30
+        LD V1, 0x10
+        SUB V0, V1
+        LD V2, VF
+        LD I, pass
+        SE V0, 0x20
+        LD I, fail
+        CALL mark
+        LD I, pass
+        SE V2, 1
+        LD I, fail
+        CALL mark
+
+        LD V0, 0x10
+        LD V1, 0x30
+        SUB V0, V1
+        LD V2, VF
+        LD I, pass
+        SE V0, 0xE0
+        LD I, fail
+        CALL mark
+        LD I, pass
+        SE V2, 0
+        LD I, fail
+        CALL mark
+
+        LD V0, 0x10
+        LD V1, 0x30
+        SUBN V0, V1
+        LD V2, VF
+        LD I, pass
+        SE V0, 0x20
+        LD I, fail
+        CALL mark
+        LD I, pass
+        SE V2, 1
+        LD I, fail
+        CALL mark
+
+        LD V0, 0xFF
+        LD V1, 0x05
+        SHR V0, V1
+        LD V2, VF
+        LD I, pass
+        SE V0, 0x02
+        LD I, fail
+        CALL mark
+        LD I, pass
+        SE V2, 1
+        LD I, fail
+        CALL mark
+
+        LD V0, 0xFF
+        LD V1, 0x81
+        SHL V0, V1
+        LD V2, VF
+        LD I, pass
+        SE V0, 0x02
+        LD I, fail
+        CALL mark
+        LD I, pass
+        SE V2, 1
+        LD I, fail
+        CALL mark
+
+; With VF as the destination the flag wins over the result
+        LD VF, 0x10
+        LD V1, 0x20
+        ADD VF, V1
+        LD V2, VF
+        LD I, pass
+        SE V2, 0
+        LD I, fail
+        CALL mark
+
+done:   JP done
+
+        INCLUDE "common.asm"
@@ -0,0 +1,15 @@
+; Shared by the conformance roms, included after their code.
+; Checks load pass into I, replace it with fail if the result is wrong and call mark.
+; VA and VB hold the cursor, VF is changed by the draw.
+
+; Draws the sprite at I at the cursor and moves it on, 8 marks per row
+mark:   DRW VA, VB, 6
+        ADD VA, 8
+        SE VA, 64
+        RET
+        LD VA, 0
+        ADD VB, 7
+        RET
+
+pass:   DB 0b00000010, 0b00000100, 0b10001000, 0b01010000, 0b00100000, 0
+fail:   DB 0b10001000, 0b01010000, 0b00100000, 0b01010000, 0b10001000, 0
@@ -0,0 +1,62 @@
+; DXYN DRW: collision flag, erasing, wrapped start coordinates and clipping.
+; The sprites stay on screen below the marks for the golden image.
+
+; 0 at 0, 27 does not collide, drawing it again erases it and collides
+        LD V0, 0
+        LD F, V0
+        LD V1, 0
+        LD V2, 27
+        DRW V1, V2, 5
+        LD V3, VF
+        DRW V1, V2, 5
+        LD V4, VF
+        DRW V1, V2, 5
+        LD I, pass
+        SE V3, 0
+        LD I, fail
+        CALL mark
+        LD I, pass
+        SE V4, 1
+        LD I, fail
+        CALL mark
+
+; The start coordinates wrap, 1 lands at 8, 27
+        LD V0, 1
+        LD F, V0
+        LD V1, 72
+        LD V2, 59
+        DRW V1, V2, 5
+        LD V3, VF
+        LD I, pass
+        SE V3, 0
+        LD I, fail
+        CALL mark
+
+; A full row at 60, 31 is clipped at the right edge
+        LD I, row
+        LD V1, 60
+        LD V2, 31
+        DRW V1, V2, 1
+        LD V3, VF
+        LD I, pass
+        SE V3, 0
+        LD I, fail
+        CALL mark
+
+; 8 at 20, 30 is clipped at the bottom, wrapping would hit the marks at the top
+        LD V0, 8
+        LD F, V0
+        LD V1, 20
+        LD V2, 30
+        DRW V1, V2, 5
+        LD V3, VF
+        LD I, pass
+        SE V3, 0
+        LD I, fail
+        CALL mark
+
+done:   JP done
+
+row:    DB 0xFF
+
+        INCLUDE "common.asm"
@@ -0,0 +1,77 @@
+; 00E0 CLS, 00EE RET, 0NNN SYS, 1NNN JP, 2NNN CALL, BNNN JP V0
+
+; CLS erases a sprite, so drawing it again does not collide
+        LD I, fail
+        DRW VA, VB, 6
+        CLS
+        DRW VA, VB, 6
+        LD V0, VF
+        DRW VA, VB, 6
+        LD I, pass
+        SE V0, 0
+        LD I, fail
+        CALL mark
+
+; JP skips the instruction after it
+        LD V0, 0
+        JP jp_target
+        LD V0, 1
+jp_target:
+        LD I, pass
+        SE V0, 0
+        LD I, fail
+        CALL mark
+
+; RET continues after the CALL
+        LD V0, 0
+        CALL set_v0
+        ADD V0, 1
+        LD I, pass
+        SE V0, 8
+        LD I, fail
+        CALL mark
+
+; Nested calls return in order
+        LD V0, 0
+        CALL nest1
+        LD I, pass
+        SE V0, 0x12
+        LD I, fail
+        CALL mark
+
+; JP V0 adds V0 to the address
+        LD V0, 4
+        LD V1, 0
+        JP V0, jtab
+jtab:   LD V1, 1
+        LD V1, 2
+        ADD V1, 0x10
+        ADD V1, 0x20
+        LD I, pass
+        SE V1, 0x30
+        LD I, fail
+        CALL mark
+
+; SYS is ignored
+        LD V0, 5
+        SYS 0x123
+        ADD V0, 1
+        LD I, pass
+        SE V0, 6
+        LD I, fail
+        CALL mark
+
+done:   JP done
+
+set_v0: LD V0, 7
+        RET
+
+nest1:  ADD V0, 1
+        CALL nest2
+        ADD V0, 0x10
+        RET
+
+nest2:  ADD V0, 1
+        RET
+
+        INCLUDE "common.asm"
@@ -0,0 +1,108 @@
+; ANNN LD I, FX1E ADD I, FX29 LD F, FX33 LD B, FX55 LD [I], FX65 LD Vx, [I]
+; with the COSMAC VIP quirk: loads and stores leave I after the last register.
+
+        LD I, data
+        LD V0, [I]
+        LD V3, V0
+        LD I, pass
+        SE V3, 0x11
+        LD I, fail
+        CALL mark
+
+; The load above moved I on by one
+        LD I, data
+        LD V0, [I]
+        LD V0, [I]
+        LD V3, V0
+        LD I, pass
+        SE V3, 0x22
+        LD I, fail
+        CALL mark
+
+        LD I, data
+        LD V2, 3
+        ADD I, V2
+        LD V0, [I]
+        LD V3, V0
+        LD I, pass
+        SE V3, 0x44
+        LD I, fail
+        CALL mark
+
+; Font glyphs, the first row of 1 and the second row of A
+        LD V2, 1
+        LD F, V2
+        LD V0, [I]
+        LD V3, V0
+        LD I, pass
+        SE V3, 0x20
+        LD I, fail
+        CALL mark
+
+        LD V2, 0xA
+        LD F, V2
+        LD V1, [I]
+        LD I, pass
+        SE V1, 0x90
+        LD I, fail
+        CALL mark
+
+        LD V2, 234
+        LD I, scratch
+        LD B, V2
+        LD I, scratch
+        LD V2, [I]
+        LD V3, V0
+        LD V4, V1
+        LD V5, V2
+        LD I, pass
+        SE V3, 2
+        LD I, fail
+        CALL mark
+        LD I, pass
+        SE V4, 3
+        LD I, fail
+        CALL mark
+        LD I, pass
+        SE V5, 4
+        LD I, fail
+        CALL mark
+
+; Store three registers, I ends up at scratch + 3
+        LD V0, 0xA1
+        LD V1, 0xB2
+        LD V2, 0xC3
+        LD I, scratch
+        LD [I], V2
+        LD V0, [I]
+        LD V3, V0
+        LD I, pass
+        SE V3, 0x99
+        LD I, fail
+        CALL mark
+
+        LD I, scratch
+        LD V2, [I]
+        LD V3, V0
+        LD V4, V1
+        LD V5, V2
+        LD I, pass
+        SE V3, 0xA1
+        LD I, fail
+        CALL mark
+        LD I, pass
+        SE V4, 0xB2
+        LD I, fail
+        CALL mark
+        LD I, pass
+        SE V5, 0xC3
+        LD I, fail
+        CALL mark
+
+done:   JP done
+
+data:   DB 0x11, 0x22, 0x33, 0x44, 0x55
+scratch:
+        DB 0, 0, 0, 0x99
+
+        INCLUDE "common.asm"
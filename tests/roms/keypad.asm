@@ -0,0 +1,49 @@
+; EX9E SKP, EXA1 SKNP, FX0A LD Vx, K.
+; The test presses 5 for frames 2-3 and holds 9 from frame 10 to 39.
+
+        LD V0, K
+        LD V3, V0
+        LD I, pass
+        SE V3, 5
+        LD I, fail
+        CALL mark
+
+; 5 has been released
+        LD V1, 5
+        LD V2, 0
+        SKNP V1
+        LD V2, 1
+        LD I, pass
+        SE V2, 0
+        LD I, fail
+        CALL mark
+
+; Reaching the mark needs SKP to see 9
+wait9:  LD V1, 9
+        SKP V1
+        JP wait9
+        LD I, pass
+        CALL mark
+
+; 9 is still held
+        LD V2, 0
+        SKNP V1
+        LD V2, 1
+        LD I, pass
+        SE V2, 1
+        LD I, fail
+        CALL mark
+
+; 3 was never pressed
+        LD V1, 3
+        LD V2, 0
+        SKP V1
+        LD V2, 1
+        LD I, pass
+        SE V2, 1
+        LD I, fail
+        CALL mark
+
+done:   JP done
+
+        INCLUDE "common.asm"
@@ -0,0 +1,39 @@
+; 6XNN LD, 7XNN ADD, 8XY0 LD
+
+        LD V0, 0x2A
+        LD I, pass
+        SE V0, 0x2A
+        LD I, fail
+        CALL mark
+
+; ADD wraps around and leaves VF alone
+        LD V1, 0xF8
+        LD VF, 0x55
+        ADD V1, 0x10
+        LD V2, VF
+        LD I, pass
+        SE V1, 0x08
+        LD I, fail
+        CALL mark
+        LD I, pass
+        SE V2, 0x55
+        LD I, fail
+        CALL mark
+
+        LD V3, V0
+        LD I, pass
+        SE V3, 0x2A
+        LD I, fail
+        CALL mark
+
+; LD into VF and back
+        LD VF, 0x99
+        LD V4, VF
+        LD I, pass
+        SE V4, 0x99
+        LD I, fail
+        CALL mark
+
+done:   JP done
+
+        INCLUDE "common.asm"
@@ -0,0 +1,43 @@
+; CXNN RND, the mask is applied to the random byte
+
+        RND V0, 0
+        LD I, pass
+        SE V0, 0
+        LD I, fail
+        CALL mark
+
+        LD V3, 8
+masked: RND V0, 0x0F
+        LD V1, 0xF0
+        AND V1, V0
+        SE V1, 0
+        JP masked_fail
+        ADD V3, -1
+        SE V3, 0
+        JP masked
+        LD I, pass
+        CALL mark
+        JP varies
+
+masked_fail:
+        LD I, fail
+        CALL mark
+
+; Eight more bytes are not all the same as the first
+varies: RND V1, 0xFF
+        LD V4, 0
+        LD V3, 8
+again:  RND V0, 0xFF
+        SE V0, V1
+        LD V4, 1
+        ADD V3, -1
+        SE V3, 0
+        JP again
+        LD I, pass
+        SE V4, 1
+        LD I, fail
+        CALL mark
+
+done:   JP done
+
+        INCLUDE "common.asm"
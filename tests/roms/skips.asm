@@ -0,0 +1,74 @@
+; 3XNN SE, 4XNN SNE, 5XY0 SE, 9XY0 SNE, each taken and not taken.
+; V2 stays 0 when the skip is taken and becomes 1 otherwise.
+
+        LD V0, 0x42
+        LD V1, 0x42
+        LD V3, 0x17
+
+        LD V2, 0
+        SE V0, 0x42
+        LD V2, 1
+        LD I, pass
+        SE V2, 0
+        LD I, fail
+        CALL mark
+
+        LD V2, 0
+        SE V0, 0x43
+        LD V2, 1
+        LD I, pass
+        SE V2, 1
+        LD I, fail
+        CALL mark
+
+        LD V2, 0
+        SNE V0, 0x43
+        LD V2, 1
+        LD I, pass
+        SE V2, 0
+        LD I, fail
+        CALL mark
+
+        LD V2, 0
+        SNE V0, 0x42
+        LD V2, 1
+        LD I, pass
+        SE V2, 1
+        LD I, fail
+        CALL mark
+
+        LD V2, 0
+        SE V0, V1
+        LD V2, 1
+        LD I, pass
+        SE V2, 0
+        LD I, fail
+        CALL mark
+
+        LD V2, 0
+        SE V0, V3
+        LD V2, 1
+        LD I, pass
+        SE V2, 1
+        LD I, fail
+        CALL mark
+
+        LD V2, 0
+        SNE V0, V3
+        LD V2, 1
+        LD I, pass
+        SE V2, 0
+        LD I, fail
+        CALL mark
+
+        LD V2, 0
+        SNE V0, V1
+        LD V2, 1
+        LD I, pass
+        SE V2, 1
+        LD I, fail
+        CALL mark
+
+done:   JP done
+
+        INCLUDE "common.asm"
@@ -0,0 +1,35 @@
+; FX07 LD Vx, DT, FX15 LD DT, Vx, FX18 LD ST, Vx
+
+; The delay timer reads back what was written within the same frame
+        LD V0, 30
+        LD DT, V0
+        LD V1, DT
+        LD I, pass
+        SE V1, 30
+        LD I, fail
+        CALL mark
+
+; It counts down once per frame, reaching each mark passes
+        LD V0, 5
+        LD DT, V0
+wait4:  LD V1, DT
+        SE V1, 4
+        JP wait4
+        LD I, pass
+        CALL mark
+
+wait0:  LD V1, DT
+        SE V1, 0
+        JP wait0
+        LD I, pass
+        CALL mark
+
+; The sound timer can be set, it is not readable
+        LD V0, 2
+        LD ST, V0
+        LD I, pass
+        CALL mark
+
+done:   JP done
+
+        INCLUDE "common.asm"